@@ -0,0 +1,86 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::atomic::{lock, write_atomic, FileLock};
+
+// Aliases and what they stand for: log aliases map to the log's canonical path,
+// sample aliases to the sample id
+#[derive(Serialize, Deserialize, Default)]
+struct AliasMap {
+    logs: BTreeMap<String, PathBuf>,
+    samples: BTreeMap<String, String>,
+}
+
+#[derive(Default)]
+struct Aliases {
+    map: AliasMap,
+    log_aliases: BTreeMap<PathBuf, String>,
+    sample_aliases: BTreeMap<String, String>,
+    changed: bool,
+}
+
+// Stable stand-ins for log filenames and sample ids, for output shared outside the
+// team; the mapping file records every alias handed out, so later scans reuse them
+// and aliases can be looked up again. Aliases are printed as soon as they're handed
+// out, so the mapping file stays locked from load until this is dropped: concurrent
+// runs with the same file wait their turn rather than give one alias two meanings
+pub struct IdAliases {
+    path: PathBuf,
+    aliases: Mutex<Aliases>,
+    _lock: FileLock,
+}
+
+impl IdAliases {
+    pub fn load(path: &Path) -> Result<Self> {
+        let lock = lock(path)?;
+        let map: AliasMap = match std::fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).with_context(|| format!("Failed to parse alias map {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => AliasMap::default(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let log_aliases = map.logs.iter().map(|(alias, log)| (log.clone(), alias.clone())).collect();
+        let sample_aliases = map.samples.iter().map(|(alias, id)| (id.clone(), alias.clone())).collect();
+        let aliases = Aliases { map, log_aliases, sample_aliases, changed: false };
+        Ok(Self { path: path.to_path_buf(), aliases: Mutex::new(aliases), _lock: lock })
+    }
+
+    // The log as a bare aliased filename, keeping its extension
+    pub fn log(&self, log_path: &Path) -> PathBuf {
+        let canonical = log_path.canonicalize().unwrap_or_else(|_| log_path.to_path_buf());
+        let mut aliases = self.aliases.lock().unwrap();
+        if let Some(alias) = aliases.log_aliases.get(&canonical) {
+            return PathBuf::from(alias);
+        }
+        let extension = log_path.extension().map(|ext| format!(".{}", ext.to_string_lossy())).unwrap_or_default();
+        let alias = format!("log-{}{}", aliases.map.logs.len() + 1, extension);
+        aliases.map.logs.insert(alias.clone(), canonical.clone());
+        aliases.log_aliases.insert(canonical, alias.clone());
+        aliases.changed = true;
+        PathBuf::from(alias)
+    }
+
+    // The same id is given the same alias in every log, as Inspect reuses a dataset's ids across runs
+    pub fn sample(&self, sample_id: &str) -> String {
+        let mut aliases = self.aliases.lock().unwrap();
+        if let Some(alias) = aliases.sample_aliases.get(sample_id) {
+            return alias.clone();
+        }
+        let alias = format!("sample-{}", aliases.map.samples.len() + 1);
+        aliases.map.samples.insert(alias.clone(), sample_id.to_string());
+        aliases.sample_aliases.insert(sample_id.to_string(), alias.clone());
+        aliases.changed = true;
+        alias
+    }
+
+    // Write the mapping file, if any new aliases were handed out
+    pub fn save(&self) -> Result<()> {
+        let aliases = self.aliases.lock().unwrap();
+        if !aliases.changed {
+            return Ok(());
+        }
+        write_atomic(&self.path, &serde_json::to_vec_pretty(&aliases.map)?)
+    }
+}
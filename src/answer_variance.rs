@@ -0,0 +1,92 @@
+use colored::*;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::history::preview;
+use crate::inspect::{ChatMessageRole, EvalSample};
+
+// The final answer of an epoch: the answer a scorer extracted (the first by scorer
+// name), else the last assistant message, with whitespace collapsed
+pub fn final_answer(sample: &EvalSample) -> Option<String> {
+    let scored = sample.scores.iter().sorted_by(|a, b| a.0.cmp(b.0)).find_map(|(_, score)| score.answer.as_deref());
+    let answer = scored.or_else(|| {
+        let message = sample.messages.iter().flatten().rfind(|m| m.role == ChatMessageRole::Assistant)?;
+        Some(&*message.content)
+    })?;
+    Some(answer.split_whitespace().join(" "))
+}
+
+#[derive(Default)]
+struct Epochs {
+    // (epoch, final answer)
+    answers: Vec<(i64, Option<String>)>,
+    // Whether any epoch had a message passing the filters
+    matched: bool,
+}
+
+// Final answers of each sample's epochs, by log and sample id
+#[derive(Default)]
+pub struct AnswerVariance {
+    samples: BTreeMap<(PathBuf, String), Epochs>,
+}
+
+impl AnswerVariance {
+    pub fn add_sample(&mut self, log_path: &Path, sample: &EvalSample, matched: bool) {
+        let epochs = self.samples.entry((log_path.to_path_buf(), sample.id.clone())).or_default();
+        epochs.answers.push((sample.epoch, final_answer(sample)));
+        epochs.matched |= matched;
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        for (key, epochs) in other.samples {
+            let entry = self.samples.entry(key).or_default();
+            entry.answers.extend(epochs.answers);
+            entry.matched |= epochs.matched;
+        }
+        self
+    }
+
+    pub fn matched(&self) -> usize {
+        self.samples.values().filter(|epochs| epochs.matched).count()
+    }
+
+    // Matched samples run for more than one epoch
+    pub fn samples(&self) -> usize {
+        self.samples.values().filter(|epochs| epochs.matched && epochs.answers.len() > 1).count()
+    }
+
+    // Each sample whose epochs disagree, with its answers and the epochs that gave them,
+    // most common answer first
+    pub fn display(mut self) {
+        let mut disagreeing = 0;
+        for ((path, sample_id), epochs) in &mut self.samples {
+            if !epochs.matched || epochs.answers.len() < 2 {
+                continue;
+            }
+            epochs.answers.sort();
+            let groups = epochs.answers.iter().into_group_map_by(|(_, answer)| answer.as_deref());
+            if groups.len() < 2 {
+                continue;
+            }
+            disagreeing += 1;
+            let file = path.file_name().unwrap().to_string_lossy();
+            println!(
+                "\n{} sample {} | {}",
+                file.cyan(),
+                sample_id.yellow(),
+                format!("{} answers across {} epochs", groups.len(), epochs.answers.len()).red().bold()
+            );
+            let groups = groups.into_iter().sorted_by(|a, b| b.1.len().cmp(&a.1.len()).then(a.1[0].0.cmp(&b.1[0].0)));
+            for (answer, runs) in groups {
+                let answer = answer.map_or_else(|| "(no answer)".dimmed().to_string(), preview);
+                let label = if runs.len() == 1 { "epoch" } else { "epochs" };
+                let epochs = runs.iter().map(|(epoch, _)| epoch).join(", ");
+                println!("  {}  {}", format!("{} {}:", label, epochs).green(), answer);
+            }
+        }
+        let total = self.samples();
+        let share = 100.0 * disagreeing as f64 / total.max(1) as f64;
+        eprintln!("{} of {} samples with several epochs had epochs that disagree ({:.1}%)", disagreeing, total, share);
+    }
+}
@@ -0,0 +1,101 @@
+use colored::*;
+use itertools::Itertools;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::answer_variance::final_answer;
+use crate::inspect::EvalSample;
+use crate::stats::score_label;
+
+// One sample's final answer next to its target
+struct AnswerRow {
+    log: PathBuf,
+    sample_id: String,
+    epoch: i64,
+    target: String,
+    answer: Option<String>,
+    // Scorer -> score label
+    scores: BTreeMap<String, String>,
+}
+
+// Final answers against targets, for regrading by hand
+#[derive(Default)]
+pub struct AnswerTable {
+    rows: Vec<AnswerRow>,
+}
+
+// A field quoted as RFC 4180 asks, when it needs to be
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+impl AnswerTable {
+    pub fn add_sample(&mut self, log_path: &Path, sample: &EvalSample) {
+        self.rows.push(AnswerRow {
+            log: log_path.to_path_buf(),
+            sample_id: sample.id.clone(),
+            epoch: sample.epoch,
+            // Several acceptable answers are listed together
+            target: sample.target.join(" | "),
+            answer: final_answer(sample),
+            scores: sample.scores.iter().map(|(name, score)| (name.clone(), score_label(&score.value))).collect(),
+        });
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        self.rows.extend(other.rows);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    // Every scorer seen, so each gets its own column
+    fn scorers(&self) -> Vec<&str> {
+        self.rows.iter().flat_map(|row| row.scores.keys().map(String::as_str)).unique().sorted().collect()
+    }
+
+    fn sort(&mut self) {
+        self.rows.sort_by(|a, b| (&a.log, &a.sample_id, a.epoch).cmp(&(&b.log, &b.sample_id, b.epoch)));
+    }
+
+    // Rows of (log, sample_id, epoch, target, final_answer, score), with a score column per
+    // scorer (`score_<name>`) when there are several
+    pub fn write_csv(mut self) {
+        self.sort();
+        let scorers = self.scorers();
+        let score_columns = match scorers.as_slice() {
+            [_] => vec!["score".to_string()],
+            scorers => scorers.iter().map(|scorer| format!("score_{}", scorer)).collect(),
+        };
+        let header = ["log", "sample_id", "epoch", "target", "final_answer"].into_iter().map(String::from).chain(score_columns);
+        println!("{}", header.map(|column| csv_field(&column).into_owned()).join(","));
+        for row in &self.rows {
+            let log = row.log.display().to_string();
+            let epoch = row.epoch.to_string();
+            let fields = [log.as_str(), &row.sample_id, &epoch, &row.target, row.answer.as_deref().unwrap_or_default()];
+            let scores = scorers.iter().map(|scorer| row.scores.get(*scorer).map_or("", String::as_str));
+            println!("{}", fields.into_iter().chain(scores).map(csv_field).join(","));
+        }
+    }
+
+    pub fn display(mut self) {
+        self.sort();
+        for row in &self.rows {
+            let scores = row.scores.iter().map(|(name, score)| format!("{} {}", name, score)).join(", ");
+            println!("{}:{}:{}  {}", row.log.display().to_string().cyan(), row.sample_id, row.epoch, scores.dimmed());
+            println!("  target: {}", row.target.green());
+            println!("  answer: {}", row.answer.as_deref().unwrap_or("(none)"));
+        }
+    }
+}
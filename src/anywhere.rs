@@ -0,0 +1,89 @@
+use anyhow::Result;
+use colored::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::assembly::SampleEntries;
+use crate::encoding::Encoding;
+use crate::log_format::open_log;
+use crate::theme::Theme;
+
+// A sample where the pattern appeared somewhere, and the fields it appeared in
+#[derive(Serialize, Deserialize)]
+pub struct AnywhereMatch {
+    pub sample_id: String,
+    pub epoch: u32,
+    pub fields: Vec<String>,
+}
+
+// Top-level sample fields searched as a whole
+const FIELDS: &[&str] = &["input", "target", "output", "metadata", "scores", "attachments"];
+
+fn value_matches(value: &Value, regex: &Regex) -> bool {
+    match value {
+        Value::String(s) => regex.is_match(s),
+        Value::Number(n) => regex.is_match(&n.to_string()),
+        Value::Bool(b) => regex.is_match(&b.to_string()),
+        Value::Array(values) => values.iter().any(|v| value_matches(v, regex)),
+        Value::Object(map) => map.values().any(|v| value_matches(v, regex)),
+        Value::Null => false,
+    }
+}
+
+pub fn matching_fields(sample: &Value, extra_attachments: &[String], regex: &Regex) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+
+    // Messages are split into their content and the tool calls they make
+    let messages = sample.get("messages").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+    let (mut content_hit, mut tool_args_hit) = (false, false);
+    for message in messages.iter().filter_map(Value::as_object) {
+        for (key, value) in message {
+            if key == "tool_calls" {
+                tool_args_hit = tool_args_hit || value_matches(value, regex);
+            } else {
+                content_hit = content_hit || value_matches(value, regex);
+            }
+        }
+    }
+    if content_hit {
+        fields.push("messages");
+    }
+    if tool_args_hit {
+        fields.push("tool args");
+    }
+
+    for field in FIELDS {
+        let hit = sample.get(*field).is_some_and(|v| value_matches(v, regex))
+            || (*field == "attachments" && extra_attachments.iter().any(|a| regex.is_match(a)));
+        if hit {
+            fields.push(field);
+        }
+    }
+    fields
+}
+
+pub fn search_anywhere(log_path: &Path, samples: &[SampleEntries], regex: &Regex) -> Result<Vec<AnywhereMatch>> {
+    let mut archive = open_log(log_path)?;
+
+    let mut matches = Vec::new();
+    for sample in samples {
+        let attachments = sample.read_attachments(&mut archive, None, Encoding::Utf8)?.into_values().collect::<Vec<_>>();
+        let json: Value = serde_json::from_reader(std::io::BufReader::new(sample.reader(&mut archive)?))?;
+        let fields = matching_fields(&json, &attachments, regex);
+        if !fields.is_empty() {
+            matches.push(AnywhereMatch {
+                sample_id: sample.sample_id.clone(),
+                epoch: sample.epoch,
+                fields: fields.into_iter().map(String::from).collect(),
+            });
+        }
+    }
+    Ok(matches)
+}
+
+pub fn display_anywhere_match(log_file: &Path, anywhere_match: &AnywhereMatch, theme: &Theme) {
+    let fields = format!("[{}]", anywhere_match.fields.join(", ")).bold();
+    println!("{}", theme.header(&log_file.file_name().unwrap().to_string_lossy(), &anywhere_match.sample_id, anywhere_match.epoch as i64, fields));
+}
@@ -0,0 +1,193 @@
+use anyhow::{bail, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Cursor, Read, Seek};
+use std::str::FromStr;
+use zip::ZipArchive;
+
+use crate::content::truncate_field_of;
+use crate::encoding::Encoding;
+
+// How sample entries are named within an archive. `sample` matches a sample
+// entry (optionally one shard of a sample split across several entries) with
+// named groups `id`, `epoch` (defaults to 1 when absent) and `part`;
+// `attachment` matches an attachment stored in its own entry alongside the
+// sample, with groups `id`, `epoch` and `hash`
+#[derive(Debug, Clone)]
+pub struct SampleLayout {
+    sample: Regex,
+    attachment: Option<Regex>,
+}
+
+impl SampleLayout {
+    fn new(sample: &str, attachment: Option<&str>) -> Self {
+        Self {
+            sample: Regex::new(sample).expect("Failed to compile regex"),
+            attachment: attachment.map(|re| Regex::new(re).expect("Failed to compile regex")),
+        }
+    }
+
+    fn sample_key(&self, name: &str) -> Option<((String, u32), u32)> {
+        let caps = self.sample.captures(name)?;
+        let epoch = caps.name("epoch").map_or(Some(1), |e| e.as_str().parse().ok())?;
+        // The unsharded entry sorts before any numbered part
+        let part = caps.name("part").map_or(Some(0), |p| p.as_str().parse::<u32>().ok().map(|p| p + 1))?;
+        Some(((caps["id"].to_string(), epoch), part))
+    }
+
+    fn attachment_key(&self, name: &str) -> Option<((String, u32), String)> {
+        let caps = self.attachment.as_ref()?.captures(name)?;
+        let epoch = caps.name("epoch").map_or(Some(1), |e| e.as_str().parse().ok())?;
+        Some(((caps["id"].to_string(), epoch), caps["hash"].to_string()))
+    }
+}
+
+impl FromStr for SampleLayout {
+    type Err = anyhow::Error;
+
+    // A custom layout is a regex for sample entries; attachments aren't recognized
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let sample = Regex::new(s)?;
+        if !sample.capture_names().any(|name| name == Some("id")) {
+            bail!("sample layout must have an `id` capture group, e.g. '^samples/(?P<id>.+)_epoch_(?P<epoch>\\d+)\\.json$'");
+        }
+        Ok(Self { sample, attachment: None })
+    }
+}
+
+lazy_static! {
+    // Layouts tried when none is given, the first being the one Inspect writes
+    static ref KNOWN_LAYOUTS: Vec<SampleLayout> = vec![
+        SampleLayout::new(
+            r"^samples/(?P<id>.*)_epoch_(?P<epoch>\d+)(?:\.part(?P<part>\d+))?\.json$",
+            Some(r"^samples/(?P<id>.*)_epoch_(?P<epoch>\d+)\.attachments/(?P<hash>.+)$"),
+        ),
+        // One directory per sample
+        SampleLayout::new(
+            r"^samples/(?P<id>.+)/epoch_(?P<epoch>\d+)(?:\.part(?P<part>\d+))?\.json$",
+            Some(r"^samples/(?P<id>.+)/epoch_(?P<epoch>\d+)\.attachments/(?P<hash>.+)$"),
+        ),
+        // Single-epoch logs without an epoch suffix
+        SampleLayout::new(r"^samples/(?P<id>[^/]+)\.json$", None),
+    ];
+}
+
+// The known layout matching the most entries, if any matches at all
+pub fn detect_layout(file_names: &[&str]) -> Option<&'static SampleLayout> {
+    KNOWN_LAYOUTS
+        .iter()
+        .map(|layout| (layout, file_names.iter().filter(|name| layout.sample_key(name).is_some()).count()))
+        .filter(|(_, count)| *count > 0)
+        // max_by_key keeps the last maximum, so prefer earlier layouts on ties
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(layout, _)| layout)
+}
+
+// All of the zip entries that make up a single sample/epoch
+#[derive(Debug, Clone)]
+pub struct SampleEntries {
+    pub sample_id: String,
+    pub epoch: u32,
+    // The sample JSON, in order: the unsharded entry first, then shards by part number
+    pub entries: Vec<String>,
+    // Attachment hash -> entry name
+    pub attachments: Vec<(String, String)>,
+}
+
+impl SampleEntries {
+    // Name used to refer to the sample in messages
+    pub fn name(&self) -> &str {
+        &self.entries[0]
+    }
+
+    // Read the sample's JSON, stitching shards back together
+    pub fn reader<'a, R: Read + Seek>(&self, archive: &'a mut ZipArchive<R>) -> Result<Box<dyn Read + 'a>> {
+        if self.entries.len() == 1 {
+            return Ok(Box::new(crate::resources::decompressing(archive.by_name(&self.entries[0])?)));
+        }
+        let mut buffer = Vec::new();
+        for entry in &self.entries {
+            crate::resources::decompressing(archive.by_name(entry)?).read_to_end(&mut buffer)?;
+        }
+        Ok(Box::new(Cursor::new(buffer)))
+    }
+
+    // Attachments by hash, decoded per `encoding`; with `max_bytes`, only that much of each is read
+    pub fn read_attachments<R: Read + Seek>(&self, archive: &mut ZipArchive<R>, max_bytes: Option<usize>, encoding: Encoding) -> Result<HashMap<String, String>> {
+        let mut attachments = HashMap::new();
+        for (hash, entry) in &self.attachments {
+            let file = archive.by_name(entry)?;
+            let size = file.size() as usize;
+            let mut bytes = Vec::new();
+            crate::resources::decompressing(file).take(max_bytes.map_or(u64::MAX, |max| max as u64)).read_to_end(&mut bytes)?;
+            let mut content = match String::from_utf8(bytes) {
+                Ok(content) if encoding != Encoding::Latin1 => content,
+                Ok(content) => encoding.decode(content.into_bytes()),
+                Err(e) if encoding != Encoding::Utf8 => encoding.decode(e.into_bytes()),
+                // Cut short mid-character
+                Err(e) if e.as_bytes().len() < size && e.utf8_error().error_len().is_none() => {
+                    let valid = e.utf8_error().valid_up_to();
+                    let mut bytes = e.into_bytes();
+                    bytes.truncate(valid);
+                    String::from_utf8(bytes)?
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(max) = max_bytes {
+                truncate_field_of(&mut content, max, size);
+            }
+            attachments.insert(hash.clone(), content);
+        }
+        Ok(attachments)
+    }
+}
+
+#[derive(Default)]
+struct Pieces {
+    // (part number, entry name)
+    entries: Vec<(u32, String)>,
+    attachments: Vec<(String, String)>,
+}
+
+// JSON entries other than the log's own metadata, which might be samples in an
+// unrecognized layout
+pub fn unrecognized_entries(file_names: &[&str]) -> usize {
+    const METADATA: [&str; 3] = ["header.json", "reductions.json", "summaries.json"];
+    file_names
+        .iter()
+        .filter(|name| name.ends_with(".json") && !METADATA.contains(name) && !name.starts_with("_journal/"))
+        .count()
+}
+
+// Group an archive's entry names into samples, detecting the layout if none is given
+pub fn sample_entries(file_names: &[&str], layout: Option<&SampleLayout>) -> Vec<SampleEntries> {
+    let Some(layout) = layout.or_else(|| detect_layout(file_names)) else {
+        return Vec::new();
+    };
+    let mut samples: BTreeMap<(String, u32), Pieces> = BTreeMap::new();
+
+    for &name in file_names {
+        if let Some((key, part)) = layout.sample_key(name) {
+            samples.entry(key).or_default().entries.push((part, name.to_string()));
+        } else if let Some((key, hash)) = layout.attachment_key(name) {
+            samples.entry(key).or_default().attachments.push((hash, name.to_string()));
+        }
+    }
+
+    samples
+        .into_iter()
+        // Attachments without any sample JSON can't be searched
+        .filter(|(_, pieces)| !pieces.entries.is_empty())
+        .map(|((sample_id, epoch), mut pieces)| {
+            pieces.entries.sort();
+            SampleEntries {
+                sample_id,
+                epoch,
+                entries: pieces.entries.into_iter().map(|(_, name)| name).collect(),
+                attachments: pieces.attachments,
+            }
+        })
+        .collect()
+}
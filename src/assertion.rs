@@ -0,0 +1,322 @@
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Names that can appear in an --assert expression
+pub const VARIABLES: [&str; 6] = ["matches", "matched_samples", "total_samples", "selected_samples", "messages", "files"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Number(f64),
+    Variable(String),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, vars: &BTreeMap<&str, f64>) -> f64 {
+        let truth = |b: bool| if b { 1.0 } else { 0.0 };
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Variable(name) => vars[name.as_str()],
+            Expr::Neg(e) => -e.eval(vars),
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(vars), r.eval(vars));
+                // An undefined value (0 / 0) makes anything built on it undefined, so
+                // `x != 1` can't hold because x is NaN
+                if l.is_nan() || r.is_nan() {
+                    return f64::NAN;
+                }
+                match op {
+                    Op::Or => truth(l != 0.0 || r != 0.0),
+                    Op::And => truth(l != 0.0 && r != 0.0),
+                    Op::Eq => truth(l == r),
+                    Op::Ne => truth(l != r),
+                    Op::Lt => truth(l < r),
+                    Op::Le => truth(l <= r),
+                    Op::Gt => truth(l > r),
+                    Op::Ge => truth(l >= r),
+                    Op::Add => l + r,
+                    Op::Sub => l - r,
+                    Op::Mul => l * r,
+                    Op::Div => l / r,
+                }
+            }
+        }
+    }
+
+    fn variables<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Expr::Number(_) => {}
+            Expr::Variable(name) => {
+                if !out.contains(&name.as_str()) {
+                    out.push(name);
+                }
+            }
+            Expr::Neg(e) => e.variables(out),
+            Expr::Binary(_, l, r) => {
+                l.variables(out);
+                r.variables(out);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(Op),
+    Open,
+    Close,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            (c, _) if c.is_ascii_digit() || c == '.' => {
+                let end = (i..chars.len()).find(|&j| !(chars[j].is_ascii_digit() || chars[j] == '.')).unwrap_or(chars.len());
+                let number = chars[i..end].iter().collect::<String>();
+                let number = number.parse().map_err(|_| anyhow!("invalid number '{}'", number))?;
+                (Token::Number(number), end - i)
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let end = (i..chars.len()).find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_')).unwrap_or(chars.len());
+                (Token::Ident(chars[i..end].iter().collect()), end - i)
+            }
+            ('|', Some('|')) => (Token::Op(Op::Or), 2),
+            ('&', Some('&')) => (Token::Op(Op::And), 2),
+            ('=', Some('=')) => (Token::Op(Op::Eq), 2),
+            ('!', Some('=')) => (Token::Op(Op::Ne), 2),
+            ('<', Some('=')) => (Token::Op(Op::Le), 2),
+            ('>', Some('=')) => (Token::Op(Op::Ge), 2),
+            ('<', _) => (Token::Op(Op::Lt), 1),
+            ('>', _) => (Token::Op(Op::Gt), 1),
+            ('+', _) => (Token::Op(Op::Add), 1),
+            ('-', _) => (Token::Op(Op::Sub), 1),
+            ('*', _) => (Token::Op(Op::Mul), 1),
+            ('/', _) => (Token::Op(Op::Div), 1),
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            (c, _) => bail!("unexpected '{}'", c),
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+// Recursive descent over the tokens, loosest-binding operators first
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    const LEVELS: [&'static [Op]; 4] = [
+        &[Op::Or],
+        &[Op::And],
+        &[Op::Eq, Op::Ne, Op::Lt, Op::Le, Op::Gt, Op::Ge],
+        &[Op::Add, Op::Sub],
+    ];
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr> {
+        if level == Self::LEVELS.len() {
+            return self.product();
+        }
+        let mut left = self.binary(level + 1)?;
+        while let Some(Token::Op(op)) = self.peek().cloned() {
+            if !Self::LEVELS[level].contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn product(&mut self) -> Result<Expr> {
+        let mut left = self.unary()?;
+        while let Some(Token::Op(op @ (Op::Mul | Op::Div))) = self.peek().cloned() {
+            self.pos += 1;
+            let right = self.unary()?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let token = self.peek().cloned().ok_or_else(|| anyhow!("unexpected end of expression"))?;
+        self.pos += 1;
+        match token {
+            Token::Op(Op::Sub) => Ok(Expr::Neg(Box::new(self.unary()?))),
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Ident(name) if VARIABLES.contains(&name.as_str()) => Ok(Expr::Variable(name)),
+            Token::Ident(name) => bail!("unknown variable '{}' (expected one of {})", name, VARIABLES.join(", ")),
+            Token::Open => {
+                let inner = self.binary(0)?;
+                match self.peek() {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(inner)
+                    }
+                    _ => bail!("missing ')'"),
+                }
+            }
+            token => bail!("unexpected {:?}", token),
+        }
+    }
+}
+
+// A condition on the scan's totals, checked once it finishes
+#[derive(Debug, Clone)]
+pub struct Assertion {
+    source: String,
+    expr: Expr,
+}
+
+impl FromStr for Assertion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { tokens: tokenize(s)?, pos: 0 };
+        let expr = parser.binary(0)?;
+        if let Some(token) = parser.peek() {
+            bail!("unexpected {:?}", token);
+        }
+        Ok(Self { source: s.trim().to_string(), expr })
+    }
+}
+
+// An assertion that didn't hold, with the values it was evaluated against
+pub struct Violation<'a> {
+    assertion: &'a Assertion,
+    values: Vec<(&'a str, f64)>,
+}
+
+impl fmt::Display for Violation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = self.values.iter().map(|(name, value)| format!("{} = {}", name, value)).collect::<Vec<_>>();
+        write!(f, "assertion failed: {}", self.assertion.source)?;
+        if !values.is_empty() {
+            write!(f, " ({})", values.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Assertion {
+    pub fn check<'a>(&'a self, vars: &BTreeMap<&str, f64>) -> Option<Violation<'a>> {
+        let result = self.expr.eval(vars);
+        // NaN (e.g. from dividing by zero samples) fails the assertion too
+        if result != 0.0 && !result.is_nan() {
+            return None;
+        }
+        let mut names = Vec::new();
+        self.expr.variables(&mut names);
+        Some(Violation { assertion: self, values: names.into_iter().map(|name| (name, vars[name])).collect() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(matches: f64, matched_samples: f64, total_samples: f64) -> BTreeMap<&'static str, f64> {
+        BTreeMap::from([
+            ("matches", matches),
+            ("matched_samples", matched_samples),
+            ("total_samples", total_samples),
+            ("selected_samples", total_samples),
+            ("messages", 10.0),
+            ("files", 2.0),
+        ])
+    }
+
+    fn holds(expr: &str, vars: &BTreeMap<&str, f64>) -> bool {
+        expr.parse::<Assertion>().unwrap().check(vars).is_none()
+    }
+
+    fn parse_error(expr: &str) -> String {
+        expr.parse::<Assertion>().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn precedence() {
+        let vars = vars(3.0, 1.0, 4.0);
+        assert!(holds("1 + 2 * 3 == 7", &vars));
+        assert!(holds("(1 + 2) * 3 == 9", &vars));
+        assert!(holds("10 - 4 - 3 == 3", &vars));
+        assert!(holds("12 / 2 / 3 == 2", &vars));
+        assert!(holds("-2 * 3 == -6", &vars));
+        assert!(holds("matches + 1 > files * 1.5", &vars));
+        // && binds tighter than ||
+        assert!(holds("matches == 0 && files == 0 || messages == 10", &vars));
+        assert!(!holds("matches == 0 && (files == 0 || messages == 10)", &vars));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let empty = vars(0.0, 0.0, 0.0);
+        assert!(!holds("matched_samples / total_samples < 0.01", &empty));
+        assert!(!holds("matched_samples / total_samples", &empty));
+        assert!(!holds("matched_samples / total_samples != 1", &empty));
+        assert!(!holds("matched_samples / total_samples == 0 || files == 2", &empty));
+        assert!(holds("matched_samples / total_samples < 0.5", &vars(0.0, 1.0, 4.0)));
+    }
+
+    #[test]
+    fn unknown_variable() {
+        let error = parse_error("matchs == 0");
+        assert!(error.contains("unknown variable 'matchs'"), "{}", error);
+        assert!(error.contains("matched_samples"), "{}", error);
+    }
+
+    #[test]
+    fn leftover_tokens() {
+        assert_eq!(parse_error("matches == 0 )"), "unexpected Close");
+        assert_eq!(parse_error("matches 0"), "unexpected Number(0.0)");
+        assert_eq!(parse_error("(matches == 0"), "missing ')'");
+        assert_eq!(parse_error("matches =="), "unexpected end of expression");
+        assert_eq!(parse_error("matches = 0"), "unexpected '='");
+    }
+
+    #[test]
+    fn violation_lists_values() {
+        let vars = vars(5.0, 2.0, 4.0);
+        let assertion = "matches == 0 && files > 0".parse::<Assertion>().unwrap();
+        let violation = assertion.check(&vars).unwrap();
+        assert_eq!(violation.to_string(), "assertion failed: matches == 0 && files > 0 (matches = 5, files = 2)");
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+// Write a file by renaming a fully written temp file over it, so concurrent
+// readers and writers only ever see a complete old or new version
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+    let mut file = NamedTempFile::new_in(dir).with_context(|| format!("Failed to create a temp file in {}", dir.display()))?;
+    file.write_all(contents)?;
+    file.as_file().sync_all()?;
+    file.persist(path).with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+// An exclusive advisory lock on `<path>.lock`, released when dropped. Atomic writes
+// alone let two runs that read, update and rewrite the same file each drop the other's
+// changes; runs holding this lock take turns instead
+pub struct FileLock {
+    _file: File,
+}
+
+pub fn lock(path: &Path) -> Result<FileLock> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    let lock_path = PathBuf::from(lock_path);
+    if let Some(dir) = lock_path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open lock file {}", lock_path.display()))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            eprintln!("{} waiting for another run to release {}", "note:".yellow().bold(), lock_path.display());
+            file.lock().with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+        }
+        Err(TryLockError::Error(e)) => return Err(e).with_context(|| format!("Failed to lock {}", lock_path.display())),
+    }
+    Ok(FileLock { _file: file })
+}
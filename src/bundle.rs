@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::assembly::SampleEntries;
+use crate::export::ScanMetadata;
+use crate::log_format::open_log;
+
+// A .tar.gz of matched samples as they are stored in their logs: for each log, a
+// directory with its header and the sample and attachment entries of its matches,
+// plus a manifest of the scan that picked them
+pub struct Bundle {
+    builder: tar::Builder<GzEncoder<File>>,
+    mtime: u64,
+}
+
+impl Bundle {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mtime = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        Ok(Self { builder: tar::Builder::new(GzEncoder::new(file, Compression::default())), mtime })
+    }
+
+    fn append(&mut self, path: &Path, contents: &[u8]) -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(self.mtime);
+        self.builder.append_data(&mut header, path, contents)?;
+        Ok(())
+    }
+
+    // Copy the header and the samples' entries, byte for byte, under `dir`
+    pub fn add_log(&mut self, log_path: &Path, dir: &Path, samples: &[SampleEntries]) -> Result<()> {
+        let mut archive = open_log(log_path)?;
+        let headers = ["header.json", "_journal/start.json"].into_iter().filter(|name| archive.file_names().any(|n| n == *name));
+        let headers = headers.map(String::from).collect::<Vec<_>>();
+        let entries = samples.iter().flat_map(|sample| sample.entries.iter().chain(sample.attachments.iter().map(|(_, entry)| entry)));
+        for name in headers.iter().chain(entries) {
+            let mut contents = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut contents).with_context(|| format!("Failed to read {} from {}", name, log_path.display()))?;
+            self.append(&dir.join(name), &contents)?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self, metadata: &ScanMetadata) -> Result<()> {
+        self.append(Path::new("manifest.json"), &serde_json::to_vec_pretty(metadata)?)?;
+        self.builder.into_inner()?.finish()?;
+        Ok(())
+    }
+}
+
+// Where a log's entries go in the bundle: its path below the scanned directory, so
+// logs with the same name in different runs don't collide
+pub fn log_dir(root: &Path, log_path: &Path) -> PathBuf {
+    match log_path.strip_prefix(root) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+        _ => PathBuf::from(log_path.file_name().unwrap_or_default()),
+    }
+}
@@ -0,0 +1,76 @@
+use colored::*;
+use itertools::Itertools;
+use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::inspect::{ChatMessage, ChatMessageRole, EvalSample};
+
+// Render a word-level diff, like `git diff --word-diff`: removed words in red, added
+// words in green, and `[-...-]`/`{+...+}` markers when color is disabled
+pub fn word_diff(old: &str, new: &str) -> String {
+    let diff = TextDiff::from_words(old, new);
+    let mut out = String::new();
+    // Consecutive changes of the same kind are rendered as a single run
+    let runs = diff.iter_all_changes().group_by(|change| change.tag());
+    for (tag, changes) in &runs {
+        let text = changes.map(|change| change.value()).collect::<String>();
+        out.push_str(&render_run(tag, &text));
+    }
+    out
+}
+
+fn render_run(tag: ChangeTag, text: &str) -> String {
+    let plain = !colored::control::SHOULD_COLORIZE.should_colorize();
+    match tag {
+        ChangeTag::Equal => text.to_string(),
+        ChangeTag::Delete if plain => format!("[-{}-]", text),
+        ChangeTag::Insert if plain => format!("{{+{}+}}", text),
+        ChangeTag::Delete => text.red().strikethrough().to_string(),
+        ChangeTag::Insert => text.green().to_string(),
+    }
+}
+
+fn assistant_messages(sample: &EvalSample) -> Vec<&ChatMessage> {
+    sample.messages.iter().flatten().filter(|m| m.role == ChatMessageRole::Assistant).collect()
+}
+
+fn display_sample_diff(sample_id: &str, epoch: i64, left: &EvalSample, right: &EvalSample) {
+    let (left_messages, right_messages) = (assistant_messages(left), assistant_messages(right));
+    let header = format!("sample {} epoch {}", sample_id.yellow(), epoch.to_string().green());
+    let contents = |messages: &[&ChatMessage]| messages.iter().map(|m| m.content.clone()).collect::<Vec<_>>();
+    if contents(&left_messages) == contents(&right_messages) {
+        println!("{} | {}", header, "identical".dimmed());
+        return;
+    }
+
+    println!("\n{} | {}", header, "assistant messages differ".red().bold());
+    for index in 0..left_messages.len().max(right_messages.len()) {
+        let old = left_messages.get(index).map_or("", |m| &*m.content);
+        let new = right_messages.get(index).map_or("", |m| &*m.content);
+        if old != new {
+            println!("{}", format!("[assistant #{}]", index).green().bold());
+            println!("{}", word_diff(old, new));
+        }
+    }
+    println!();
+}
+
+// Compare the assistant messages of samples that appear in both logs
+pub fn display_comparison(left_path: &Path, left: Vec<EvalSample>, right_path: &Path, right: Vec<EvalSample>) {
+    let key = |s: &EvalSample| (s.id.clone(), s.epoch);
+    let left = left.into_iter().map(|s| (key(&s), s)).collect::<BTreeMap<_, _>>();
+    let right = right.into_iter().map(|s| (key(&s), s)).collect::<BTreeMap<_, _>>();
+
+    println!("{} {}", "---".red(), left_path.display().to_string().red());
+    println!("{} {}", "+++".green(), right_path.display().to_string().green());
+    for ((sample_id, epoch), left_sample) in &left {
+        match right.get(&(sample_id.clone(), *epoch)) {
+            Some(right_sample) => display_sample_diff(sample_id, *epoch, left_sample, right_sample),
+            None => println!("sample {} epoch {} | {}", sample_id.yellow(), epoch.to_string().green(), "only in first log".red()),
+        }
+    }
+    for (sample_id, epoch) in right.keys().filter(|k| !left.contains_key(*k)) {
+        println!("sample {} epoch {} | {}", sample_id.yellow(), epoch.to_string().green(), "only in second log".green());
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::str::FromStr;
+
+use crate::inspect::EvalSpec;
+
+// Header objects searched, in order, for a key given without a dotted path
+const CONFIG_SECTIONS: [&[&str]; 5] = [
+    &["eval", "task_args"],
+    &["eval", "config"],
+    &["plan", "config"],
+    &["eval", "model_args"],
+    &["eval"],
+];
+
+// `key=regex` (or `key!=regex` to exclude), matched against a run configuration
+// value in a log's header
+#[derive(Debug, Clone)]
+pub struct ConfigFilter {
+    key: String,
+    regex: Regex,
+    exclude: bool,
+}
+
+impl FromStr for ConfigFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, pattern) = s.split_once('=').ok_or_else(|| anyhow!("expected key=regex or key!=regex"))?;
+        let (key, exclude) = match key.strip_suffix('!') {
+            Some(key) => (key, true),
+            None => (key, false),
+        };
+        if key.is_empty() {
+            return Err(anyhow!("missing key before '='"));
+        }
+        // The whole value has to match, so `temperature=0` doesn't also select 0.7
+        let regex = Regex::new(&format!("^(?:{})$", pattern))?;
+        Ok(Self { key: key.to_string(), regex, exclude })
+    }
+}
+
+fn lookup<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| value.get(key))
+}
+
+// A configuration value as text: strings as-is, sandboxes by their type, anything else as JSON
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Object(map) if map.contains_key("type") => value_text(&map["type"]),
+        // Older logs give sandboxes as `[type, config]`
+        Value::Array(items) if items.first().is_some_and(Value::is_string) => value_text(&items[0]),
+        other => other.to_string(),
+    }
+}
+
+impl ConfigFilter {
+    // The value the key refers to: a dotted path from the header root, or the
+    // first config section that has it
+    fn value<'a>(&self, header: &'a Value) -> Option<&'a Value> {
+        if self.key.contains('.') {
+            return lookup(header, &self.key.split('.').collect::<Vec<_>>());
+        }
+        CONFIG_SECTIONS
+            .iter()
+            .filter_map(|section| lookup(header, section)?.get(&self.key))
+            .find(|value| !value.is_null())
+    }
+
+    // Logs without the key are only kept by exclusions
+    pub fn matches(&self, header: &Value) -> bool {
+        let found = self.value(header).is_some_and(|value| self.regex.is_match(&value_text(value)));
+        found != self.exclude
+    }
+}
+
+// Which runs to search by the task, model and run id in a log's header, checked
+// before any sample is read
+#[derive(Debug, Clone, Default)]
+pub struct HeaderFilter {
+    pub task: Option<Regex>,
+    pub model: Option<Regex>,
+    pub run_id: Option<Regex>,
+}
+
+impl HeaderFilter {
+    pub fn is_empty(&self) -> bool {
+        self.task.is_none() && self.model.is_none() && self.run_id.is_none()
+    }
+
+    pub fn matches(&self, spec: &EvalSpec) -> bool {
+        let matches = |regex: &Option<Regex>, value: &str| regex.as_ref().is_none_or(|regex| regex.is_match(value));
+        matches(&self.task, &spec.task) && matches(&self.model, &spec.model) && matches(&self.run_id, &spec.run_id)
+    }
+}
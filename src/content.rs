@@ -0,0 +1,246 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::ops::Deref;
+
+// One part of a content list
+#[derive(Debug, Clone)]
+pub enum ContentPart {
+    Text(String),
+    Reasoning(String),
+    // An image, audio or video part, by kind and its URL or data URI
+    Media { kind: String, source: String },
+    // A part of a type we don't render, by type
+    Other(String),
+}
+
+impl ContentPart {
+    fn parse(part: &serde_json::Value) -> Self {
+        let field = |name: &str| part.get(name).and_then(serde_json::Value::as_str).unwrap_or_default().to_string();
+        match part.get("type").and_then(serde_json::Value::as_str).unwrap_or_default() {
+            "text" => ContentPart::Text(field("text")),
+            "reasoning" => ContentPart::Reasoning(field("reasoning")),
+            kind @ ("image" | "audio" | "video") => ContentPart::Media { kind: kind.to_string(), source: field(kind) },
+            kind => ContentPart::Other(kind.to_string()),
+        }
+    }
+
+    fn render(&self) -> String {
+        match self {
+            ContentPart::Text(text) => text.clone(),
+            ContentPart::Reasoning(text) => format!("[reasoning]\n{}", text),
+            ContentPart::Media { kind, source } => match describe_data_uri(source) {
+                Some(description) => format!("[{}: {}]", kind, description),
+                None => format!("[{}: {}]", kind, source),
+            },
+            ContentPart::Other(kind) => format!("[{}]", kind),
+        }
+    }
+
+    fn truncate(&mut self, max: usize) -> bool {
+        match self {
+            ContentPart::Text(text) | ContentPart::Reasoning(text) => truncate_field(text, max),
+            // Cutting a data URI would misreport the media's size, and it's rendered as a short placeholder
+            ContentPart::Media { .. } | ContentPart::Other(_) => false,
+        }
+    }
+
+    fn source(&self) -> &str {
+        match self {
+            ContentPart::Text(text) | ContentPart::Reasoning(text) => text,
+            ContentPart::Media { source, .. } => source,
+            ContentPart::Other(kind) => kind,
+        }
+    }
+
+    fn source_mut(&mut self) -> &mut String {
+        match self {
+            ContentPart::Text(text) | ContentPart::Reasoning(text) => text,
+            ContentPart::Media { source, .. } => source,
+            ContentPart::Other(kind) => kind,
+        }
+    }
+}
+
+// "128KB png" for a base64 data URI
+fn describe_data_uri(source: &str) -> Option<String> {
+    let (header, data) = source.strip_prefix("data:")?.split_once(',')?;
+    let mime = header.strip_suffix(";base64")?;
+    let format = mime.split_once('/').map_or(mime, |(_, subtype)| subtype);
+    Some(format!("{} {}", format_size(data.len() * 3 / 4), format))
+}
+
+// Cut `value` to at most `max` bytes (at a character boundary) plus a marker saying
+// how large it was; false if it already fit
+pub fn truncate_field(value: &mut String, max: usize) -> bool {
+    truncate_field_of(value, max, value.len())
+}
+
+// As truncate_field, for a value already cut short of its full `size`
+pub fn truncate_field_of(value: &mut String, max: usize, size: usize) -> bool {
+    if size <= max {
+        return false;
+    }
+    let mut end = max.min(value.len());
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    value.truncate(end);
+    value.push_str(&format!("\n[... truncated by --max-field-bytes: {} of {}]", format_size(end), format_size(size)));
+    true
+}
+
+fn format_size(bytes: usize) -> String {
+    match bytes {
+        0..1024 => format!("{}B", bytes),
+        1024..1_048_576 => format!("{}KB", bytes / 1024),
+        _ => format!("{:.1}MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+// Message content, which Inspect writes either as a string or as a list of typed
+// parts. Derefs to the text that's searched and displayed, where non-text parts
+// are rendered as placeholders like `[image: 128KB png]`
+#[derive(Debug, Clone)]
+pub enum Content {
+    Text(String),
+    Parts { parts: Vec<ContentPart>, text: String },
+}
+
+impl Content {
+    fn from_parts(parts: Vec<ContentPart>) -> Self {
+        let text = parts.iter().map(ContentPart::render).collect::<Vec<_>>().join("\n");
+        Content::Parts { parts, text }
+    }
+
+    // Hashes of the `attachment://` references left in the text or any part
+    pub fn attachment_refs(&self) -> Vec<&str> {
+        match self {
+            Content::Text(text) => text.strip_prefix("attachment://").into_iter().collect(),
+            Content::Parts { parts, .. } => parts.iter().filter_map(|part| part.source().strip_prefix("attachment://")).collect(),
+        }
+    }
+
+    // Replace `attachment://` references, in the text or any part, with what
+    // `resolve` finds for their hash
+    pub fn resolve_attachments<'a>(&mut self, resolve: impl Fn(&str) -> Option<&'a String>) {
+        let replace = |value: &mut String| {
+            if let Some(content) = value.strip_prefix("attachment://").and_then(&resolve) {
+                *value = content.clone();
+            }
+        };
+        match self {
+            Content::Text(text) => replace(text),
+            Content::Parts { parts, .. } => {
+                let mut parts = std::mem::take(parts);
+                parts.iter_mut().map(ContentPart::source_mut).for_each(replace);
+                *self = Content::from_parts(parts);
+            }
+        }
+    }
+}
+
+impl Content {
+    // The sources of its image parts, in order: data URIs or URLs
+    pub fn images(&self) -> impl Iterator<Item = &str> {
+        let parts: &[ContentPart] = match self {
+            Content::Text(_) => &[],
+            Content::Parts { parts, .. } => parts,
+        };
+        parts.iter().filter_map(|part| match part {
+            ContentPart::Media { kind, source } if kind == "image" => Some(source.as_str()),
+            _ => None,
+        })
+    }
+
+    // Replace the text, or each text part, with what `rewrite` makes of it, where it
+    // makes something
+    pub fn rewrite_text(&mut self, rewrite: impl Fn(&str) -> Option<String>) {
+        match self {
+            Content::Text(text) => {
+                if let Some(rewritten) = rewrite(text) {
+                    *text = rewritten;
+                }
+            }
+            Content::Parts { parts, .. } => {
+                let mut parts = std::mem::take(parts);
+                for part in &mut parts {
+                    if let ContentPart::Text(text) | ContentPart::Reasoning(text) = part {
+                        if let Some(rewritten) = rewrite(text) {
+                            *text = rewritten;
+                        }
+                    }
+                }
+                *self = Content::from_parts(parts);
+            }
+        }
+    }
+
+    // Cut the text, or each text part, to `max` bytes, marking where; false if it all fit
+    pub fn truncate(&mut self, max: usize) -> bool {
+        match self {
+            Content::Text(text) => truncate_field(text, max),
+            Content::Parts { parts, .. } => {
+                let mut parts = std::mem::take(parts);
+                let truncated = parts.iter_mut().fold(false, |truncated, part| part.truncate(max) | truncated);
+                *self = Content::from_parts(parts);
+                truncated
+            }
+        }
+    }
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Content::Text(String::new())
+    }
+}
+
+impl Deref for Content {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Content::Text(text) | Content::Parts { text, .. } => text,
+        }
+    }
+}
+
+impl From<String> for Content {
+    fn from(text: String) -> Self {
+        Content::Text(text)
+    }
+}
+
+impl PartialEq for Content {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl fmt::Display for Content {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Content {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Text(String),
+            Parts(Vec<serde_json::Value>),
+        }
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Text(text) => Content::Text(text),
+            Raw::Parts(parts) => Content::from_parts(parts.iter().map(ContentPart::parse).collect()),
+        })
+    }
+}
+
+// Written as the rendered text, so exported and spilled messages read back the same
+impl Serialize for Content {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
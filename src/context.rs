@@ -0,0 +1,33 @@
+// How many messages to show around each match, like grep's -B/-A
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContextWindow {
+    pub before: usize,
+    pub after: usize,
+}
+
+impl ContextWindow {
+    // Every message of a sample with a match
+    pub const WHOLE_SAMPLE: Self = Self { before: usize::MAX, after: usize::MAX };
+
+    pub fn is_empty(&self) -> bool {
+        self.before == 0 && self.after == 0
+    }
+
+    // Indices of the messages to show for matches at the given (ascending) indices,
+    // split into runs of adjacent messages; overlapping windows are merged
+    pub fn groups(&self, matches: &[usize], len: usize) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for &index in matches {
+            let start = index.saturating_sub(self.before);
+            let end = index.saturating_add(self.after).min(len.saturating_sub(1));
+            match groups.last_mut() {
+                Some(group) if *group.last().unwrap() + 1 >= start => {
+                    let next = group.last().unwrap() + 1;
+                    group.extend(next..=end);
+                }
+                _ => groups.push((start..=end).collect()),
+            }
+        }
+        groups
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::filter_set::FilterSet;
+
+// How many candidates survived each filtering stage, for explaining empty results
+#[derive(Debug, Default)]
+pub struct ScanCounters {
+    // Logs skipped because their header didn't satisfy --config-filter or --task/--model/--run-id
+    pub files_excluded: AtomicUsize,
+    // Logs skipped because the index records no messages with the requested roles in them
+    pub files_without_role: AtomicUsize,
+    // Sample entries found in the scanned logs
+    pub samples_found: AtomicUsize,
+    // JSON entries in logs where no sample layout matched
+    pub entries_unrecognized: AtomicUsize,
+    // Samples passing the sample id and epoch filters
+    pub samples_selected: AtomicUsize,
+    // Selected samples skipped because their sandbox didn't match --sandbox-filter
+    pub samples_outside_sandbox: AtomicUsize,
+    // Selected samples skipped because their scores didn't match --score/--correct/--incorrect
+    pub samples_outside_scores: AtomicUsize,
+    // Samples with the scores asked for skipped because the model never stopped for a --stop-reason reason
+    pub samples_outside_stop_reason: AtomicUsize,
+    // Samples left after the filters above skipped for erroring, or not, against --errors-only / --no-errors
+    pub samples_outside_errored: AtomicUsize,
+    // Selected samples skipped because they weren't offered every --has-tool tool
+    pub samples_without_tool: AtomicUsize,
+    // Selected samples that ended abnormally, with --truncated-only
+    pub samples_truncated: AtomicUsize,
+    // Messages in the selected samples
+    pub messages_seen: AtomicUsize,
+    // Messages passing the role filter
+    pub messages_with_role: AtomicUsize,
+    // Messages passing the role and pattern filters, when --tool-name/--tool-args are given
+    pub messages_before_tools: AtomicUsize,
+    // Messages passing the role, pattern and tool call filters, when --numbers is given
+    pub messages_before_numbers: AtomicUsize,
+    // Messages passing every filter but --lang, when it's given
+    pub messages_before_language: AtomicUsize,
+    // Selected samples over --max-sample-size
+    pub samples_oversized: AtomicUsize,
+    // Samples with at least one match
+    pub samples_matched: AtomicUsize,
+}
+
+impl ScanCounters {
+    pub fn add(counter: &AtomicUsize, n: usize) {
+        counter.fetch_add(n, Ordering::Relaxed);
+    }
+
+    // Values of the variables --assert expressions can refer to
+    pub fn assertion_variables(&self, matches: usize, files: usize) -> BTreeMap<&'static str, f64> {
+        BTreeMap::from([
+            ("matches", matches as f64),
+            ("matched_samples", self.samples_matched.load(Ordering::Relaxed) as f64),
+            ("total_samples", self.samples_found.load(Ordering::Relaxed) as f64),
+            ("selected_samples", self.samples_selected.load(Ordering::Relaxed) as f64),
+            ("messages", self.messages_seen.load(Ordering::Relaxed) as f64),
+            ("files", files as f64),
+        ])
+    }
+
+    // Explain which filter eliminated every candidate
+    pub fn hint(&self, files: usize, filters: &FilterSet) -> String {
+        let roles = &filters.roles;
+        let samples_found = self.samples_found.load(Ordering::Relaxed);
+        let samples_selected = self.samples_selected.load(Ordering::Relaxed);
+        let messages_seen = self.messages_seen.load(Ordering::Relaxed);
+        let messages_with_role = self.messages_with_role.load(Ordering::Relaxed);
+
+        let files_excluded = self.files_excluded.load(Ordering::Relaxed);
+        if files == 0 && files_excluded > 0 {
+            return format!("{} .eval files found but none matched --config-filter/--task/--model/--run-id", files_excluded);
+        }
+        if files == 0 {
+            return "no .eval files found".to_string();
+        }
+        if roles.is_empty() {
+            return "--exclude-roles excludes every role, so no message can match".to_string();
+        }
+        if self.files_without_role.load(Ordering::Relaxed) == files {
+            let roles = roles.iter().map(ToString::to_string).collect::<Vec<_>>().join("/");
+            return format!("the log index records no {} messages in any of the {} files", roles, files);
+        }
+        if samples_found == 0 {
+            let unrecognized = self.entries_unrecognized.load(Ordering::Relaxed);
+            if unrecognized > 0 {
+                return format!(
+                    "no sample entries found in {} files, but {} JSON entries didn't match any known layout; try --sample-layout",
+                    files, unrecognized
+                );
+            }
+            return format!("no sample entries found in {} files", files);
+        }
+        if samples_selected == 0 {
+            return format!("{} samples found but none matched the sample id/epoch filters", samples_found);
+        }
+        if self.samples_outside_scores.load(Ordering::Relaxed) == samples_selected {
+            return format!("{} samples matched id/epoch filters but none had the scores (or score explanations) asked for", samples_selected);
+        }
+        let outside_stop_reason = self.samples_outside_stop_reason.load(Ordering::Relaxed);
+        if outside_stop_reason > 0 && self.samples_outside_scores.load(Ordering::Relaxed) + outside_stop_reason == samples_selected {
+            return format!("{} samples matched id/epoch and score filters but the model never stopped for a --stop-reason reason in any of them", outside_stop_reason);
+        }
+        let outside_errored = self.samples_outside_errored.load(Ordering::Relaxed);
+        if outside_errored > 0 && self.samples_outside_scores.load(Ordering::Relaxed) + outside_stop_reason + outside_errored == samples_selected {
+            return format!("{} samples matched the other sample filters but --errors-only / --no-errors ruled out all of them", outside_errored);
+        }
+        if self.samples_outside_sandbox.load(Ordering::Relaxed) == samples_selected {
+            return format!("{} samples matched id/epoch filters but none ran in a sandbox matching --sandbox-filter", samples_selected);
+        }
+        if self.samples_without_tool.load(Ordering::Relaxed) == samples_selected {
+            return format!("{} samples matched id/epoch filters but none were offered every --has-tool tool", samples_selected);
+        }
+        if messages_seen == 0 {
+            return format!("{} samples matched id/epoch filters but they contain no messages", samples_selected);
+        }
+        let messages_before_language = self.messages_before_language.load(Ordering::Relaxed);
+        if messages_before_language > 0 {
+            return format!(
+                "{} messages matched the other filters but none were detected as the --lang languages",
+                messages_before_language
+            );
+        }
+        let messages_before_numbers = self.messages_before_numbers.load(Ordering::Relaxed);
+        if messages_before_numbers > 0 {
+            return format!(
+                "{} messages matched the other filters but none mentioned a number in the --numbers range",
+                messages_before_numbers
+            );
+        }
+        let messages_before_tools = self.messages_before_tools.load(Ordering::Relaxed);
+        if messages_before_tools > 0 {
+            return format!(
+                "{} messages matched the other filters but none made a tool call matching --tool-name/--tool-args",
+                messages_before_tools
+            );
+        }
+        let role_names = (!roles.is_all()).then(|| roles.iter().map(|r| format!("'{}'", r)).collect::<Vec<_>>().join(" or "));
+        if messages_with_role == 0 {
+            if let Some(role_names) = role_names {
+                return format!(
+                    "{} samples matched id/epoch filters but 0 of their {} messages had role {}",
+                    samples_selected, messages_seen, role_names
+                );
+            }
+        }
+        match (&filters.pattern, role_names) {
+            (Some(pattern), Some(role_names)) => format!(
+                "{} messages had role {} but none matched {}",
+                messages_with_role, role_names, pattern
+            ),
+            (Some(pattern), None) => format!(
+                "{} samples matched id/epoch filters but none of their {} messages matched {}",
+                samples_selected, messages_seen, pattern
+            ),
+            _ => format!("{} samples matched id/epoch filters but nothing else matched", samples_selected),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use itertools::Itertools;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::inspect::ChatMessage;
+use crate::theme::Theme;
+
+// Hash identifying a message's exact content, shown as 16 hex digits
+pub fn content_hash(message: &ChatMessage) -> u64 {
+    crate::fingerprint::hash(&message.content)
+}
+
+struct Group {
+    // Lowest (file, sample, epoch) the content appeared in, so the example is stable across runs
+    first: (PathBuf, String, i64),
+    message: ChatMessage,
+    count: usize,
+    samples: usize,
+    logs: HashSet<PathBuf>,
+}
+
+// Matched messages grouped by byte-identical content, so each is reported once with a count
+#[derive(Default)]
+pub struct ContentGroups {
+    groups: Mutex<HashMap<u64, Group>>,
+}
+
+impl ContentGroups {
+    // Record one sample's matched messages
+    pub fn add_sample(&self, source: (&Path, &str, i64), messages: &[&ChatMessage]) {
+        let (path, sample_id, epoch) = source;
+        let mut groups = self.groups.lock().unwrap();
+        let mut seen_in_sample = HashSet::new();
+        for message in messages {
+            let hash = content_hash(message);
+            let group = groups.entry(hash).or_insert_with(|| Group {
+                first: (path.to_path_buf(), sample_id.to_string(), epoch),
+                message: (*message).clone(),
+                count: 0,
+                samples: 0,
+                logs: HashSet::new(),
+            });
+            let source = (path.to_path_buf(), sample_id.to_string(), epoch);
+            if source < group.first {
+                group.first = source;
+            }
+            group.count += 1;
+            if seen_in_sample.insert(hash) {
+                group.samples += 1;
+            }
+            group.logs.insert(path.to_path_buf());
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.lock().unwrap().is_empty()
+    }
+
+    // Most repeated content first, each shown once under where it first appeared
+    pub fn display(&self, show_hash: bool, highlight: Option<&Regex>, theme: &Theme) {
+        let groups = self.groups.lock().unwrap();
+        for (hash, group) in groups.iter().sorted_by(|(a_hash, a), (b_hash, b)| b.count.cmp(&a.count).then(a_hash.cmp(b_hash))) {
+            let (path, sample_id, epoch) = &group.first;
+            let repeats = format!("{}{} in {} samples across {} logs", crate::glyphs::glyph("×", "x"), group.count, group.samples, group.logs.len());
+            let hash = show_hash.then(|| format!("#{:016x}", hash));
+            let label = [Some(repeats), hash].into_iter().flatten().join(" ");
+            crate::display_message((path, sample_id, *epoch), &group.message, None, Some(&label), highlight, None, None, theme);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use clap::ValueEnum;
+use std::io::Read;
+
+// Bytes read from the entry at a time while transcoding
+const CHUNK: usize = 64 * 1024;
+
+// Windows-1252's characters for the bytes Latin-1 leaves to control codes (0x80-0x9f),
+// which is where UTF-8 read as Windows-1252 puts curly quotes and dashes (`â€™` for `’`)
+const WINDOWS_1252: [(char, u8); 27] = [
+    ('€', 0x80), ('‚', 0x82), ('ƒ', 0x83), ('„', 0x84), ('…', 0x85), ('†', 0x86), ('‡', 0x87), ('ˆ', 0x88), ('‰', 0x89),
+    ('Š', 0x8a), ('‹', 0x8b), ('Œ', 0x8c), ('Ž', 0x8e), ('‘', 0x91), ('’', 0x92), ('“', 0x93), ('”', 0x94), ('•', 0x95),
+    ('–', 0x96), ('—', 0x97), ('˜', 0x98), ('™', 0x99), ('š', 0x9a), ('›', 0x9b), ('œ', 0x9c), ('ž', 0x9e), ('Ÿ', 0x9f),
+];
+
+/// How sample entries and attachments that aren't clean UTF-8 are read, for --encoding.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Encoding {
+    /// Keep valid UTF-8, read invalid bytes as Latin-1, and decode `\xNN` escapes and UTF-8 mis-read as Latin-1 (`Ã©` for `é`) in message content
+    Auto,
+    /// UTF-8 only, as Inspect writes logs: samples with invalid bytes are skipped as unreadable
+    #[default]
+    Utf8,
+    /// Every byte is a Latin-1 character, for logs written in Latin-1 throughout
+    Latin1,
+}
+
+fn push_latin1(out: &mut Vec<u8>, bytes: &[u8]) {
+    for byte in bytes {
+        let mut utf8 = [0; 2];
+        out.extend_from_slice((*byte as char).encode_utf8(&mut utf8).as_bytes());
+    }
+}
+
+// Move what's valid UTF-8 at the start of `pending` to `out`, reading invalid sequences
+// as Latin-1; an incomplete sequence at the end is left for more input unless `eof`
+fn transcode(pending: &mut Vec<u8>, out: &mut Vec<u8>, encoding: Encoding, eof: bool) {
+    if encoding == Encoding::Latin1 {
+        push_latin1(out, pending);
+        pending.clear();
+        return;
+    }
+    let mut start = 0;
+    while start < pending.len() {
+        match std::str::from_utf8(&pending[start..]) {
+            Ok(valid) => {
+                out.extend_from_slice(valid.as_bytes());
+                start = pending.len();
+            }
+            Err(e) => {
+                let valid = start + e.valid_up_to();
+                out.extend_from_slice(&pending[start..valid]);
+                match e.error_len() {
+                    Some(len) => {
+                        push_latin1(out, &pending[valid..valid + len]);
+                        start = valid + len;
+                    }
+                    None if eof => {
+                        push_latin1(out, &pending[valid..]);
+                        start = pending.len();
+                    }
+                    None => {
+                        start = valid;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    pending.drain(..start);
+}
+
+// An entry's bytes as UTF-8, for the JSON parser, per the encoding
+struct Transcoding<R> {
+    inner: R,
+    encoding: Encoding,
+    pending: Vec<u8>,
+    out: Vec<u8>,
+    position: usize,
+    eof: bool,
+}
+
+impl<R: Read> Read for Transcoding<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.out.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            let filled = self.pending.len();
+            self.pending.resize(filled + CHUNK, 0);
+            let read = self.inner.read(&mut self.pending[filled..])?;
+            self.pending.truncate(filled + read);
+            self.eof = read == 0;
+            self.out.clear();
+            self.position = 0;
+            transcode(&mut self.pending, &mut self.out, self.encoding, self.eof);
+        }
+        let read = buf.len().min(self.out.len() - self.position);
+        buf[..read].copy_from_slice(&self.out[self.position..self.position + read]);
+        self.position += read;
+        Ok(read)
+    }
+}
+
+// The byte a character stands for when UTF-8 was mis-read as Latin-1 or Windows-1252
+fn misread_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        0x80..=0xff => Some(c as u8),
+        _ => WINDOWS_1252.iter().find(|(misread, _)| *misread == c).map(|(_, byte)| *byte),
+    }
+}
+
+// Runs of `\xNN` escapes spelling out UTF-8 characters (`\xe2\x80\x99` for `’`), decoded;
+// escapes of ASCII are left alone, as they're as likely code talking about escapes
+fn decode_byte_escapes(text: &str) -> Option<String> {
+    let escape = |at: &str| at.strip_prefix("\\x").filter(|hex| hex.len() >= 2 && hex.is_char_boundary(2)).and_then(|hex| u8::from_str_radix(&hex[..2], 16).ok());
+    if !text.contains("\\x") {
+        return None;
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut changed = false;
+    let mut rest = text;
+    while let Some(at) = rest.find("\\x") {
+        decoded.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let mut bytes = Vec::new();
+        while let Some(byte) = escape(rest) {
+            bytes.push(byte);
+            rest = &rest[4..];
+        }
+        if bytes.is_empty() {
+            decoded.push_str("\\x");
+            rest = &rest[2..];
+            continue;
+        }
+        match std::str::from_utf8(&bytes) {
+            Ok(text) if bytes.iter().all(|byte| *byte >= 0x80) => {
+                decoded.push_str(text);
+                changed = true;
+            }
+            // Not UTF-8, so put the escapes back as they were
+            _ => bytes.iter().for_each(|byte| decoded.push_str(&format!("\\x{:02x}", byte))),
+        }
+    }
+    decoded.push_str(rest);
+    changed.then_some(decoded)
+}
+
+// A run of characters that might be mis-read bytes, with the UTF-8 characters in it
+// decoded and the rest kept as they were; true if anything was decoded
+fn decode_run(run: &[(u8, char)], decoded: &mut String) -> bool {
+    let bytes = run.iter().map(|(byte, _)| *byte).collect::<Vec<_>>();
+    let mut changed = false;
+    let mut offset = 0;
+    for chunk in bytes.utf8_chunks() {
+        // Every byte of the run is at least 0x80, so whatever's valid is multi-byte characters
+        decoded.push_str(chunk.valid());
+        changed |= !chunk.valid().is_empty();
+        offset += chunk.valid().len();
+        decoded.extend(run[offset..offset + chunk.invalid().len()].iter().map(|(_, c)| c));
+        offset += chunk.invalid().len();
+    }
+    changed
+}
+
+// Runs of characters that are UTF-8 bytes mis-read one per character (`cafÃ©`), decoded
+fn decode_misread_utf8(text: &str) -> Option<String> {
+    if text.is_ascii() {
+        return None;
+    }
+    let mut decoded = String::with_capacity(text.len());
+    let mut changed = false;
+    let mut run = Vec::new();
+    for c in text.chars() {
+        match misread_byte(c) {
+            Some(byte) => run.push((byte, c)),
+            None => {
+                changed |= decode_run(&run, &mut decoded);
+                run.clear();
+                decoded.push(c);
+            }
+        }
+    }
+    changed |= decode_run(&run, &mut decoded);
+    changed.then_some(decoded)
+}
+
+impl Encoding {
+    /// `reader` over a sample entry, converted to UTF-8 as the JSON parser needs it.
+    pub fn reader<'a, R: Read + 'a>(self, reader: R) -> Box<dyn Read + 'a> {
+        match self {
+            Encoding::Utf8 => Box::new(reader),
+            encoding => Box::new(Transcoding { inner: reader, encoding, pending: Vec::new(), out: Vec::new(), position: 0, eof: false }),
+        }
+    }
+
+    /// An attachment's bytes as text; strictly read, invalid UTF-8 is replaced with `�`.
+    pub fn decode(self, mut bytes: Vec<u8>) -> String {
+        if self == Encoding::Utf8 {
+            return String::from_utf8_lossy(&bytes).into_owned();
+        }
+        let mut out = Vec::with_capacity(bytes.len());
+        transcode(&mut bytes, &mut out, self, true);
+        String::from_utf8(out).expect("transcoding only writes UTF-8")
+    }
+
+    /// Message text with what looks mis-encoded decoded, with `auto`; None if it's unchanged.
+    pub fn repair(self, text: &str) -> Option<String> {
+        if self != Encoding::Auto {
+            return None;
+        }
+        let unescaped = decode_byte_escapes(text);
+        let source = unescaped.as_deref().unwrap_or(text);
+        decode_misread_utf8(source).or(unescaped)
+    }
+}
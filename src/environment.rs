@@ -0,0 +1,130 @@
+use colored::*;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::inspect::{EvalLogHeader, EvalSample, ToolInfo};
+use crate::theme::Theme;
+
+// The sandbox a sample ran in: its type (docker, local, k8s, ...) and config,
+// usually a compose file or Dockerfile path
+#[derive(Debug, Clone)]
+pub struct SandboxSpec {
+    kind: String,
+    config: Option<String>,
+}
+
+impl SandboxSpec {
+    // Inspect writes specs as `{"type": ..., "config": ...}`, and older logs as `[type, config]`
+    pub fn parse(value: &Value) -> Option<Self> {
+        let (kind, config) = match value {
+            Value::String(kind) => (kind.as_str(), None),
+            Value::Array(parts) => (parts.first()?.as_str()?, parts.get(1)),
+            Value::Object(spec) => (spec.get("type")?.as_str()?, spec.get("config")),
+            _ => return None,
+        };
+        Some(Self { kind: kind.to_string(), config: config.and_then(describe_config) })
+    }
+
+    // Header-level sandbox of a log, which samples without their own inherit
+    pub fn of_log(log_path: &Path) -> Option<Self> {
+        let header: EvalLogHeader = crate::read_json_entry(log_path, "header.json").ok()??;
+        header.eval.sandbox.as_ref().and_then(Self::parse)
+    }
+
+    pub fn of_sample(sample: &EvalSample, log_sandbox: Option<&Self>) -> Option<Self> {
+        sample.sandbox.as_ref().and_then(Self::parse).or_else(|| log_sandbox.cloned())
+    }
+
+    // `docker (compose.yaml)`, what --sandbox-filter is matched against
+    pub fn describe(&self) -> String {
+        match &self.config {
+            Some(config) => format!("{} ({})", self.kind, config),
+            None => self.kind.clone(),
+        }
+    }
+}
+
+// Tools a log's solver steps offer (e.g. `use_tools`), for samples without model
+// events to read them from; the plan lists them by name, or as `{"name": ...}` specs
+pub fn log_tools(log_path: &Path) -> Vec<ToolInfo> {
+    let Ok(Some(header)) = crate::read_json_entry::<EvalLogHeader>(log_path, "header.json") else {
+        return Vec::new();
+    };
+    let steps = header.plan.as_ref().and_then(|plan| plan.get("steps")).and_then(Value::as_array);
+    let listed = steps.into_iter().flatten().filter_map(|step| step.pointer("/params/tools")?.as_array()).flatten();
+    let mut tools: Vec<ToolInfo> = Vec::new();
+    for tool in listed {
+        let name = match tool {
+            Value::String(name) => name.as_str(),
+            tool => match tool.get("name").and_then(Value::as_str) {
+                Some(name) => name,
+                None => continue,
+            },
+        };
+        if !tools.iter().any(|known| known.name == name) {
+            let description = tool.get("description").and_then(Value::as_str).map(String::from);
+            tools.push(ToolInfo { name: name.to_string(), description });
+        }
+    }
+    tools
+}
+
+// The tools the model was offered in the sample's model calls, or else those its log's plan lists
+pub fn sample_tools<'a>(sample: &'a EvalSample, log_tools: &'a [ToolInfo]) -> &'a [ToolInfo] {
+    if sample.tools.is_empty() { log_tools } else { &sample.tools }
+}
+
+// A config path as is; for inline configs (e.g. compose services) just the images they name
+fn describe_config(config: &Value) -> Option<String> {
+    fn images<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value) {
+                        ("image", Value::String(image)) => out.push(image),
+                        (_, value) => images(value, out),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|value| images(value, out)),
+            _ => {}
+        }
+    }
+    match config {
+        Value::Null => None,
+        Value::String(path) => Some(path.clone()),
+        config => {
+            let mut found = Vec::new();
+            images(config, &mut found);
+            match found.is_empty() {
+                true => Some(config.to_string()),
+                false => Some(format!("image {}", found.join(", "))),
+            }
+        }
+    }
+}
+
+pub fn display_environment(source: (&Path, &str, i64), sandbox: Option<&SandboxSpec>, tools: &[ToolInfo], theme: &Theme) {
+    let (log_file, sample_id, epoch) = source;
+    let marker = "[env]".cyan().bold();
+    println!("\n{}", theme.header(&log_file.file_name().unwrap().to_string_lossy(), sample_id, epoch, marker));
+    println!("sandbox: {}", sandbox.map_or("none".to_string(), SandboxSpec::describe));
+    let names = tools.iter().map(|tool| tool.name.as_str()).collect::<Vec<_>>();
+    println!("tools: {}", if names.is_empty() { "none".to_string() } else { names.join(", ") });
+}
+
+// Each tool with the first line of its description
+pub fn display_tools(source: (&Path, &str, i64), tools: &[ToolInfo], theme: &Theme) {
+    let (log_file, sample_id, epoch) = source;
+    let marker = "[tools]".cyan().bold();
+    println!("\n{}", theme.header(&log_file.file_name().unwrap().to_string_lossy(), sample_id, epoch, marker));
+    if tools.is_empty() {
+        println!("{}", "none".dimmed());
+    }
+    for tool in tools {
+        match tool.description.as_deref().and_then(|description| description.lines().next()) {
+            Some(description) => println!("{}: {}", tool.name.bold(), description.dimmed()),
+            None => println!("{}", tool.name.bold()),
+        }
+    }
+}
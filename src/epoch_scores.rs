@@ -0,0 +1,68 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::inspect::{EvalLogHeader, EvalSample, EvalSampleReductions, Score};
+use crate::stats::score_label;
+
+// A log's per-sample scores reduced across epochs, for showing next to each epoch's own score
+#[derive(Default)]
+pub struct ReducedScores {
+    epochs: u32,
+    // sample id -> scorer -> (reducer, reduced score)
+    by_sample: HashMap<String, HashMap<String, (Option<String>, Score)>>,
+}
+
+fn id_text(id: &Value) -> String {
+    match id {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl ReducedScores {
+    // Missing or unreadable headers and reductions just mean there's nothing to show
+    pub fn load(log_path: &Path) -> Self {
+        let header: Option<EvalLogHeader> = crate::read_json_entry(log_path, "header.json").ok().flatten();
+        let epochs = header.and_then(|h| h.eval.config.epochs).unwrap_or(1);
+        if epochs <= 1 {
+            return Self::default();
+        }
+        let reductions: Vec<EvalSampleReductions> =
+            crate::read_json_entry(log_path, "reductions.json").ok().flatten().unwrap_or_default();
+        let mut by_sample: HashMap<String, HashMap<_, _>> = HashMap::new();
+        for reduction in reductions {
+            for sample in reduction.samples {
+                by_sample
+                    .entry(id_text(&sample.sample_id))
+                    .or_default()
+                    .insert(reduction.scorer.clone(), (reduction.reducer.clone(), sample.score));
+            }
+        }
+        Self { epochs, by_sample }
+    }
+
+    // Each scorer's score for this epoch, followed by the sample's reduced score,
+    // e.g. `match I (mean 0.5)`; None for single-epoch logs
+    pub fn label(&self, sample: &EvalSample) -> Option<String> {
+        if self.epochs <= 1 || sample.scores.is_empty() {
+            return None;
+        }
+        let reduced = self.by_sample.get(&sample.id);
+        let mut scorers = sample.scores.iter().collect::<Vec<_>>();
+        scorers.sort_by_key(|(name, _)| name.as_str());
+        let labels = scorers
+            .into_iter()
+            .map(|(name, score)| {
+                let epoch_score = format!("{} {}", name, score_label(&score.value));
+                match reduced.and_then(|reduced| reduced.get(name)) {
+                    Some((reducer, reduced)) => {
+                        format!("{} ({} {})", epoch_score, reducer.as_deref().unwrap_or("reduced"), score_label(&reduced.value))
+                    }
+                    None => epoch_score,
+                }
+            })
+            .collect::<Vec<_>>();
+        Some(labels.join(", "))
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::Result;
+use colored::*;
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::assembly::SampleEntries;
+use crate::encoding::Encoding;
+use crate::log_format::open_log;
+use crate::message_pattern::MessagePattern;
+use crate::theme::Theme;
+
+// Event fields never searched: a model event's input, tools and request repeat the
+// conversation and tool definitions, and the rest is bookkeeping
+const SKIPPED_FIELDS: &[&str] = &["event", "input", "tools", "tool_choice", "config", "call", "timestamp", "working_start", "pending", "uuid", "span_id"];
+
+/// Which events are searched, and for what.
+pub struct EventQuery<'a> {
+    /// Event types to search (`model`, `tool`, `sandbox`, `logger`, ...); all when empty.
+    pub types: &'a [String],
+    pub pattern: Option<&'a MessagePattern>,
+}
+
+/// An event that matched, with its searched fields as (path, text).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventMatch {
+    /// Position in the sample's event transcript.
+    pub index: usize,
+    pub kind: String,
+    pub fields: Vec<(String, String)>,
+}
+
+/// A sample's matching events.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SampleEvents {
+    pub sample_id: String,
+    pub epoch: i64,
+    pub events: Vec<EventMatch>,
+}
+
+// The text leaves of an event, by dotted path (`output.choices.0.message.content`)
+fn fields(value: &Value, path: &str, out: &mut Vec<(String, String)>) {
+    let join = |key: &str| if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter().filter(|(key, _)| !(path.is_empty() && SKIPPED_FIELDS.contains(&key.as_str()))) {
+                fields(value, &join(key), out);
+            }
+        }
+        Value::Array(values) => values.iter().enumerate().for_each(|(i, value)| fields(value, &join(&i.to_string()), out)),
+        Value::String(text) => out.push((path.to_string(), text.clone())),
+        Value::Number(number) => out.push((path.to_string(), number.to_string())),
+        Value::Bool(flag) => out.push((path.to_string(), flag.to_string())),
+        Value::Null => {}
+    }
+}
+
+impl EventQuery<'_> {
+    fn wants(&self, kind: &str) -> bool {
+        self.types.is_empty() || self.types.iter().any(|wanted| wanted == kind)
+    }
+
+    // The event when it matches; with a pattern, only the fields where it occurs are kept
+    fn check(&self, index: usize, kind: String, fields: Vec<(String, String)>) -> Option<EventMatch> {
+        let Some(pattern) = self.pattern else {
+            return Some(EventMatch { index, kind, fields });
+        };
+        let text = fields.iter().map(|(_, text)| text.as_str()).collect::<Vec<_>>().join("\n");
+        if !pattern.is_match(&text) {
+            return None;
+        }
+        let shown = match pattern.is_single_regex() {
+            true => fields.into_iter().filter(|(_, text)| pattern.regex().is_match(text)).collect(),
+            false => fields,
+        };
+        Some(EventMatch { index, kind, fields: shown })
+    }
+}
+
+// An event as read: matched, or held until the attachments it refers to are known
+enum ReadEvent {
+    Matched(EventMatch),
+    Unresolved(usize, String, Vec<(String, String)>),
+}
+
+struct EventsSeed<'a>(&'a EventQuery<'a>);
+
+impl<'de> DeserializeSeed<'de> for EventsSeed<'_> {
+    type Value = Vec<ReadEvent>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        struct EventsVisitor<'a>(&'a EventQuery<'a>);
+
+        impl<'de> Visitor<'de> for EventsVisitor<'_> {
+            type Value = Vec<ReadEvent>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a sequence of events")
+            }
+
+            // Each event is parsed, checked and dropped in turn, so a long transcript is never held whole
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut kept = Vec::new();
+                let mut index = 0;
+                while let Some(event) = seq.next_element::<Value>()? {
+                    let kind = event.get("event").and_then(Value::as_str).unwrap_or_default().to_string();
+                    if self.0.wants(&kind) {
+                        let mut found = Vec::new();
+                        fields(&event, "", &mut found);
+                        if found.iter().any(|(_, text)| text.starts_with("attachment://")) {
+                            kept.push(ReadEvent::Unresolved(index, kind, found));
+                        } else if let Some(matched) = self.0.check(index, kind, found) {
+                            kept.push(ReadEvent::Matched(matched));
+                        }
+                    }
+                    index += 1;
+                }
+                Ok(kept)
+            }
+        }
+
+        deserializer.deserialize_seq(EventsVisitor(self.0))
+    }
+}
+
+// Just a sample's id, epoch, events and inline attachments
+struct SampleSeed<'a>(&'a EventQuery<'a>);
+
+impl<'de> DeserializeSeed<'de> for SampleSeed<'_> {
+    type Value = (String, i64, Vec<ReadEvent>, HashMap<String, String>);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        struct SampleVisitor<'a>(&'a EventQuery<'a>);
+
+        impl<'de> Visitor<'de> for SampleVisitor<'_> {
+            type Value = (String, i64, Vec<ReadEvent>, HashMap<String, String>);
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("struct EvalSample")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let (mut id, mut epoch, mut events, mut attachments) = (None, None, Vec::new(), HashMap::new());
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "id" => {
+                            id = Some(match map.next_value::<Value>()? {
+                                Value::String(id) => id,
+                                id => id.to_string(),
+                            })
+                        }
+                        "epoch" => epoch = Some(map.next_value()?),
+                        "events" => events = map.next_value_seed(EventsSeed(self.0))?,
+                        "attachments" => attachments = map.next_value::<Option<HashMap<String, String>>>()?.unwrap_or_default(),
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                let id = id.ok_or_else(|| de::Error::missing_field("id"))?;
+                let epoch = epoch.ok_or_else(|| de::Error::missing_field("epoch"))?;
+                Ok((id, epoch, events, attachments))
+            }
+        }
+
+        deserializer.deserialize_map(SampleVisitor(self.0))
+    }
+}
+
+/// The matching events of each sample that has any.
+pub fn search_events(log_path: &Path, samples: &[SampleEntries], query: &EventQuery) -> Result<Vec<SampleEvents>> {
+    let mut archive = open_log(log_path)?;
+    let mut found = Vec::new();
+    for sample in samples {
+        let mut external = sample.read_attachments(&mut archive, None, Encoding::Utf8)?;
+        let reader = std::io::BufReader::new(sample.reader(&mut archive)?);
+        let (sample_id, epoch, events, inline) = SampleSeed(query).deserialize(&mut serde_json::Deserializer::from_reader(reader))?;
+        external.extend(inline);
+        let events = events
+            .into_iter()
+            .filter_map(|event| match event {
+                ReadEvent::Matched(matched) => Some(matched),
+                ReadEvent::Unresolved(index, kind, mut fields) => {
+                    for (_, text) in &mut fields {
+                        if let Some(content) = text.strip_prefix("attachment://").and_then(|hash| external.get(hash)) {
+                            *text = content.clone();
+                        }
+                    }
+                    query.check(index, kind, fields)
+                }
+            })
+            .collect::<Vec<_>>();
+        if !events.is_empty() {
+            found.push(SampleEvents { sample_id, epoch, events });
+        }
+    }
+    Ok(found)
+}
+
+/// Print a sample's matching events, each under a header naming its position and type.
+pub fn display_sample_events(log_file: &Path, sample: &SampleEvents, highlight: Option<&regex::Regex>, theme: &Theme) {
+    let file_name = log_file.file_name().unwrap().to_string_lossy();
+    for event in &sample.events {
+        let marker = format!("[event {}: {}]", event.index, event.kind).cyan().bold();
+        println!("\n{}", theme.header(&file_name, &sample.sample_id, sample.epoch, marker));
+        for (path, text) in &event.fields {
+            let text = match highlight {
+                Some(regex) => regex.replace_all(text, |caps: &regex::Captures| theme.highlight(&caps[0]).to_string()).into_owned(),
+                None => text.clone(),
+            };
+            match text.contains('\n') {
+                true => println!("{}:\n{}", path.dimmed(), text.trim_end()),
+                false => println!("{}: {}", path.dimmed(), text),
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+use crate::atomic::write_atomic;
+use crate::inspect::{ChatMessage, EvalSample};
+use crate::stats::score_label;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ExportFormat {
+    /// One rendered transcript file per matched sample
+    Files,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum TranscriptStyle {
+    Markdown,
+    Text,
+}
+
+impl TranscriptStyle {
+    fn extension(self) -> &'static str {
+        match self {
+            TranscriptStyle::Markdown => "md",
+            TranscriptStyle::Text => "txt",
+        }
+    }
+}
+
+// Where an export came from, so it can be understood long after it was made
+#[derive(Debug, Serialize)]
+pub struct ScanMetadata {
+    pub tool: &'static str,
+    pub version: &'static str,
+    // RFC 3339, UTC
+    pub scanned_at: String,
+    // The full command line, as given
+    pub command: Vec<String>,
+    pub query: ScanQuery,
+    pub files_scanned: usize,
+    pub samples_exported: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanQuery {
+    pub path: PathBuf,
+    pub message_regex: Vec<String>,
+    // Whether messages had to match every pattern, rather than any
+    pub all: bool,
+    pub invert_match: bool,
+    pub samples: Option<String>,
+    pub epochs: String,
+    pub roles: Vec<String>,
+}
+
+impl ScanMetadata {
+    pub fn new(query: ScanQuery) -> Self {
+        Self {
+            tool: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            scanned_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            command: std::env::args().collect(),
+            query,
+            files_scanned: 0,
+            samples_exported: 0,
+        }
+    }
+
+    // One line for the top of each transcript
+    fn summary(&self) -> String {
+        format!("{} {} at {}", self.tool, self.version, self.scanned_at)
+    }
+}
+
+pub const METADATA_FILE: &str = "export-metadata.json";
+
+pub fn write_metadata(dir: &Path, metadata: &ScanMetadata) -> Result<()> {
+    let json = serde_json::to_string_pretty(metadata)?;
+    write_atomic(&dir.join(METADATA_FILE), (json + "\n").as_bytes())
+}
+
+// Keep names usable as a single path component
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() || "-_.".contains(c) { c } else { '_' }).collect()
+}
+
+// `<dir>/<task>/<sample>_epoch_<n>.<ext>`
+pub fn transcript_path(dir: &Path, task: &str, sample: &EvalSample, style: TranscriptStyle) -> PathBuf {
+    dir.join(sanitize(task)).join(format!("{}_epoch_{}.{}", sanitize(&sample.id), sample.epoch, style.extension()))
+}
+
+pub fn render_transcript(
+    log_path: &Path,
+    task: &str,
+    sample: &EvalSample,
+    messages: &[(&ChatMessage, bool)],
+    style: TranscriptStyle,
+    metadata: &ScanMetadata,
+) -> String {
+    let mut out = String::new();
+    let mut scores = sample.scores.iter().map(|(scorer, score)| format!("{}: {}", scorer, score_label(&score.value))).collect::<Vec<_>>();
+    scores.sort();
+
+    match style {
+        TranscriptStyle::Markdown => {
+            writeln!(out, "# {} — sample {} epoch {}\n", task, sample.id, sample.epoch).unwrap();
+            writeln!(out, "- Log: `{}`", log_path.display()).unwrap();
+            writeln!(out, "- Exported by: {} (see `{}` in the export directory)", metadata.summary(), METADATA_FILE).unwrap();
+            if !scores.is_empty() {
+                writeln!(out, "- Scores: {}", scores.join(", ")).unwrap();
+            }
+            for (index, (message, matched)) in messages.iter().enumerate() {
+                let marker = if *matched { " (match)" } else { "" };
+                writeln!(out, "\n## {}. {}{}\n\n{}", index, message.role, marker, message.content).unwrap();
+            }
+        }
+        TranscriptStyle::Text => {
+            writeln!(out, "{} sample {} epoch {}", task, sample.id, sample.epoch).unwrap();
+            writeln!(out, "log: {}", log_path.display()).unwrap();
+            writeln!(out, "exported by: {} (see {} in the export directory)", metadata.summary(), METADATA_FILE).unwrap();
+            if !scores.is_empty() {
+                writeln!(out, "scores: {}", scores.join(", ")).unwrap();
+            }
+            for (index, (message, matched)) in messages.iter().enumerate() {
+                let marker = if *matched { " *match*" } else { "" };
+                writeln!(out, "\n[{}] {}{}\n{}", index, message.role, marker, message.content).unwrap();
+            }
+        }
+    }
+    out
+}
+
+// Exports into the same directory may run concurrently, so never leave a half-written transcript
+pub fn write_transcript(path: &Path, transcript: &str) -> Result<()> {
+    write_atomic(path, transcript.as_bytes())
+}
@@ -0,0 +1,130 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::assembly::{sample_entries, SampleEntries, SampleLayout};
+use crate::log_format::open_log;
+
+// Matching samples found during a scan, by log, for --export-eval to carve out
+#[derive(Default)]
+pub struct EvalExport {
+    samples: Mutex<BTreeMap<PathBuf, HashSet<(String, i64)>>>,
+}
+
+// Sample ids are strings or numbers in the header and reductions
+fn id_string(id: &Value) -> String {
+    match id {
+        Value::String(id) => id.clone(),
+        id => id.to_string(),
+    }
+}
+
+impl EvalExport {
+    pub fn add(&self, log_path: &Path, sample_id: &str, epoch: i64) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.entry(log_path.to_path_buf()).or_default().insert((sample_id.to_string(), epoch));
+    }
+
+    // Write the samples to a new .eval with the source log's header, adjusted to
+    // describe just them; an .eval holds a single eval, so they must all come from one log
+    pub fn write(self, output: &Path, layout: Option<&SampleLayout>) -> Result<()> {
+        let samples = self.samples.into_inner().unwrap();
+        let (log_path, keep) = match samples.len() {
+            0 => {
+                eprintln!("No matching samples, so {} was not written", output.display());
+                return Ok(());
+            }
+            1 => samples.into_iter().next().unwrap(),
+            logs => bail!("--export-eval found matching samples in {} logs, but an .eval holds one eval; narrow the scan to a single log", logs),
+        };
+        let ids = keep.iter().map(|(id, _)| id.as_str()).collect::<HashSet<_>>();
+
+        let mut archive = open_log(&log_path)?;
+        // In archive order, so header.json stays ahead of the samples as Inspect writes it
+        let file_names = (0..archive.len()).map(|i| Ok(archive.by_index_raw(i)?.name().to_string())).collect::<Result<Vec<_>>>()?;
+        let entries = sample_entries(&file_names.iter().map(String::as_str).collect::<Vec<_>>(), layout);
+        let (kept, dropped): (Vec<_>, Vec<_>) = entries.iter().partition(|sample| keep.contains(&(sample.sample_id.clone(), sample.epoch as i64)));
+        // Entries of the samples left out, unless a kept sample shares them
+        let left_out = &sample_files(&dropped) - &sample_files(&kept);
+
+        let out = std::fs::File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+        let mut writer = ZipWriter::new(out);
+        for name in &file_names {
+            let name = name.as_str();
+            let adjusted = match name {
+                "header.json" => Some(adjust_header(read_json(&mut archive, name)?, &ids, kept.len())),
+                "summaries.json" => {
+                    let mut summaries = read_json(&mut archive, name)?;
+                    if let Some(summaries) = summaries.as_array_mut() {
+                        summaries.retain(|summary| {
+                            let id = summary.get("id").map(id_string).unwrap_or_default();
+                            let epoch = summary.get("epoch").and_then(Value::as_i64).unwrap_or(1);
+                            keep.contains(&(id, epoch))
+                        });
+                    }
+                    Some(summaries)
+                }
+                "reductions.json" => {
+                    let mut reductions = read_json(&mut archive, name)?;
+                    for reduction in reductions.as_array_mut().into_iter().flatten() {
+                        if let Some(samples) = reduction.get_mut("samples").and_then(Value::as_array_mut) {
+                            samples.retain(|sample| sample.get("sample_id").is_some_and(|id| ids.contains(id_string(id).as_str())));
+                        }
+                    }
+                    Some(reductions)
+                }
+                // The journal's summaries are superseded by summaries.json
+                name if name.starts_with("_journal/summaries/") => continue,
+                name if left_out.contains(name) => continue,
+                _ => None,
+            };
+            match adjusted {
+                Some(json) => {
+                    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+                    writer.start_file(name, options)?;
+                    writer.write_all(&serde_json::to_vec(&json)?)?;
+                }
+                None => writer.raw_copy_file(archive.by_name(name)?)?,
+            }
+        }
+        writer.finish()?;
+        eprintln!("Wrote {}: {} of {} samples from {}", output.display(), kept.len(), entries.len(), log_path.display());
+        Ok(())
+    }
+}
+
+// The sample JSON and attachment entries of samples
+fn sample_files<'a>(samples: &[&'a SampleEntries]) -> HashSet<&'a str> {
+    let files = samples.iter().flat_map(|sample| sample.entries.iter().chain(sample.attachments.iter().map(|(_, entry)| entry)));
+    files.map(String::as_str).collect()
+}
+
+fn read_json<R: Read + std::io::Seek>(archive: &mut zip::ZipArchive<R>, name: &str) -> Result<Value> {
+    let mut json = Vec::new();
+    archive.by_name(name)?.read_to_end(&mut json)?;
+    serde_json::from_slice(&json).with_context(|| format!("Failed to parse {}", name))
+}
+
+// The dataset lists only the exported samples, and the results count them; the
+// metrics are left as the full run's
+fn adjust_header(mut header: Value, ids: &HashSet<&str>, samples: usize) -> Value {
+    if let Some(dataset) = header.pointer_mut("/eval/dataset").and_then(Value::as_object_mut) {
+        if let Some(sample_ids) = dataset.get_mut("sample_ids").and_then(Value::as_array_mut) {
+            sample_ids.retain(|id| ids.contains(id_string(id).as_str()));
+        }
+        dataset.insert("samples".to_string(), ids.len().into());
+    }
+    if let Some(results) = header.get_mut("results").and_then(Value::as_object_mut) {
+        for key in ["total_samples", "completed_samples"] {
+            if results.contains_key(key) {
+                results.insert(key.to_string(), samples.into());
+            }
+        }
+    }
+    header
+}
@@ -0,0 +1,55 @@
+use itertools::Itertools;
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+// The text of each match's capture groups that took part in it, or of the whole match
+// when the pattern has no groups
+pub fn captures<'a>(regex: &Regex, text: &'a str) -> Vec<&'a str> {
+    let groups = regex.captures_len();
+    regex
+        .captures_iter(text)
+        .flat_map(|caps| match groups {
+            1 => caps.get(0).into_iter().collect::<Vec<_>>(),
+            _ => (1..groups).filter_map(|i| caps.get(i)).collect(),
+        })
+        .map(|capture| capture.as_str())
+        .collect()
+}
+
+// A capture on one line, whatever it spans
+pub fn one_line(capture: &str) -> String {
+    capture.replace('\r', "\\r").replace('\n', "\\n")
+}
+
+// How often each distinct captured value was seen, for --extract-count
+#[derive(Default)]
+pub struct CaptureCounts {
+    counts: Mutex<HashMap<String, usize>>,
+}
+
+impl CaptureCounts {
+    pub fn add(&self, captures: &[&str]) {
+        let mut counts = self.counts.lock().unwrap();
+        for capture in captures {
+            *counts.entry(capture.to_string()).or_default() += 1;
+        }
+    }
+
+    // `count value` per distinct value, most frequent first, like `sort | uniq -c | sort -rn`
+    pub fn display(self) {
+        let counts = self.counts.into_inner().unwrap();
+        let width = counts.values().max().map_or(1, |max| max.to_string().len());
+        for (value, count) in counts.into_iter().sorted_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b))) {
+            println!("{:>width$} {}", count, one_line(&value));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
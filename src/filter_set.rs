@@ -0,0 +1,148 @@
+use clap::ValueEnum;
+
+use crate::counters::ScanCounters;
+use crate::inspect::{ChatMessage, ChatMessageRole, EvalSample};
+use crate::language::{self, Language};
+use crate::message_index::MessageIndexFilter;
+use crate::message_pattern::MessagePattern;
+use crate::numbers::NumberRange;
+use crate::score_filter::ScoreSelection;
+use crate::stop_reason::{self, StopReason};
+use crate::tool_calls::ToolCallFilter;
+
+/// Roles a message may have, as a bitmask over [`ChatMessageRole`]'s variants.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RoleSet(u8);
+
+impl RoleSet {
+    fn bit(role: &ChatMessageRole) -> u8 {
+        1 << ChatMessageRole::value_variants().iter().position(|variant| variant == role).unwrap_or_default()
+    }
+
+    /// Every role.
+    pub fn all() -> Self {
+        Self((1 << ChatMessageRole::value_variants().len()) - 1)
+    }
+
+    /// Just these roles, or every role when none are given.
+    pub fn of(roles: &[ChatMessageRole]) -> Self {
+        match roles {
+            [] => Self::all(),
+            roles => Self(roles.iter().fold(0, |mask, role| mask | Self::bit(role))),
+        }
+    }
+
+    /// This set less some roles.
+    pub fn without(self, roles: &[ChatMessageRole]) -> Self {
+        Self(roles.iter().fold(self.0, |mask, role| mask & !Self::bit(role)))
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn is_all(self) -> bool {
+        self == Self::all()
+    }
+
+    pub fn contains(self, role: &ChatMessageRole) -> bool {
+        self.0 & Self::bit(role) != 0
+    }
+
+    /// The roles in the set, in declaration order.
+    pub fn iter(self) -> impl Iterator<Item = &'static ChatMessageRole> {
+        ChatMessageRole::value_variants().iter().filter(move |role| self.contains(role))
+    }
+}
+
+impl Default for RoleSet {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Every message and score filter of a query, built once before a scan and shared by
+/// reference with the workers reading samples, so nothing is compiled or captured per
+/// sample. A new message filter is a field here and a stage in [`FilterSet::accepts`].
+#[derive(Debug, Clone, Default)]
+pub struct FilterSet {
+    pub roles: RoleSet,
+    /// Languages a message must be detected as, or any.
+    pub languages: Vec<Language>,
+    pub pattern: Option<MessagePattern>,
+    pub numbers: Option<NumberRange>,
+    pub tool_calls: Option<ToolCallFilter>,
+    /// Positions in the conversation a message must be at, or any.
+    pub indices: Option<MessageIndexFilter>,
+    pub scores: ScoreSelection,
+    /// Reasons the model must have stopped for on some call, or any.
+    pub stop_reasons: Vec<StopReason>,
+    /// Whether samples must have ended in an error (true) or completed (false), or either.
+    pub errored: Option<bool>,
+}
+
+impl FilterSet {
+    /// Whether the message passes every filter, counting how many reach each stage so
+    /// empty results can be explained.
+    pub fn accepts(&self, message: &ChatMessage, counters: &ScanCounters) -> bool {
+        ScanCounters::add(&counters.messages_seen, 1);
+        if !self.roles.contains(&message.role) {
+            return false;
+        }
+        ScanCounters::add(&counters.messages_with_role, 1);
+        if let Some(pattern) = &self.pattern {
+            if !pattern.is_match_for(&message.role, &message.content) {
+                return false;
+            }
+        }
+        if let Some(tool_calls) = &self.tool_calls {
+            ScanCounters::add(&counters.messages_before_tools, 1);
+            if !tool_calls.any_in(message) {
+                return false;
+            }
+        }
+        if let Some(numbers) = &self.numbers {
+            ScanCounters::add(&counters.messages_before_numbers, 1);
+            if !numbers.any_in(&message.content) {
+                return false;
+            }
+        }
+        // Detection is the slowest check, so it goes last
+        if !self.languages.is_empty() {
+            ScanCounters::add(&counters.messages_before_language, 1);
+            if !language::is_in(&message.content, &self.languages) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The messages of a conversation that pass, at the positions asked for; the rest
+    /// are `None`. Whole conversations are read unfiltered and matched with this.
+    pub fn matched<'a>(&self, messages: &'a [Option<ChatMessage>], counters: &ScanCounters) -> Vec<Option<&'a ChatMessage>> {
+        let total = messages.len();
+        let at = |index: usize| self.indices.as_ref().is_none_or(|indices| indices.contains(index, total));
+        messages.iter().enumerate().map(|(index, message)| message.as_ref().filter(|message| at(index) && self.accepts(message, counters))).collect()
+    }
+
+    /// Whether any of the sample's messages passes.
+    pub fn any_message(&self, sample: &EvalSample, counters: &ScanCounters) -> bool {
+        self.matched(&sample.messages, counters).iter().any(Option::is_some)
+    }
+
+    /// Keep the samples whose scores, stop reasons and errors are asked for. Streamed
+    /// samples were never parsed, so they're kept.
+    pub fn retain(&self, samples: &mut Vec<EvalSample>, counters: &ScanCounters) {
+        self.scores.retain(samples, counters);
+        if !self.stop_reasons.is_empty() {
+            let before = samples.len();
+            samples.retain(|sample| sample.streamed.is_some() || stop_reason::any_of(&self.stop_reasons, sample));
+            ScanCounters::add(&counters.samples_outside_stop_reason, before - samples.len());
+        }
+        if let Some(errored) = self.errored {
+            let before = samples.len();
+            samples.retain(|sample| sample.streamed.is_some() || sample.error.is_some() == errored);
+            ScanCounters::add(&counters.samples_outside_errored, before - samples.len());
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use colored::*;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::inspect::{ChatMessage, ChatMessageRole, EvalSample};
+
+// Samples listed under each fingerprint
+const EXAMPLES: usize = 5;
+
+// The shape of a conversation: each message's role, with the tools an assistant
+// message called, ignoring all content. Repeats are collapsed, e.g. `tool×3`
+pub fn structure(messages: &[&ChatMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| match (&message.role, message.tool_calls.is_empty()) {
+            (ChatMessageRole::Assistant, false) => {
+                format!("assistant[{}]", message.tool_calls.iter().map(|call| call.function.as_str()).join(","))
+            }
+            (role, _) => role.to_string(),
+        })
+        .dedup_with_count()
+        .map(|(count, step)| if count > 1 { format!("{}×{}", step, count) } else { step })
+        .join(" ")
+}
+
+// FNV-1a, so fingerprints (and content hashes) are stable across runs and builds
+pub fn hash(structure: &str) -> u64 {
+    structure.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[derive(Default)]
+struct Group {
+    structure: String,
+    // (file, sample id, epoch)
+    samples: Vec<(String, String, i64)>,
+}
+
+// Samples grouped by conversation structure
+#[derive(Default)]
+pub struct Fingerprints {
+    groups: HashMap<u64, Group>,
+}
+
+impl Fingerprints {
+    pub fn add_sample(&mut self, log_path: &Path, sample: &EvalSample) {
+        let messages = sample.messages.iter().flatten().collect::<Vec<_>>();
+        let structure = structure(&messages);
+        let group = self.groups.entry(hash(&structure)).or_default();
+        group.structure = structure;
+        group.samples.push((log_path.file_name().unwrap().to_string_lossy().to_string(), sample.id.clone(), sample.epoch));
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        for (fingerprint, group) in other.groups {
+            let entry = self.groups.entry(fingerprint).or_default();
+            entry.structure = group.structure;
+            entry.samples.extend(group.samples);
+        }
+        self
+    }
+
+    pub fn samples(&self) -> usize {
+        self.groups.values().map(|group| group.samples.len()).sum()
+    }
+
+    // Most common trajectories first
+    pub fn display(mut self) {
+        let total = self.samples();
+        let mut groups = self.groups.drain().collect::<Vec<_>>();
+        groups.sort_by(|(a_hash, a), (b_hash, b)| b.samples.len().cmp(&a.samples.len()).then(a_hash.cmp(b_hash)));
+
+        println!("{}", format!("{} distinct trajectories across {} samples", groups.len(), total).bold());
+        for (fingerprint, mut group) in groups {
+            group.samples.sort();
+            let share = 100.0 * group.samples.len() as f64 / total as f64;
+            println!(
+                "\n{}  {} samples ({:.1}%)\n  {}",
+                format!("{:016x}", fingerprint).yellow(),
+                group.samples.len(),
+                share,
+                // The structure is hashed as it is, so the fingerprint doesn't depend on how it's drawn
+                group.structure.replace('×', crate::glyphs::glyph("×", "x"))
+            );
+            let examples = group.samples.iter().take(EXAMPLES).map(|(file, id, epoch)| format!("{}:{}:{}", file, id, epoch)).join(", ");
+            let more = group.samples.len().saturating_sub(EXAMPLES);
+            let more = if more > 0 { format!(" (+{} more)", more) } else { String::new() };
+            println!("  {}{}", examples.dimmed(), more.dimmed());
+        }
+    }
+}
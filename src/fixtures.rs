@@ -0,0 +1,201 @@
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// What a synthetic log holds. Each sample is an arithmetic question: a system prompt,
+/// the user's question, `turns` rounds of the assistant calling `bash` and the tool
+/// answering, then the final answer, scored `C` or `I` by a `match` scorer. The same
+/// options always write the same log, so tests can rely on its contents.
+#[derive(Debug, Clone)]
+pub struct FixtureOptions {
+    pub task: String,
+    pub model: String,
+    pub samples: usize,
+    pub epochs: usize,
+    /// Tool call rounds before each final answer.
+    pub turns: usize,
+    /// The share of samples answered correctly, the first ones in id order.
+    pub accuracy: f64,
+    /// How many samples, the last ones in id order, end in an error instead of an answer.
+    pub errors: usize,
+    /// Picks the numbers in the questions.
+    pub seed: u64,
+}
+
+impl Default for FixtureOptions {
+    fn default() -> Self {
+        Self { task: "fixture".to_string(), model: "mockllm/model".to_string(), samples: 3, epochs: 1, turns: 1, accuracy: 0.5, errors: 0, seed: 0 }
+    }
+}
+
+// How a sample (in every epoch) turns out
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Correct,
+    Incorrect,
+    Errored,
+}
+
+impl FixtureOptions {
+    fn outcome(&self, index: usize) -> Outcome {
+        let answered = self.samples.saturating_sub(self.errors);
+        let correct = (self.accuracy.clamp(0.0, 1.0) * answered as f64).round() as usize;
+        match index {
+            index if index >= answered => Outcome::Errored,
+            index if index < correct => Outcome::Correct,
+            _ => Outcome::Incorrect,
+        }
+    }
+
+    // The operands of a sample's question, from a splitmix64 step on the seed
+    fn operands(&self, index: usize) -> (u64, u64) {
+        let mut z = self.seed.wrapping_add((index as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z % 90 + 10, (z >> 32) % 90 + 10)
+    }
+}
+
+fn model_event(model: &str, content: &str) -> Value {
+    json!({
+        "event": "model",
+        "model": model,
+        "input": [],
+        "tools": [{"name": "bash", "description": "Run a shell command"}],
+        "config": {"temperature": 0.0},
+        "output": {
+            "choices": [{"message": {"role": "assistant", "content": content}, "stop_reason": "stop"}],
+            "usage": {"input_tokens": 20, "output_tokens": 8, "total_tokens": 28},
+        },
+    })
+}
+
+/// The JSON of one sample, as Inspect writes it under `samples/`; `index` counts from
+/// 0 and the sample's id is `index + 1`.
+pub fn sample(options: &FixtureOptions, index: usize, epoch: usize) -> Value {
+    let (a, b) = options.operands(index);
+    let sum = a + b;
+    let question = format!("What is {}+{}?", a, b);
+    let mut messages = vec![
+        json!({"role": "system", "content": "You are a helpful assistant."}),
+        json!({"role": "user", "content": format!("{} Answer precisely.", question)}),
+    ];
+    let mut events = Vec::new();
+    for turn in 0..options.turns {
+        let call_id = format!("call_{}", turn + 1);
+        let content = "Let me compute with bash.";
+        messages.push(json!({
+            "role": "assistant",
+            "content": content,
+            "tool_calls": [{"id": call_id, "function": "bash", "arguments": {"cmd": format!("echo $(({}+{}))", a, b)}, "type": "function"}],
+        }));
+        messages.push(json!({"role": "tool", "content": format!("{}\n", sum), "tool_call_id": call_id, "function": "bash"}));
+        events.push(model_event(&options.model, content));
+    }
+
+    let outcome = options.outcome(index);
+    let answer = match outcome {
+        Outcome::Incorrect => sum + 1,
+        _ => sum,
+    };
+    let mut sample = json!({
+        "id": (index + 1).to_string(),
+        "epoch": epoch,
+        "input": question,
+        "target": sum.to_string(),
+        "metadata": {"a": a, "b": b},
+        "attachments": {},
+    });
+    let fields = sample.as_object_mut().unwrap();
+    match outcome {
+        Outcome::Errored => {
+            fields.insert("output".to_string(), json!({"model": options.model, "choices": []}));
+            fields.insert("scores".to_string(), json!({}));
+            fields.insert("error".to_string(), json!({"message": "RuntimeError: sandbox container exited", "traceback": "Traceback (most recent call last):\nRuntimeError: sandbox container exited\n"}));
+        }
+        _ => {
+            let content = format!("The answer is {}.", answer);
+            messages.push(json!({"role": "assistant", "content": content}));
+            events.push(model_event(&options.model, &content));
+            fields.insert("output".to_string(), json!({
+                "model": options.model,
+                "choices": [{"message": {"role": "assistant", "content": content}, "stop_reason": "stop"}],
+                "usage": {"input_tokens": 20, "output_tokens": 8, "total_tokens": 28},
+            }));
+            let value = if outcome == Outcome::Correct { "C" } else { "I" };
+            fields.insert("scores".to_string(), json!({"match": {"value": value, "answer": answer.to_string(), "explanation": format!("expected {}", sum)}}));
+        }
+    }
+    fields.insert("messages".to_string(), Value::Array(messages));
+    fields.insert("events".to_string(), Value::Array(events));
+    sample
+}
+
+/// The log's `header.json`.
+pub fn header(options: &FixtureOptions) -> Value {
+    let scored = (0..options.samples).filter(|index| options.outcome(*index) != Outcome::Errored).count();
+    let correct = (0..options.samples).filter(|index| options.outcome(*index) == Outcome::Correct).count();
+    let accuracy = if scored == 0 { 0.0 } else { correct as f64 / scored as f64 };
+    json!({
+        "version": 2,
+        "status": "success",
+        "eval": {
+            "run_id": format!("run-{}", options.task),
+            "created": "2025-01-01T00:00:00+00:00",
+            "task": options.task,
+            "task_id": format!("{}-id", options.task),
+            "task_args": {},
+            "model": options.model,
+            "dataset": {
+                "name": format!("{}_dataset", options.task),
+                "samples": options.samples,
+                "sample_ids": (1..=options.samples).map(|id| id.to_string()).collect::<Vec<_>>(),
+            },
+            "config": {"epochs": options.epochs},
+        },
+        "results": {
+            "total_samples": options.samples * options.epochs,
+            "completed_samples": scored * options.epochs,
+            "scores": [{
+                "name": "match",
+                "scorer": "match",
+                "reducer": "mean",
+                "metrics": {"accuracy": {"name": "accuracy", "value": accuracy}},
+            }],
+        },
+    })
+}
+
+// Every epoch of a sample scores the same, so its reduced score is its score
+fn reductions(options: &FixtureOptions) -> Value {
+    let samples = (0..options.samples)
+        .filter(|index| options.outcome(*index) != Outcome::Errored)
+        .map(|index| json!({"sample_id": (index + 1).to_string(), "value": if options.outcome(index) == Outcome::Correct { 1.0 } else { 0.0 }}))
+        .collect::<Vec<_>>();
+    json!([{"scorer": "match", "reducer": "mean", "samples": samples}])
+}
+
+/// Write a synthetic `.eval` log to `path`.
+pub fn write(path: &Path, options: &FixtureOptions) -> Result<()> {
+    let file = std::fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = ZipWriter::new(file);
+    let mut entry = |name: &str, json: &Value| -> Result<()> {
+        writer.start_file(name, FileOptions::default().compression_method(CompressionMethod::Deflated))?;
+        writer.write_all(&serde_json::to_vec(json)?)?;
+        Ok(())
+    };
+    entry("header.json", &header(options))?;
+    for index in 0..options.samples {
+        for epoch in 1..=options.epochs {
+            entry(&format!("samples/{}_epoch_{}.json", index + 1, epoch), &sample(options, index, epoch))?;
+        }
+    }
+    entry("reductions.json", &reductions(options))?;
+    entry("summaries.json", &json!([]))?;
+    writer.finish()?;
+    Ok(())
+}
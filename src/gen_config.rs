@@ -0,0 +1,71 @@
+use itertools::Itertools;
+use serde_json::Value;
+
+use crate::inspect::{ChatMessage, ChatMessageRole, ModelCall};
+
+// Settings shown, in this order, when a call sets them
+const SHOWN: &[&str] = &["temperature", "top_p", "top_k", "seed", "max_tokens", "reasoning_effort", "reasoning_tokens"];
+
+/// Generation settings the call behind a matched assistant message must have used, for
+/// --temperature and --seed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GenConfigFilter {
+    pub temperature: Option<f64>,
+    pub seed: Option<i64>,
+}
+
+impl GenConfigFilter {
+    pub fn is_empty(&self) -> bool {
+        self.temperature.is_none() && self.seed.is_none()
+    }
+
+    fn accepts(&self, call: Option<&ModelCall>) -> bool {
+        let setting = |key: &str| call.and_then(|call| call.config.get(key));
+        self.temperature.is_none_or(|temperature| setting("temperature").and_then(Value::as_f64) == Some(temperature))
+            && self.seed.is_none_or(|seed| setting("seed").and_then(Value::as_i64) == Some(seed))
+    }
+
+    /// Drop the matched messages that aren't assistant messages from a call with these
+    /// settings; `calls` is [`producing_calls`] of the same sample.
+    pub fn retain(&self, matched: &mut [Option<&ChatMessage>], calls: &[Option<&ModelCall>]) {
+        for (message, call) in matched.iter_mut().zip(calls) {
+            if message.is_some() && !self.accepts(*call) {
+                *message = None;
+            }
+        }
+    }
+}
+
+/// The model call behind each of a sample's assistant messages: the call whose output is
+/// the message's text, or else the one at the message's position among the assistant
+/// messages, when every message was kept and there's exactly one call for each. Other
+/// messages have none.
+pub fn producing_calls<'a>(messages: &[Option<ChatMessage>], calls: &'a [ModelCall]) -> Vec<Option<&'a ModelCall>> {
+    let turns = messages.iter().positions(|message| message.as_ref().is_some_and(|message| message.role == ChatMessageRole::Assistant)).collect::<Vec<_>>();
+    let positional = turns.len() == calls.len() && messages.iter().all(Option::is_some);
+    messages
+        .iter()
+        .enumerate()
+        .map(|(index, message)| {
+            let message = message.as_ref().filter(|message| message.role == ChatMessageRole::Assistant)?;
+            let by_text = calls.iter().find(|call| call.message.as_deref() == Some(&*message.content));
+            by_text.or_else(|| positional.then(|| turns.iter().position(|turn| *turn == index).map(|turn| &calls[turn])).flatten())
+        })
+        .collect()
+}
+
+/// The settings a call set, as `temperature=0.7 seed=42`, or `default settings`.
+pub fn label(call: &ModelCall) -> String {
+    let settings = SHOWN
+        .iter()
+        .filter_map(|key| {
+            // Strings unquoted, as `reasoning_effort=high`
+            let value = call.config.get(*key)?;
+            Some(format!("{}={}", key, value.as_str().map_or_else(|| value.to_string(), str::to_string)))
+        })
+        .join(" ");
+    match settings.is_empty() {
+        true => "default settings".to_string(),
+        false => settings,
+    }
+}
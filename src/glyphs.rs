@@ -0,0 +1,44 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+// Whether the terminal can be expected to show Unicode: its locale's encoding is UTF-8,
+// and it isn't the Linux console or a terminal known to be limited. The locale is read
+// as setlocale does, LC_ALL over LC_CTYPE over LANG
+pub fn unicode_supported() -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    let term = std::env::var("TERM").unwrap_or_default();
+    if term == "linux" || term == "dumb" || term.starts_with("vt") {
+        return false;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter().filter_map(|name| std::env::var(name).ok()).find(|value| !value.is_empty());
+    locale.is_some_and(|locale| {
+        let locale = locale.to_ascii_lowercase();
+        locale.contains("utf-8") || locale.contains("utf8")
+    })
+}
+
+// Draw with ASCII only from now on, when asked to or the terminal can't show Unicode
+pub fn init(force_ascii: bool) {
+    ASCII.store(force_ascii || !unicode_supported(), Ordering::Relaxed);
+}
+
+pub fn ascii() -> bool {
+    ASCII.load(Ordering::Relaxed)
+}
+
+// `unicode`, or `ascii` on terminals that can't show it
+pub fn glyph(unicode: &'static str, ascii: &'static str) -> &'static str {
+    if self::ascii() {
+        ascii
+    } else {
+        unicode
+    }
+}
+
+// Ellipsis marking text cut short
+pub fn ellipsis() -> &'static str {
+    glyph("…", "...")
+}
@@ -0,0 +1,22 @@
+use regex::Regex;
+use std::path::Path;
+
+use crate::inspect::ChatMessage;
+use crate::streaming::Streamed;
+
+// `path:sample:epoch:msgidx:line` for each line of a message that matches (every line without a pattern),
+// uncolored, so tools that parse `grep -H -n` output can consume it
+pub fn display_grep_lines(source: (&Path, &str, i64), index: usize, message: &ChatMessage, regex: Option<&Regex>) {
+    let (path, sample_id, epoch) = source;
+    for line in message.content.lines().filter(|line| regex.map_or(!line.is_empty(), |re| re.is_match(line))) {
+        println!("{}:{}:{}:{}:{}", path.display(), sample_id, epoch, index, line);
+    }
+}
+
+// Streamed samples were never split into messages, so their snippets have no index
+pub fn display_grep_snippets(source: (&Path, &str, i64), streamed: &Streamed) {
+    let (path, sample_id, epoch) = source;
+    for snippet in &streamed.snippets {
+        println!("{}:{}:{}:-:{}", path.display(), sample_id, epoch, snippet);
+    }
+}
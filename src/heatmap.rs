@@ -0,0 +1,45 @@
+use colored::*;
+use std::path::Path;
+
+use crate::glyphs::{self, glyph};
+use crate::inspect::{ChatMessage, EvalSample};
+use crate::summary::scores;
+use crate::theme::Theme;
+
+// Cells in a strip, each covering an equal share of the conversation, so strips of
+// conversations of any length line up
+const STRIP_WIDTH: usize = 40;
+// A cell by the share of its messages that matched, from a few to all (`·` for none)
+const SHADES: [&str; 4] = ["░", "▒", "▓", "█"];
+const ASCII_SHADES: [&str; 4] = [":", "+", "*", "#"];
+
+// Where in the conversation the matched messages fall, start to end
+fn strip(matched: &[Option<&ChatMessage>]) -> String {
+    let total = matched.len();
+    (0..STRIP_WIDTH)
+        .map(|cell| {
+            // Conversations shorter than the strip spread each message over several cells
+            let start = cell * total / STRIP_WIDTH;
+            let end = ((cell + 1) * total / STRIP_WIDTH).max(start + 1).min(total);
+            let hits = matched[start..end].iter().filter(|m| m.is_some()).count();
+            match hits {
+                0 => glyph("·", ".").dimmed().to_string(),
+                hits => {
+                    let shades = if glyphs::ascii() { ASCII_SHADES } else { SHADES };
+                    shades[(hits * shades.len()).div_ceil(end - start) - 1].to_string()
+                }
+            }
+        })
+        .collect()
+}
+
+/// One line for a matched sample: a strip showing where in its conversation the matches
+/// are, how many messages matched out of how many, and where the sample is.
+pub fn display_heatmap_line(path: &Path, sample: &EvalSample, matched: &[Option<&ChatMessage>], score: Option<&str>, theme: &Theme) {
+    if matched.is_empty() {
+        return;
+    }
+    let count = format!("{:>4}/{:<4}", matched.iter().flatten().count(), matched.len());
+    let header = theme.header(&path.file_name().unwrap().to_string_lossy(), &sample.id, sample.epoch, score.map_or_else(|| scores(sample), str::to_string).bold());
+    println!("{}{}{} {} {}", glyph("▕", "|"), strip(matched), glyph("▏", "|"), count.dimmed(), header);
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use colored::*;
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+
+use crate::assembly::{sample_entries, SampleLayout};
+use crate::inspect::{ChatMessage, ChatMessageRole, EvalLogHeader, ReadOptions};
+use crate::log_format::open_log;
+use crate::skipped;
+use crate::stats::score_label;
+use crate::{Filter, IntFilter};
+
+// Characters of the final answer shown per run
+const ANSWER_WIDTH: usize = 80;
+
+// One epoch of the sample in one log
+struct Run {
+    path: PathBuf,
+    // When the run started: the header's `created`, else the log's modification time
+    time: String,
+    model: String,
+    epoch: u32,
+    // (scorer, score label), by scorer
+    scores: Vec<(String, String)>,
+    answer: Option<String>,
+}
+
+fn log_time(path: &Path, header: Option<&EvalLogHeader>) -> String {
+    header.and_then(|h| h.eval.created.clone()).unwrap_or_else(|| {
+        std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map(|time| DateTime::<Utc>::from(time).to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+            .unwrap_or_default()
+    })
+}
+
+fn runs_in_log(path: &Path, sample_id: &str, epochs: &IntFilter, layout: Option<&SampleLayout>) -> Result<Vec<Run>> {
+    let mut archive = open_log(path)?;
+    let file_names = archive.file_names().collect::<Vec<_>>();
+    let entries = sample_entries(&file_names, layout)
+        .into_iter()
+        .filter(|sample| sample.sample_id == sample_id && epochs.filter(&sample.epoch))
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let header: Option<EvalLogHeader> = crate::read_json_entry(path, "header.json")?;
+    let time = log_time(path, header.as_ref());
+    let model = header.map(|h| h.eval.model).unwrap_or_default();
+    let runs = entries
+        .iter()
+        .filter_map(|entry| {
+            let read = crate::read_sample_from(&mut archive, entry, ReadOptions::default(), None, |_: &ChatMessage| true);
+            let sample = skipped::or_skip(read, format_args!("{}: {}", path.display(), entry.name()))?;
+            let mut scores = sample.scores.iter().map(|(name, score)| (name.clone(), score_label(&score.value))).collect::<Vec<_>>();
+            scores.sort();
+            let answer = sample
+                .messages
+                .iter()
+                .flatten()
+                .rfind(|m| m.role == ChatMessageRole::Assistant)
+                .map(|m| m.content.split_whitespace().collect::<Vec<_>>().join(" "));
+            Some(Run { path: path.to_path_buf(), time: time.clone(), model: model.clone(), epoch: entry.epoch, scores, answer })
+        })
+        .collect();
+    Ok(runs)
+}
+
+pub fn preview(answer: &str) -> String {
+    match answer.char_indices().nth(ANSWER_WIDTH) {
+        Some((end, _)) => format!("{}{}", &answer[..end], crate::glyphs::ellipsis()),
+        None => answer.to_string(),
+    }
+}
+
+// Every run of a sample across logs, oldest first, with how its scores and answer changed
+pub fn run(sample_id: &str, paths: &[PathBuf], epochs: &IntFilter, layout: Option<&SampleLayout>) -> Result<()> {
+    let mut runs = paths
+        .par_iter()
+        .filter_map(|path| skipped::or_skip(runs_in_log(path, sample_id, epochs, layout), path.display()))
+        .flatten()
+        .collect::<Vec<_>>();
+    runs.sort_by(|a, b| (&a.time, &a.path, a.epoch).cmp(&(&b.time, &b.path, b.epoch)));
+
+    let logs = runs.iter().map(|r| &r.path).collect::<std::collections::HashSet<_>>().len();
+    println!("{}", format!("history of sample {} across {} logs ({} runs)", sample_id, logs, runs.len()).bold());
+
+    let mut changes = 0;
+    let mut previous: Option<&Run> = None;
+    for run in &runs {
+        let scores = run
+            .scores
+            .iter()
+            .map(|(scorer, label)| {
+                let before = previous.and_then(|p| p.scores.iter().find(|(s, _)| s == scorer)).map(|(_, l)| l);
+                match before {
+                    Some(before) if before != label => {
+                        changes += 1;
+                        format!("{} {} {}", scorer, label.yellow().bold(), format!("(was {})", before).dimmed())
+                    }
+                    _ => format!("{} {}", scorer, label.bold()),
+                }
+            })
+            .collect::<Vec<_>>();
+        let answer = match (&run.answer, previous.and_then(|p| p.answer.as_ref())) {
+            (Some(answer), Some(before)) if answer == before => "(same answer)".dimmed().to_string(),
+            (Some(answer), _) => preview(answer),
+            (None, _) => "(no answer)".dimmed().to_string(),
+        };
+        println!(
+            "\n{}  {}  {}  epoch {}  {}\n  {}",
+            run.time.green(),
+            run.path.file_name().unwrap().to_string_lossy().cyan(),
+            run.model,
+            run.epoch,
+            scores.join(", "),
+            answer
+        );
+        previous = Some(run);
+    }
+
+    if runs.is_empty() {
+        eprintln!("{} no logs under the given path contain sample '{}'", "hint:".yellow().bold(), sample_id);
+    } else {
+        eprintln!("{} score changes across {} runs", changes, runs.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::atomic::{lock, write_atomic};
+
+// Queries remembered, most recent last
+const MAX_QUERIES: usize = 100;
+
+// The logs that matched one earlier query
+#[derive(Serialize, Deserialize)]
+struct QueryHits {
+    pattern: String,
+    files: Vec<PathBuf>,
+}
+
+// Which logs matched recent --hot-first searches, kept in the user's cache directory
+#[derive(Serialize, Deserialize, Default)]
+pub struct HitHistory {
+    queries: Vec<QueryHits>,
+}
+
+fn history_path() -> Option<PathBuf> {
+    Some(crate::log_index::cache_dir()?.join("hits.json"))
+}
+
+fn words(pattern: &str) -> HashSet<String> {
+    pattern
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// 1 for the same pattern, else the share of words the patterns have in common
+fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl HitHistory {
+    // A missing or unreadable history is an empty one
+    pub fn load() -> Self {
+        history_path().map(|path| Self::read(&path)).unwrap_or_default()
+    }
+
+    fn read(path: &Path) -> Self {
+        std::fs::read(path).ok().and_then(|bytes| serde_json::from_slice(&bytes).ok()).unwrap_or_default()
+    }
+
+    // Order logs by how often they matched earlier queries, weighted by how similar
+    // each query was to this one; logs that never matched keep their order, last
+    pub fn sort(&self, paths: &mut [PathBuf], pattern: &str) {
+        let mut scores = HashMap::<&Path, f64>::new();
+        for query in &self.queries {
+            let weight = similarity(&query.pattern, pattern);
+            if weight > 0.0 {
+                for file in &query.files {
+                    *scores.entry(file.as_path()).or_default() += weight;
+                }
+            }
+        }
+        paths.sort_by_cached_key(|path| {
+            let score = scores.get(canonical(path).as_path()).copied().unwrap_or(0.0);
+            std::cmp::Reverse((score * 1000.0) as u64)
+        });
+    }
+
+    // Add a query's matches to the history on disk, re-read under a lock so that
+    // searches finishing at the same time each keep the other's
+    pub fn record(pattern: &str, files: impl IntoIterator<Item = PathBuf>) -> Result<()> {
+        let Some(path) = history_path() else { return Ok(()) };
+        let mut files = files.into_iter().map(|file| canonical(&file)).collect::<Vec<_>>();
+        files.sort();
+        let _lock = lock(&path)?;
+        let mut history = Self::read(&path);
+        history.queries.retain(|query| query.pattern != pattern);
+        history.queries.push(QueryHits { pattern: pattern.to_string(), files });
+        let excess = history.queries.len().saturating_sub(MAX_QUERIES);
+        history.queries.drain(..excess);
+        write_atomic(&path, &serde_json::to_vec(&history)?)
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use clap::ValueEnum;
+use colored::*;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+
+use crate::content::Content;
+
+// Widest preview, in terminal columns, and the pixel size sixel previews are scaled to
+// fit, assuming cells of roughly 10x20 pixels
+const MAX_COLUMNS: usize = 80;
+const MAX_SIXEL_WIDTH: u32 = 800;
+const MAX_SIXEL_HEIGHT: u32 = 480;
+// Kitty takes its payload in chunks of at most this many base64 bytes
+const KITTY_CHUNK: usize = 4096;
+
+/// How image content is drawn in the terminal.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ImageProtocol {
+    /// Whichever the terminal is known to support, or none when it isn't recognized
+    Auto,
+    /// The kitty graphics protocol (kitty, Ghostty, Konsole)
+    Kitty,
+    /// iTerm2's inline images (iTerm2, WezTerm, VS Code)
+    Iterm,
+    /// Sixel graphics (foot, mlterm, xterm -ti vt340, Windows Terminal)
+    Sixel,
+}
+
+impl ImageProtocol {
+    /// The protocol to draw with: an explicit one as given, `auto` from the terminal's
+    /// environment (and never when stdout isn't a terminal).
+    pub fn resolve(self) -> Option<ImageProtocol> {
+        if self != ImageProtocol::Auto {
+            return Some(self);
+        }
+        if !std::io::stdout().is_terminal() {
+            return None;
+        }
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let (term, program) = (var("TERM"), var("TERM_PROGRAM"));
+        match program.as_str() {
+            "iTerm.app" | "WezTerm" | "vscode" => return Some(ImageProtocol::Iterm),
+            "ghostty" => return Some(ImageProtocol::Kitty),
+            _ => {}
+        }
+        if std::env::var_os("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || std::env::var_os("KONSOLE_VERSION").is_some() {
+            Some(ImageProtocol::Kitty)
+        } else if term.starts_with("foot") || term.starts_with("mlterm") || std::env::var_os("WT_SESSION").is_some() {
+            Some(ImageProtocol::Sixel)
+        } else {
+            None
+        }
+    }
+}
+
+// An image's bytes and format, from a base64 data URI
+fn decode_data_uri(source: &str) -> Result<(Vec<u8>, Option<ImageFormat>)> {
+    let Some((header, data)) = source.strip_prefix("data:").and_then(|uri| uri.split_once(',')) else {
+        bail!("not embedded in the log");
+    };
+    let mime = header.strip_suffix(";base64").context("not base64-encoded")?;
+    let bytes = base64::engine::general_purpose::STANDARD.decode(data.trim()).context("invalid base64")?;
+    let format = ImageFormat::from_mime_type(mime).or_else(|| image::guess_format(&bytes).ok());
+    Ok((bytes, format))
+}
+
+fn decode_image(bytes: &[u8], format: Option<ImageFormat>) -> Result<DynamicImage> {
+    Ok(match format {
+        Some(format) => image::load_from_memory_with_format(bytes, format)?,
+        None => image::load_from_memory(bytes)?,
+    })
+}
+
+// Columns wide to draw an image `width` pixels wide
+fn columns(width: u32) -> usize {
+    let terminal = term_size::dimensions().map_or(MAX_COLUMNS, |(columns, _)| columns);
+    (width as usize).div_ceil(10).clamp(1, MAX_COLUMNS.min(terminal))
+}
+
+fn kitty(bytes: Vec<u8>, format: Option<ImageFormat>) -> Result<String> {
+    // Kitty reads PNG itself; anything else is re-encoded as PNG
+    let image = decode_image(&bytes, format)?;
+    let png = match format {
+        Some(ImageFormat::Png) => bytes,
+        _ => {
+            let mut png = Vec::new();
+            image.write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)?;
+            png
+        }
+    };
+    let payload = base64::engine::general_purpose::STANDARD.encode(png);
+    let chunks = payload.as_bytes().chunks(KITTY_CHUNK).collect::<Vec<_>>();
+    let mut out = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        let chunk = std::str::from_utf8(chunk)?;
+        match i {
+            // q=2 keeps the terminal from answering on stdin
+            0 => write!(out, "\x1b_Ga=T,f=100,q=2,c={},m={};{}\x1b\\", columns(image.width()), more, chunk)?,
+            _ => write!(out, "\x1b_Gm={};{}\x1b\\", more, chunk)?,
+        }
+    }
+    Ok(out)
+}
+
+fn iterm(bytes: Vec<u8>, format: Option<ImageFormat>) -> Result<String> {
+    let (width, _) = decode_image(&bytes, format)?.dimensions();
+    let payload = base64::engine::general_purpose::STANDARD.encode(&bytes);
+    Ok(format!("\x1b]1337;File=inline=1;size={};width={};preserveAspectRatio=1:{}\x07", bytes.len(), columns(width), payload))
+}
+
+// The image scaled to fit, quantized to a 6x6x6 color cube, as sixel bands of six rows
+fn sixel(bytes: Vec<u8>, format: Option<ImageFormat>) -> Result<String> {
+    let mut image = decode_image(&bytes, format)?;
+    if image.width() > MAX_SIXEL_WIDTH || image.height() > MAX_SIXEL_HEIGHT {
+        image = image.thumbnail(MAX_SIXEL_WIDTH, MAX_SIXEL_HEIGHT);
+    }
+    let image = image.to_rgba8();
+    let (width, height) = image.dimensions();
+    let level = |channel: u8| (channel as usize * 5 + 127) / 255;
+    // Palette register per pixel, None where it's transparent
+    let registers = image.pixels().map(|pixel| (pixel[3] >= 128).then(|| level(pixel[0]) * 36 + level(pixel[1]) * 6 + level(pixel[2]))).collect::<Vec<_>>();
+
+    let mut out = format!("\x1bP0;1;0q\"1;1;{};{}", width, height);
+    for register in 0..216 {
+        let percent = |level: usize| level * 100 / 5;
+        write!(out, "#{};2;{};{};{}", register, percent(register / 36), percent(register / 6 % 6), percent(register % 6))?;
+    }
+    for band in (0..height).step_by(6) {
+        let rows = band..(band + 6).min(height);
+        let mut used = vec![false; 216];
+        for y in rows.clone() {
+            for x in 0..width {
+                if let Some(register) = registers[(y * width + x) as usize] {
+                    used[register] = true;
+                }
+            }
+        }
+        for register in (0..216).filter(|register| used[*register]) {
+            write!(out, "#{}", register)?;
+            let sixels = (0..width)
+                .map(|x| {
+                    let bits = rows.clone().filter(|y| registers[(y * width + x) as usize] == Some(register)).fold(0, |bits, y| bits | 1 << (y - band));
+                    (63 + bits) as u8 as char
+                })
+                .collect::<Vec<_>>();
+            // Runs of the same sixel are written once, with a repeat count
+            for run in sixels.chunk_by(|a, b| a == b) {
+                match run.len() {
+                    1..=3 => out.extend(run),
+                    count => write!(out, "!{}{}", count, run[0])?,
+                }
+            }
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\");
+    Ok(out)
+}
+
+/// Draw each image in the content, in order, below its text. Images that can't be
+/// drawn (linked rather than embedded, or undecodable) get a note instead.
+pub fn display_images(content: &Content, protocol: ImageProtocol) {
+    for source in content.images() {
+        let preview = decode_data_uri(source).and_then(|(bytes, format)| match protocol {
+            ImageProtocol::Kitty | ImageProtocol::Auto => kitty(bytes, format),
+            ImageProtocol::Iterm => iterm(bytes, format),
+            ImageProtocol::Sixel => sixel(bytes, format),
+        });
+        match preview {
+            Ok(preview) => println!("{}", preview),
+            Err(e) => println!("{}", format!("[no preview: {:#}]", e).dimmed()),
+        }
+    }
+}
@@ -1,9 +1,22 @@
+//! Inspect's sample format, and a deserializer that filters a sample's messages
+//! while it's being parsed.
+
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::io::Read;
+use std::rc::Rc;
 use serde::{de, Deserialize, Deserializer, Serialize};
 use serde::de::{DeserializeSeed, MapAccess, SeqAccess, Visitor};
 use clap::ValueEnum;
 
-#[derive(Debug, Serialize, Deserialize, Clone, ValueEnum, PartialEq)]
+use crate::content::Content;
+use crate::encoding::Encoding;
+use crate::message_index::MessageIndexFilter;
+use crate::streaming::Streamed;
+
+/// Who a message is from.
+#[derive(Debug, Serialize, Deserialize, Clone, ValueEnum, PartialEq, Default)]
 pub enum ChatMessageRole {
     #[serde(rename = "system")]
     System,
@@ -12,7 +25,11 @@ pub enum ChatMessageRole {
     #[serde(rename = "assistant")]
     Assistant,
     #[serde(rename = "tool")]
-    Tool
+    Tool,
+    /// A missing, empty or unrecognized role, from scaffold bugs.
+    #[default]
+    #[serde(rename = "unknown", other)]
+    Unknown,
 }
 
 impl std::fmt::Display for ChatMessageRole {
@@ -22,6 +39,7 @@ impl std::fmt::Display for ChatMessageRole {
             ChatMessageRole::User => write!(f, "user"),
             ChatMessageRole::Assistant => write!(f, "assistant"),
             ChatMessageRole::Tool => write!(f, "tool"),
+            ChatMessageRole::Unknown => write!(f, "unknown"),
         }
     }
 }
@@ -51,63 +69,486 @@ impl std::fmt::Display for ChatMessageRole {
 //     }
 // }
 
-
-#[derive(Debug, Serialize, Deserialize)]
+/// One message of a sample's transcript.
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChatMessage {
+    #[serde(default, deserialize_with = "null_as_default")]
     pub role: ChatMessageRole,
-    pub content: String,
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub content: Content,
+    /// Tool calls made by an assistant message.
+    #[serde(default, deserialize_with = "null_as_default", skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// The call a tool message answers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolCall {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub function: String,
+    #[serde(default)]
+    pub arguments: serde_json::Value,
+}
+
+// Inspect writes `null` for absent optional lists
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct EvalDataset {
-    pub name: String,
-    pub sample_ids: Vec<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub sample_ids: Vec<serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Default)]
 pub struct EvalLogConfig {
-    pub epochs: u32,
-    pub message_limit: u32,
+    #[serde(default)]
+    pub epochs: Option<u32>,
+    #[serde(default)]
+    pub message_limit: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EvalLogHeader {
     pub eval: EvalSpec,
-    pub dataset: EvalDataset,
-    pub config: EvalLogConfig,
+    #[serde(default)]
+    pub results: Option<EvalResults>,
+    // Solver steps, whose params may list the tools the task offers
+    #[serde(default)]
+    pub plan: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EvalSpec {
     pub run_id: String,
+    // When the run started, RFC 3339
+    #[serde(default)]
+    pub created: Option<String>,
     pub task: String,
+    #[serde(default)]
+    pub model: String,
+    #[serde(default)]
+    pub dataset: EvalDataset,
+    #[serde(default)]
+    pub config: EvalLogConfig,
+    // Sandbox the task's samples run in, as `{"type": ..., "config": ...}` or `[type, config]`
+    #[serde(default)]
+    pub sandbox: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvalResults {
+    #[serde(default)]
+    pub total_samples: u64,
+    #[serde(default)]
+    pub completed_samples: u64,
+    #[serde(default)]
+    pub scores: Vec<EvalScore>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvalScore {
+    pub name: String,
+    #[serde(default)]
+    pub reducer: Option<String>,
+    // Metric name -> `{"name": ..., "value": ...}`
+    #[serde(default)]
+    pub metrics: serde_json::Map<String, serde_json::Value>,
+}
+
+// Per-sample scores after reducing across epochs (`reductions.json`)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EvalSampleReductions {
+    pub scorer: String,
+    #[serde(default)]
+    pub reducer: Option<String>,
+    pub samples: Vec<ReducedScore>,
+}
+
+// One sample's entry in a reduction
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReducedScore {
+    #[serde(default)]
+    pub sample_id: serde_json::Value,
+    #[serde(flatten)]
+    pub score: Score,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Score {
+    pub value: serde_json::Value,
+    // The answer the scorer extracted from the output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub answer: Option<String>,
+    // Why the scorer gave this score
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+}
+
+impl Score {
+    // Numeric value of the score, using Inspect's conventions for letter grades
+    pub fn as_f64(&self) -> Option<f64> {
+        match &self.value {
+            serde_json::Value::Number(n) => n.as_f64(),
+            serde_json::Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            serde_json::Value::String(s) => match s.as_str() {
+                "C" => Some(1.0),
+                "P" => Some(0.5),
+                "I" | "N" => Some(0.0),
+                other => other.parse().ok(),
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ModelOutput {
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub choices: Vec<ChatCompletionChoice>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChatCompletionChoice {
+    // `finish_reason` in logs written by other tools in OpenAI's format
+    #[serde(default, alias = "finish_reason")]
+    pub stop_reason: Option<String>,
+}
+
+// The limit (messages, tokens, time, ...) that ended a sample early
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SampleLimit {
+    #[serde(rename = "type")]
+    pub kind: String,
 }
 
-#[derive(Debug)]
+// The error that ended a sample before it completed
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SampleError {
+    #[serde(default)]
+    pub message: String,
+    #[serde(default)]
+    pub traceback: String,
+}
+
+/// One epoch of a sample.
+///
+/// Logs are read with [`FilteredEvalSampleDeserializer`]; the derived impls only
+/// round-trip samples spilled to disk.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct EvalSample {
     pub id: String,
     pub epoch: i64,
     pub messages: Vec<Option<ChatMessage>>,
+    // Byte range of each message's JSON object within the (decompressed) sample entry
+    pub message_spans: Vec<(u64, u64)>,
+    // Zip entry the sample was read from
+    pub entry: String,
+    pub scores: HashMap<String, Score>,
+    pub output: Option<ModelOutput>,
+    pub limit: Option<SampleLimit>,
+    #[serde(default)]
+    pub error: Option<SampleError>,
+    // Model calls from the event log, only read when asked for
+    #[serde(default)]
+    pub model_calls: Vec<ModelCall>,
+    // Sandbox overriding the task's for this sample
+    #[serde(default)]
+    pub sandbox: Option<serde_json::Value>,
+    // The dataset's target, one entry per acceptable answer
+    #[serde(default)]
+    pub target: Vec<String>,
+    // Tools offered to the model, from the event log, only read when asked for
+    #[serde(default)]
+    pub tools: Vec<ToolInfo>,
+    // Stop reasons of its model calls, from the event log, only read when asked for
+    #[serde(default)]
+    pub stop_reasons: Vec<String>,
+    // Roles of its messages, including those the filter dropped
+    #[serde(default)]
+    pub roles: Vec<ChatMessageRole>,
+    // Set instead of messages for samples too large to parse
+    #[serde(default)]
+    pub streamed: Option<Streamed>,
+}
+
+impl EvalSample {
+    pub fn stop_reason(&self) -> Option<&str> {
+        self.output.as_ref()?.choices.first()?.stop_reason.as_deref()
+    }
+}
+
+// One call to the model, from a `model` event
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ModelCall {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    // Text of the message the call produced
+    pub message: Option<String>,
+    // Generation settings the call set (temperature, seed, ...), leaving out those it didn't
+    #[serde(default)]
+    pub config: serde_json::Map<String, serde_json::Value>,
+}
+
+impl ModelCall {
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+// Just enough of a transcript event to find model calls; other kinds of event
+// have an `output` of their own shape, so it's only interpreted for model events
+#[derive(Deserialize)]
+struct RawEvent {
+    event: String,
+    #[serde(default)]
+    output: Option<serde_json::Value>,
+    // Tools offered to the model, on model events
+    #[serde(default, deserialize_with = "null_as_default")]
+    tools: Vec<ToolInfo>,
+    // Generation settings, on model events
+    #[serde(default, deserialize_with = "null_as_default")]
+    config: serde_json::Map<String, serde_json::Value>,
+}
+
+/// A tool offered to the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawModelOutput {
+    #[serde(default, deserialize_with = "null_as_default")]
+    choices: Vec<RawChoice>,
+    #[serde(default)]
+    usage: Option<RawUsage>,
+}
+
+#[derive(Deserialize)]
+struct RawChoice {
+    message: RawMessage,
+    #[serde(default, alias = "finish_reason")]
+    stop_reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawMessage {
+    #[serde(default)]
+    content: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct RawUsage {
+    #[serde(default)]
+    input_tokens: u64,
+    #[serde(default)]
+    output_tokens: u64,
+}
+
+// Message content as text: a string, or the text parts of a content list
+fn content_text(content: &serde_json::Value) -> Option<String> {
+    match content {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(parts) => {
+            let texts = parts.iter().filter_map(|part| part.get("text")?.as_str()).collect::<Vec<_>>();
+            (!texts.is_empty()).then(|| texts.join("\n"))
+        }
+        _ => None,
+    }
+}
+
+impl RawEvent {
+    // Why the model stopped, on model events
+    fn stop_reason(&self) -> Option<String> {
+        if self.event != "model" {
+            return None;
+        }
+        let output: RawModelOutput = serde_json::from_value(self.output.clone()?).ok()?;
+        output.choices.into_iter().next()?.stop_reason
+    }
+
+    fn model_call(self, rewrite: &ContentRewrite) -> Option<ModelCall> {
+        if self.event != "model" {
+            return None;
+        }
+        let output: RawModelOutput = serde_json::from_value(self.output?).ok()?;
+        let usage = output.usage.unwrap_or(RawUsage { input_tokens: 0, output_tokens: 0 });
+        let message = output.choices.into_iter().next().and_then(|choice| content_text(&choice.message.content)).map(|text| {
+            match text.strip_prefix("attachment://").and_then(|hash| rewrite.attachments.get(hash)) {
+                Some(content) => content.clone(),
+                None => text,
+            }
+        });
+        let config = self.config.into_iter().filter(|(_, value)| !value.is_null()).collect();
+        Some(ModelCall { input_tokens: usage.input_tokens, output_tokens: usage.output_tokens, message, config })
+    }
+}
+
+// How far a reader has been consumed, and where the first `{` after the last
+// `arm()` was, so messages can be located within the stream as they're parsed
+#[derive(Debug, Default)]
+pub struct ReadPosition {
+    offset: Cell<u64>,
+    armed: Cell<bool>,
+    brace: Cell<u64>,
+}
+
+impl ReadPosition {
+    fn arm(&self) {
+        self.armed.set(true);
+    }
+
+    // Span from the first `{` since `arm()` to everything consumed so far
+    fn span(&self) -> (u64, u64) {
+        (self.brace.get(), self.offset.get())
+    }
+}
+
+pub struct PositionReader<R> {
+    inner: R,
+    position: Rc<ReadPosition>,
+}
+
+impl<R: Read> Read for PositionReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let position = &self.position;
+        if position.armed.get() {
+            if let Some(i) = buf[..n].iter().position(|b| *b == b'{') {
+                position.brace.set(position.offset.get() + i as u64);
+                position.armed.set(false);
+            }
+        }
+        position.offset.set(position.offset.get() + n as u64);
+        Ok(n)
+    }
+}
+
+/// Options for how samples are read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadOptions {
+    /// Show tool output wrapped in a JSON envelope as its labelled streams.
+    pub unwrap_tool_output: bool,
+    /// Read model calls from the event log.
+    pub model_calls: bool,
+    /// Read the tools offered to the model from the event log.
+    pub tools: bool,
+    /// Read why the model stopped on each call from the event log.
+    pub stop_reasons: bool,
+    /// Sizes beyond which fields are cut short as they're read.
+    pub max_field_bytes: FieldLimits,
+    /// Leave `attachment://` references in message content as they are, rather than
+    /// replacing them with the content they refer to.
+    pub keep_attachment_refs: bool,
+    /// How entries and attachments that aren't clean UTF-8 are read.
+    pub encoding: Encoding,
+}
+
+/// Per-field size limits, protecting memory from single huge values such as a
+/// multi-hundred-MB tool output.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FieldLimits {
+    /// Bytes of each message's content.
+    pub content: Option<usize>,
+    /// Bytes of each attachment.
+    pub attachment: Option<usize>,
+}
+
+impl FieldLimits {
+    /// These limits, falling back to `other`'s for fields they leave unset.
+    pub fn or(self, other: Self) -> Self {
+        Self { content: self.content.or(other.content), attachment: self.attachment.or(other.attachment) }
+    }
+}
+
+// `[content=|attachment=]SIZE`; a bare size limits both fields
+impl std::str::FromStr for FieldLimits {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> anyhow::Result<Self> {
+        let (field, size) = spec.split_once('=').map_or((None, spec), |(field, size)| (Some(field.trim()), size));
+        let size = Some(size.parse::<crate::spill::ByteSize>()?.0 as usize);
+        Ok(match field {
+            None => Self { content: size, attachment: size },
+            Some("content") => Self { content: size, attachment: None },
+            Some("attachment") => Self { content: None, attachment: size },
+            Some(field) => anyhow::bail!("unknown field '{}', expected content or attachment", field),
+        })
+    }
+}
+
+/// How message content is rewritten before it's filtered, so patterns match what
+/// will be displayed.
+pub struct ContentRewrite<'a> {
+    /// Content of attachments stored in their own entries, by hash; those in the
+    /// sample's own `attachments` map are read along with it.
+    pub attachments: &'a HashMap<String, String>,
+    pub options: ReadOptions,
+}
+
+impl ContentRewrite<'_> {
+    fn apply(&self, message: &mut ChatMessage) {
+        if !self.options.keep_attachment_refs {
+            message.content.resolve_attachments(|hash| self.attachments.get(hash));
+        }
+        if self.options.encoding == Encoding::Auto {
+            message.content.rewrite_text(|text| self.options.encoding.repair(text));
+        }
+        if self.options.unwrap_tool_output && message.role == ChatMessageRole::Tool {
+            if let Some(content) = crate::tool_output::unwrap_envelope(&message.content) {
+                message.content = content.into();
+            }
+        }
+        if let Some(max) = self.options.max_field_bytes.content {
+            message.content.truncate(max);
+        }
+    }
 }
 
-// A struct that wraps a predicate function for filtering messages
-pub struct FilteredEvalSampleDeserializer<F>
+/// Deserializes a sample, keeping only the messages a predicate accepts.
+///
+/// Rejected messages are dropped as they're parsed, so a huge transcript is never
+/// held in memory whole; their places in `messages` are left `None`.
+pub struct FilteredEvalSampleDeserializer<'a, F>
 where
     F: Fn(&ChatMessage) -> bool,
 {
     message_filter: F,
+    rewrite: &'a ContentRewrite<'a>,
+    position: &'a ReadPosition,
+    indices: Option<&'a MessageIndexFilter>,
 }
 
-impl<F> FilteredEvalSampleDeserializer<F>
+impl<'a, F> FilteredEvalSampleDeserializer<'a, F>
 where
     F: Fn(&ChatMessage) -> bool,
 {
-    pub fn new(message_filter: F) -> Self {
-        Self { message_filter }
+    pub fn new(message_filter: F, rewrite: &'a ContentRewrite<'a>, position: &'a ReadPosition) -> Self {
+        Self { message_filter, rewrite, position, indices: None }
+    }
+
+    /// Keep only the messages at these positions in the conversation. Positions counted
+    /// from the end are known once every message is read, so the filter still runs on
+    /// the messages they might select, and those they don't are dropped at the end.
+    pub fn with_indices(self, indices: Option<&'a MessageIndexFilter>) -> Self {
+        Self { indices, ..self }
     }
 }
 
-impl<'de, F> DeserializeSeed<'de> for FilteredEvalSampleDeserializer<F>
+impl<'de, F> DeserializeSeed<'de> for FilteredEvalSampleDeserializer<'_, F>
 where
     F: Fn(&ChatMessage) -> bool,
 {
@@ -118,9 +559,9 @@ where
         D: Deserializer<'de>,
     {
         // Create a visitor that will filter messages during deserialization
-        struct EvalSampleVisitor<F>(F);
+        struct EvalSampleVisitor<'a, F>(F, &'a ContentRewrite<'a>, &'a ReadPosition, Option<&'a MessageIndexFilter>);
 
-        impl<'de, F> Visitor<'de> for EvalSampleVisitor<F>
+        impl<'de, F> Visitor<'de> for EvalSampleVisitor<'_, F>
         where
             F: Fn(&ChatMessage) -> bool,
         {
@@ -136,7 +577,16 @@ where
             {
                 let mut id = None;
                 let mut epoch = None;
-                let mut messages = Vec::new();
+                let mut parsed = ParsedMessages::default();
+                let mut scores = None;
+                let mut output = None;
+                let mut limit = None;
+                let mut error = None;
+                let mut model_calls = Vec::new();
+                let mut sandbox = None;
+                let mut target = Vec::new();
+                let mut tools = Vec::new();
+                let mut stop_reasons = Vec::new();
 
                 while let Some(key) = map.next_key::<String>()? {
                     match key.as_str() {
@@ -148,32 +598,168 @@ where
                         }
                         "messages" => {
                             // Use a custom visitor for the messages sequence
-                            messages = map.next_value_seed(FilteredMessagesDeserializer(&self.0))?;
+                            parsed = map.next_value_seed(FilteredMessagesDeserializer(&self.0, self.1, self.2, self.3))?;
+                        }
+                        "attachments" if !parsed.pending.is_empty() => {
+                            let needed = parsed.pending.iter().filter_map(|index| parsed.messages[*index].as_ref());
+                            let needed = needed.flat_map(|message| message.content.attachment_refs()).collect();
+                            let inline = map.next_value_seed(NeededAttachments(needed, self.1.options.max_field_bytes.attachment))?;
+                            parsed.settle(&inline, self.1, &self.0);
+                        }
+                        "scores" => {
+                            scores = map.next_value()?;
+                        }
+                        "output" => {
+                            output = map.next_value()?;
+                        }
+                        "limit" => {
+                            limit = map.next_value()?;
+                        }
+                        "error" => {
+                            error = map.next_value()?;
+                        }
+                        "sandbox" => {
+                            sandbox = map.next_value()?;
+                        }
+                        // A string, or a list of them
+                        "target" => {
+                            target = match map.next_value()? {
+                                serde_json::Value::String(target) => vec![target],
+                                serde_json::Value::Array(targets) => targets.iter().map(crate::stats::score_label).collect(),
+                                serde_json::Value::Null => Vec::new(),
+                                target => vec![target.to_string()],
+                            };
+                        }
+                        "events" if self.1.options.model_calls || self.1.options.tools || self.1.options.stop_reasons => {
+                            let events: Vec<RawEvent> = map.next_value()?;
+                            if self.1.options.tools {
+                                let offered = events.iter().filter(|event| event.event == "model").flat_map(|event| &event.tools);
+                                for tool in offered {
+                                    if !tools.iter().any(|known: &ToolInfo| known.name == tool.name) {
+                                        tools.push(tool.clone());
+                                    }
+                                }
+                            }
+                            if self.1.options.stop_reasons {
+                                stop_reasons = events.iter().filter_map(RawEvent::stop_reason).collect();
+                            }
+                            if self.1.options.model_calls {
+                                model_calls = events.into_iter().filter_map(|event| event.model_call(self.1)).collect();
+                            }
                         }
                         _ => {
                             // Skip unknown fields
-                            let _: serde_json::Value = map.next_value()?;
+                            let _: de::IgnoredAny = map.next_value()?;
                         }
                     }
                 }
 
                 let id = id.ok_or_else(|| de::Error::missing_field("id"))?;
                 let epoch = epoch.ok_or_else(|| de::Error::missing_field("epoch"))?;
+                // References to attachments the sample doesn't have are searched as they are
+                parsed.settle(&HashMap::new(), self.1, &self.0);
+                let ParsedMessages { mut messages, spans: message_spans, roles, .. } = parsed;
+                if let Some(indices) = self.3 {
+                    let total = messages.len();
+                    for (index, message) in messages.iter_mut().enumerate() {
+                        if !indices.contains(index, total) {
+                            *message = None;
+                        }
+                    }
+                }
 
                 Ok(EvalSample {
                     id,
                     epoch,
                     messages,
+                    message_spans,
+                    entry: String::new(),
+                    scores: scores.unwrap_or_default(),
+                    output,
+                    limit,
+                    error,
+                    model_calls,
+                    sandbox,
+                    target,
+                    tools,
+                    stop_reasons,
+                    roles,
+                    streamed: None,
                 })
             }
         }
 
-        deserializer.deserialize_map(EvalSampleVisitor(self.message_filter))
+        deserializer.deserialize_map(EvalSampleVisitor(self.message_filter, self.rewrite, self.position, self.indices))
+    }
+}
+
+// A sample's messages as parsed, with each one's span and the roles seen
+#[derive(Default)]
+struct ParsedMessages {
+    messages: Vec<Option<ChatMessage>>,
+    spans: Vec<(u64, u64)>,
+    roles: Vec<ChatMessageRole>,
+    // Messages that refer to attachments not yet read, kept unfiltered until they are
+    pending: Vec<usize>,
+}
+
+impl ParsedMessages {
+    // Resolve the pending messages' references from the sample's attachments (empty if
+    // it has none), then filter them like the rest
+    fn settle(&mut self, inline: &HashMap<String, String>, rewrite: &ContentRewrite, filter: impl Fn(&ChatMessage) -> bool) {
+        for index in std::mem::take(&mut self.pending) {
+            let Some(message) = &mut self.messages[index] else { continue };
+            message.content.resolve_attachments(|hash| inline.get(hash));
+            rewrite.apply(message);
+            if !filter(message) {
+                self.messages[index] = None;
+            }
+        }
+    }
+}
+
+// The attachments in a sample's `attachments` map that some message refers to;
+// the rest, often large images, are skipped unread
+struct NeededAttachments<'a>(HashSet<&'a str>, Option<usize>);
+
+impl<'de> DeserializeSeed<'de> for NeededAttachments<'_> {
+    type Value = HashMap<String, String>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for NeededAttachments<'_> {
+    type Value = HashMap<String, String>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map of attachments")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(HashMap::new())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut found = HashMap::new();
+        while let Some(hash) = map.next_key::<String>()? {
+            if !self.0.contains(hash.as_str()) {
+                map.next_value::<de::IgnoredAny>()?;
+                continue;
+            }
+            let mut content = map.next_value::<String>()?;
+            if let Some(max) = self.1 {
+                crate::content::truncate_field(&mut content, max);
+            }
+            found.insert(hash, content);
+        }
+        Ok(found)
     }
 }
 
 // The struct that will handle filtering messages during deserialization
-struct FilteredMessagesDeserializer<'a, F>(&'a F)
+struct FilteredMessagesDeserializer<'a, F>(&'a F, &'a ContentRewrite<'a>, &'a ReadPosition, Option<&'a MessageIndexFilter>)
 where
     F: Fn(&ChatMessage) -> bool;
 
@@ -181,13 +767,13 @@ impl<'de, 'a, F> DeserializeSeed<'de> for FilteredMessagesDeserializer<'a, F>
 where
     F: Fn(&ChatMessage) -> bool,
 {
-    type Value = Vec<Option<ChatMessage>>;
+    type Value = ParsedMessages;
 
     fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: Deserializer<'de>,
     {
-        struct MessagesVisitor<'a, F>(&'a F)
+        struct MessagesVisitor<'a, F>(&'a F, &'a ContentRewrite<'a>, &'a ReadPosition, Option<&'a MessageIndexFilter>)
         where
             F: Fn(&ChatMessage) -> bool;
 
@@ -195,7 +781,7 @@ where
         where
             F: Fn(&ChatMessage) -> bool,
         {
-            type Value = Vec<Option<ChatMessage>>;
+            type Value = ParsedMessages;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
                 formatter.write_str("a sequence of messages")
@@ -205,25 +791,60 @@ where
             where
                 A: SeqAccess<'de>,
             {
-                let mut messages = Vec::new();
-                while let Some(message) = seq.next_element()? {
+                let mut parsed = ParsedMessages::default();
+                self.2.arm();
+                while let Some(mut message) = seq.next_element::<ChatMessage>()? {
+                    parsed.spans.push(self.2.span());
+                    if !parsed.roles.contains(&message.role) {
+                        parsed.roles.push(message.role.clone());
+                    }
+                    self.2.arm();
+                    // Messages at positions that can't be selected are never rewritten or filtered
+                    if self.3.is_some_and(|indices| !indices.may_contain(parsed.messages.len())) {
+                        parsed.messages.push(None);
+                        continue;
+                    }
+                    self.1.apply(&mut message);
+                    if !self.1.options.keep_attachment_refs && !message.content.attachment_refs().is_empty() {
+                        // Its content is in the sample's attachments, which come after the messages
+                        parsed.pending.push(parsed.messages.len());
+                        parsed.messages.push(Some(message));
+                        continue;
+                    }
                     // Apply the filter predicate directly to the parsed ChatMessage
-                    messages.push((self.0)(&message).then(||message));
+                    parsed.messages.push((self.0)(&message).then_some(message));
                 }
 
-                Ok(messages)
+                Ok(parsed)
             }
         }
 
-        deserializer.deserialize_seq(MessagesVisitor(self.0))
+        deserializer.deserialize_seq(MessagesVisitor(self.0, self.1, self.2, self.3))
     }
 }
-// Example usage:
+/// Deserialize a sample from its JSON, keeping only the messages `filter` accepts
+/// at the positions `indices` selects (any, with `None`).
+///
+/// ```no_run
+/// use std::collections::HashMap;
+/// use inspect_grep::inspect::{deserialize_sample_filtered, ChatMessageRole, ContentRewrite, ReadOptions};
+///
+/// let file = std::fs::File::open("1_epoch_1.json")?;
+/// let attachments = HashMap::new();
+/// let rewrite = ContentRewrite { attachments: &attachments, options: ReadOptions::default() };
+/// let sample = deserialize_sample_filtered(file, &rewrite, None, |m| m.role == ChatMessageRole::Assistant)?;
+/// println!("{} assistant messages", sample.messages.iter().flatten().count());
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
 pub fn deserialize_sample_filtered<R: std::io::Read>(
     reader: R,
+    rewrite: &ContentRewrite,
+    indices: Option<&MessageIndexFilter>,
     filter: impl Fn(&ChatMessage) -> bool,
 ) -> Result<EvalSample, serde_json::Error> {
-    let deserializer = FilteredEvalSampleDeserializer::new(filter);
+    let position = Rc::new(ReadPosition::default());
+    let reader = PositionReader { inner: reader, position: position.clone() };
+    let deserializer = FilteredEvalSampleDeserializer::new(filter, rewrite, &position).with_indices(indices);
     let mut json_deserializer = serde_json::Deserializer::from_reader(reader);
     deserializer.deserialize(&mut json_deserializer)
 }
\ No newline at end of file
@@ -0,0 +1,36 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Reset any color left half-applied by an interrupted write
+const RESET_TERMINAL: &str = "\x1b[0m";
+
+// Ask workers to stop on the first Ctrl-C; a second one exits immediately
+pub fn install_handler() -> anyhow::Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            print!("{}", RESET_TERMINAL);
+            let _ = std::io::stdout().flush();
+            std::process::exit(130);
+        }
+    })?;
+    Ok(())
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// Forget a Ctrl-C once what it stopped is over, so a session can carry on
+pub fn reset() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+// Flush pending output and restore the terminal after workers have stopped
+pub fn finish() {
+    if interrupted() {
+        print!("{}", RESET_TERMINAL);
+    }
+    let _ = std::io::stdout().flush();
+}
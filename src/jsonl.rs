@@ -0,0 +1,91 @@
+use regex::Regex;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::inspect::{ChatMessage, ChatMessageRole};
+use crate::streaming::Streamed;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Default)]
+pub enum OutputFormat {
+    // Colored terminal output
+    #[default]
+    Text,
+    // One JSON object per matched message
+    Jsonl,
+}
+
+// A matched message; byte offsets are into `content`, and `span` into the zip entry the message was read from
+#[derive(Serialize)]
+struct MessageRecord<'a> {
+    log: String,
+    sample_id: &'a str,
+    epoch: i64,
+    index: usize,
+    role: &'a ChatMessageRole,
+    content: &'a str,
+    matches: Vec<(usize, usize)>,
+    entry: &'a str,
+    span: (u64, u64),
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<&'a str>,
+    // With --show-content-hash
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_hash: Option<String>,
+    // Shown only as context around a match (-A/-B/-C)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    context: bool,
+}
+
+#[derive(Serialize)]
+struct StreamedRecord<'a> {
+    log: String,
+    sample_id: &'a str,
+    epoch: i64,
+    streamed: &'a Streamed,
+}
+
+pub struct MessageSource<'a> {
+    pub index: usize,
+    pub entry: &'a str,
+    pub span: (u64, u64),
+    pub score: Option<&'a str>,
+    pub is_context: bool,
+    pub content_hash: Option<u64>,
+}
+
+pub fn display_message_json(source: (&Path, &str, i64), message: &ChatMessage, at: MessageSource, regex: Option<&Regex>) {
+    println!("{}", message_json(source, message, at, regex));
+}
+
+// A matched message as a single line of JSON
+pub fn message_json(source: (&Path, &str, i64), message: &ChatMessage, at: MessageSource, regex: Option<&Regex>) -> String {
+    let (path, sample_id, epoch) = source;
+    // Context messages aren't matches, so their matches are left empty
+    let regex = regex.filter(|_| !at.is_context);
+    let record = MessageRecord {
+        log: path.display().to_string(),
+        sample_id,
+        epoch,
+        index: at.index,
+        role: &message.role,
+        content: &message.content,
+        matches: regex.map(|re| re.find_iter(&message.content).map(|m| (m.start(), m.end())).collect()).unwrap_or_default(),
+        entry: at.entry,
+        span: at.span,
+        score: at.score,
+        content_hash: at.content_hash.map(|hash| format!("{:016x}", hash)),
+        context: at.is_context,
+    };
+    serde_json::to_string(&record).unwrap()
+}
+
+// Streamed samples were never split into messages, so they get one record with their snippets
+pub fn display_streamed_json(source: (&Path, &str, i64), streamed: &Streamed) {
+    println!("{}", streamed_json(source, streamed));
+}
+
+pub fn streamed_json(source: (&Path, &str, i64), streamed: &Streamed) -> String {
+    let (path, sample_id, epoch) = source;
+    let record = StreamedRecord { log: path.display().to_string(), sample_id, epoch, streamed };
+    serde_json::to_string(&record).unwrap()
+}
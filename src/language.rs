@@ -0,0 +1,80 @@
+use anyhow::anyhow;
+use std::str::FromStr;
+use whatlang::Lang;
+
+// ISO 639-1 codes for the languages the detector knows, which otherwise go by ISO 639-3
+const TWO_LETTER_CODES: [(&str, Lang); 40] = [
+    ("en", Lang::Eng),
+    ("zh", Lang::Cmn),
+    ("es", Lang::Spa),
+    ("pt", Lang::Por),
+    ("it", Lang::Ita),
+    ("fr", Lang::Fra),
+    ("de", Lang::Deu),
+    ("ru", Lang::Rus),
+    ("uk", Lang::Ukr),
+    ("ar", Lang::Ara),
+    ("hi", Lang::Hin),
+    ("bn", Lang::Ben),
+    ("ja", Lang::Jpn),
+    ("ko", Lang::Kor),
+    ("he", Lang::Heb),
+    ("pl", Lang::Pol),
+    ("nl", Lang::Nld),
+    ("sv", Lang::Swe),
+    ("da", Lang::Dan),
+    ("nb", Lang::Nob),
+    ("fi", Lang::Fin),
+    ("tr", Lang::Tur),
+    ("el", Lang::Ell),
+    ("cs", Lang::Ces),
+    ("hu", Lang::Hun),
+    ("ro", Lang::Ron),
+    ("bg", Lang::Bul),
+    ("vi", Lang::Vie),
+    ("th", Lang::Tha),
+    ("id", Lang::Ind),
+    ("fa", Lang::Pes),
+    ("ur", Lang::Urd),
+    ("ta", Lang::Tam),
+    ("te", Lang::Tel),
+    ("mr", Lang::Mar),
+    ("ka", Lang::Kat),
+    ("hr", Lang::Hrv),
+    ("sr", Lang::Srp),
+    ("lt", Lang::Lit),
+    ("eo", Lang::Epo),
+];
+
+// A language to select messages by, given as an ISO 639-1 or 639-3 code or an English name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Language(Lang);
+
+impl FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let code = s.trim().to_lowercase();
+        TWO_LETTER_CODES
+            .iter()
+            .find(|(two_letter, _)| *two_letter == code)
+            .map(|(_, lang)| *lang)
+            .or_else(|| Lang::from_code(code.as_str()))
+            .or_else(|| Lang::all().iter().copied().find(|lang| lang.eng_name().eq_ignore_ascii_case(&code)))
+            .map(Language)
+            .ok_or_else(|| anyhow!("unknown language '{}', expected a code like en, zh or deu", s))
+    }
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let code = TWO_LETTER_CODES.iter().find(|(_, lang)| *lang == self.0).map_or(self.0.code(), |(code, _)| code);
+        write!(f, "{}", code)
+    }
+}
+
+// Whether the content's detected language is one of these; text too short or
+// ambiguous to detect matches none
+pub fn is_in(content: &str, languages: &[Language]) -> bool {
+    whatlang::detect(content).filter(whatlang::Info::is_reliable).is_some_and(|info| languages.contains(&Language(info.lang())))
+}
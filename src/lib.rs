@@ -0,0 +1,667 @@
+//! Search and analyze the transcripts in Inspect eval logs.
+//!
+//! The `inspect-grep` binary is a thin command line over this library. Other tools
+//! can call [`search`] for matching messages, or read samples themselves with
+//! [`read_sample_filtered`] and the streaming deserializer in [`inspect`].
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use colored::*;
+use globset::GlobMatcher;
+use itertools::Itertools;
+use rayon::prelude::*;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+use walkdir::WalkDir;
+use zip::ZipArchive;
+
+pub mod aliases;
+pub mod answer_variance;
+pub mod answers;
+pub mod anywhere;
+pub mod atomic;
+pub mod assertion;
+pub mod assembly;
+pub mod bundle;
+pub mod compare;
+pub mod config_filter;
+pub mod content;
+pub mod context;
+pub mod dedup;
+pub mod counters;
+pub mod encoding;
+pub mod environment;
+pub mod epoch_scores;
+pub mod events;
+pub mod export;
+pub mod export_eval;
+pub mod extract;
+pub mod filter_set;
+pub mod fingerprint;
+pub mod fixtures;
+pub mod gen_config;
+pub mod glyphs;
+pub mod grep_compat;
+pub mod heatmap;
+pub mod history;
+pub mod hot_files;
+pub mod images;
+pub mod inspect;
+pub mod jsonl;
+pub mod interrupt;
+pub mod language;
+pub mod line_mode;
+pub mod listing;
+pub mod lint;
+pub mod log_format;
+pub mod log_index;
+pub mod message_index;
+pub mod message_pattern;
+pub mod numbers;
+pub mod notify;
+pub mod preset;
+pub mod provenance;
+pub mod queries;
+pub mod quota;
+pub mod raw;
+pub mod reference;
+pub mod relevance;
+pub mod remote;
+pub mod repl;
+pub mod repro;
+pub mod rescore;
+pub mod resources;
+pub mod rollup;
+pub mod sample_error;
+pub mod score_filter;
+pub mod scores;
+pub mod search;
+pub mod skipped;
+pub mod slim;
+pub mod spill;
+pub mod stats;
+pub mod stop_reason;
+pub mod stream_to;
+pub mod streaming;
+pub mod summary;
+pub mod svg;
+pub mod template;
+pub mod theme;
+pub mod tool_calls;
+pub mod tool_output;
+pub mod tui;
+pub mod turns;
+pub mod truncation;
+pub mod wizard;
+
+use assembly::{sample_entries, unrecognized_entries, SampleEntries, SampleLayout};
+use config_filter::{ConfigFilter, HeaderFilter};
+use counters::ScanCounters;
+use filter_set::FilterSet;
+use message_index::MessageIndexFilter;
+use images::ImageProtocol;
+use inspect::{deserialize_sample_filtered, ChatMessage, ContentRewrite, EvalLogHeader, EvalSample, ReadOptions};
+use interrupt::interrupted;
+use log_format::{open_log, LogReader};
+use quota::FileQuota;
+use spill::{ByteSize, SpillBuffer};
+use theme::Theme;
+use tool_calls::ToolCallFilter;
+
+pub use search::{search, MatchRecord, SearchOptions};
+
+/// Order in which logs are handed to worker threads.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ScanOrder {
+    /// Most recently modified first
+    Newest,
+    /// Least recently modified first
+    Oldest,
+    /// Smallest files first
+    Smallest,
+    /// Largest files first
+    Largest,
+}
+
+/// Admits the results that fall within an offset/limit window, counting across threads.
+/// Every result a search displays passes through here, so aggregate-only mode is enforced
+/// here too: results are still counted, but none is ever admitted. The window is over
+/// entries, which are single matches except in modes printing one entry per sample;
+/// matches are counted either way
+pub struct Page {
+    offset: usize,
+    limit: Option<usize>,
+    aggregate_only: bool,
+    pub seen: AtomicUsize,
+    pub shown: AtomicUsize,
+    pub matches: AtomicUsize,
+}
+
+impl Page {
+    pub fn new(offset: usize, limit: Option<usize>, aggregate_only: bool) -> Self {
+        Self { offset, limit, aggregate_only, seen: AtomicUsize::new(0), shown: AtomicUsize::new(0), matches: AtomicUsize::new(0) }
+    }
+
+    // A single match as its own entry
+    pub fn admit(&self) -> bool {
+        self.admit_entry(1)
+    }
+
+    // An entry standing for `matches` matches, such as a sample's summary line
+    pub fn admit_entry(&self, matches: usize) -> bool {
+        self.count(matches);
+        let index = self.seen.fetch_add(1, Ordering::Relaxed);
+        let admitted = !self.aggregate_only && index >= self.offset && self.limit.is_none_or(|limit| index < self.offset + limit);
+        if admitted {
+            self.shown.fetch_add(1, Ordering::Relaxed);
+        }
+        admitted
+    }
+
+    // Matches that get no entry of their own
+    pub fn count(&self, matches: usize) {
+        self.matches.fetch_add(matches, Ordering::Relaxed);
+    }
+}
+
+/// Whether an item is selected.
+pub trait Filter<T> {
+    fn filter(&self, item: &T) -> bool;
+}
+
+/// A set of sample epochs (or other small integers): `all`, a list like `1,3` or a range like `2-5`.
+#[derive(Debug, Clone, Default)]
+pub enum IntFilter {
+    #[default]
+    All,
+    Some(HashSet<u32>),
+    Range(u32, u32),
+}
+
+impl FromStr for IntFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "all" {
+            return Ok(IntFilter::All);
+        }
+        if let Some((start, end)) = s.split_once('-') {
+            return Ok(IntFilter::Range(
+                start.parse()?,
+                end.parse()?,
+            ));
+        }
+        let nums = s.split(',')
+            .map(|n| n.trim().parse::<u32>())
+            .collect::<std::result::Result<HashSet<_>, _>>()?;
+        Ok(IntFilter::Some(nums))
+    }
+}
+
+impl fmt::Display for IntFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntFilter::All => write!(f, "all"),
+            IntFilter::Some(nums) => write!(f, "{}", nums.iter().sorted().join(",")),
+            IntFilter::Range(start, end) => write!(f, "{}-{}", start, end),
+        }
+    }
+}
+
+impl Filter<u32> for IntFilter {
+    fn filter(&self, item: &u32) -> bool {
+        match self {
+            IntFilter::All => true,
+            IntFilter::Some(ids) => ids.contains(item),
+            IntFilter::Range(start, end) => item >= start && item <= end,
+        }
+    }
+}
+
+/// The samples in a log whose id and epoch pass the filters, counting what was found and selected.
+pub fn matching_samples_in_log<'a>(
+    log_path: &Path,
+    sample_regex: &'a Option<Regex>,
+    epoch_filter: &'a IntFilter,
+    layout: Option<&SampleLayout>,
+    counters: &ScanCounters,
+) -> Result<Vec<SampleEntries>> {
+    let file_names = log_index::file_names(log_path)?;
+    let file_names = file_names.iter().map(String::as_str).collect::<Vec<_>>();
+    let samples = sample_entries(&file_names, layout);
+    ScanCounters::add(&counters.samples_found, samples.len());
+    if samples.is_empty() {
+        ScanCounters::add(&counters.entries_unrecognized, unrecognized_entries(&file_names));
+    }
+    let selected = samples
+        .into_iter()
+        .filter(|sample| {
+            sample_regex.as_ref().is_none_or(|re| re.is_match(&sample.sample_id)) && epoch_filter.filter(&sample.epoch)
+        })
+        .collect::<Vec<_>>();
+    ScanCounters::add(&counters.samples_selected, selected.len());
+    Ok(selected)
+}
+
+/// Read and parse a JSON entry from a log, or None if the log doesn't contain it
+pub fn read_json_entry<T: DeserializeOwned>(log_path: &Path, name: &str) -> Result<Option<T>> {
+    let mut archive = open_log(log_path)?;
+
+    let file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some(serde_json::from_reader(std::io::BufReader::new(resources::decompressing(file)))?))
+}
+
+/// Read one sample from a log, keeping only the messages `message_filter` accepts;
+/// the others are skipped as they're parsed and left as `None`.
+pub fn read_sample_filtered<F>(log_path: &Path, sample: &SampleEntries, options: ReadOptions, message_filter: F) -> Result<EvalSample>
+where
+    F: Fn(&ChatMessage) -> bool,
+{
+    read_sample_from(&mut open_log(log_path)?, sample, options, None, message_filter)
+}
+
+/// As [`read_sample_filtered`], from a log already opened with [`open_log`], so reading
+/// many samples doesn't parse its central directory again for each, and only at the
+/// positions `indices` selects.
+pub fn read_sample_from<F>(
+    archive: &mut ZipArchive<LogReader>,
+    sample: &SampleEntries,
+    options: ReadOptions,
+    indices: Option<&MessageIndexFilter>,
+    message_filter: F,
+) -> Result<EvalSample>
+where
+    F: Fn(&ChatMessage) -> bool,
+{
+    let attachments = match options.keep_attachment_refs {
+        true => HashMap::new(),
+        false => sample.read_attachments(archive, options.max_field_bytes.attachment, options.encoding)?,
+    };
+    let file = options.encoding.reader(sample.reader(archive)?);
+    let rewrite = ContentRewrite { attachments: &attachments, options };
+    let mut eval_sample = deserialize_sample_filtered(file, &rewrite, indices, message_filter)?;
+    resources::add_sample_parsed();
+    eval_sample.entry = sample.name().to_string();
+    Ok(eval_sample)
+}
+
+/// Set aside samples too large to parse, per --max-sample-size
+pub fn split_oversized(
+    log_path: &Path,
+    samples: Vec<SampleEntries>,
+    max_size: Option<ByteSize>,
+    counters: &ScanCounters,
+) -> Result<(Vec<SampleEntries>, Vec<SampleEntries>)> {
+    let Some(ByteSize(max_size)) = max_size else {
+        return Ok((samples, Vec::new()));
+    };
+    let mut archive = open_log(log_path)?;
+    let mut sizes = Vec::with_capacity(samples.len());
+    for sample in &samples {
+        sizes.push(streaming::uncompressed_size(&mut archive, sample)?);
+    }
+    let (oversized, fits): (Vec<_>, Vec<_>) = samples.into_iter().zip(sizes).partition(|(_, size)| *size > max_size);
+    ScanCounters::add(&counters.samples_oversized, oversized.len());
+    Ok((fits.into_iter().map(|(s, _)| s).collect(), oversized.into_iter().map(|(s, _)| s).collect()))
+}
+
+/// A sample searched as raw JSON in place of parsing it, or None when it can't be
+/// read and is skipped
+pub fn streamed_sample(log_path: &Path, sample: &SampleEntries, regex: &regex::bytes::Regex) -> Option<EvalSample> {
+    let streamed = skipped::or_skip(streaming::stream_sample(log_path, sample, regex), format_args!("{}: {}", log_path.display(), sample.name()))?;
+    Some(EvalSample {
+        id: sample.sample_id.clone(),
+        epoch: sample.epoch as i64,
+        messages: Vec::new(),
+        message_spans: Vec::new(),
+        entry: sample.name().to_string(),
+        scores: Default::default(),
+        output: None,
+        limit: None,
+        error: None,
+        model_calls: Vec::new(),
+        sandbox: None,
+        target: Vec::new(),
+        tools: Vec::new(),
+        stop_reasons: Vec::new(),
+        roles: Vec::new(),
+        streamed: Some(streamed),
+    })
+}
+
+/// A message shown only for context around a match, dimmed, marked and without highlighting
+pub fn display_context_message(source: (&Path, &str, i64), message: &ChatMessage, span: Option<(&str, (u64, u64))>, images: Option<ImageProtocol>, theme: &Theme) {
+    let (log_file, sample_id, epoch) = source;
+    let role = format!("[{}] (context)", message.role).color(theme.role_color(&message.role));
+    let header = theme.header(&log_file.file_name().unwrap().to_string_lossy(), sample_id, epoch, role);
+    match span {
+        Some((entry, (start, end))) => println!("\n{} {}", header.dimmed(), format!("@ {entry}:{start}-{end}").dimmed()),
+        None => println!("\n{}", header.dimmed()),
+    }
+    println!("{}", message.content.dimmed());
+    if let Some(protocol) = images {
+        images::display_images(&message.content, protocol);
+    }
+    println!();
+}
+
+/// Read the given samples of a log in parallel, in their order, keeping only the
+/// messages `filters` accept. Stops early once interrupted.
+pub fn process_eval_file(log_path: &Path, sample_paths: &[SampleEntries], options: ReadOptions, filters: &FilterSet, counters: &ScanCounters) -> Vec<EvalSample> {
+    read_samples(log_path, sample_paths, options, filters.indices.as_ref(), &|message: &ChatMessage| filters.accepts(message, counters))
+}
+
+/// As [`process_eval_file`], keeping only as many matching messages as `quota` has room
+/// for and reading no more of the log's samples once it's met. Samples are filtered by
+/// score as they're read (see [`FilterSet::retain`]), so only those kept claim matches;
+/// which ones fill the quota depends on which workers get to them first.
+pub fn process_eval_file_within(
+    log_path: &Path,
+    sample_paths: &[SampleEntries],
+    options: ReadOptions,
+    filters: &FilterSet,
+    counters: &ScanCounters,
+    quota: &FileQuota,
+) -> Vec<EvalSample> {
+    if sample_paths.is_empty() {
+        return Vec::new();
+    }
+    let Some(archive) = skipped::or_skip(open_log(log_path), log_path.display()) else {
+        return Vec::new();
+    };
+    let message_filter = |message: &ChatMessage| filters.accepts(message, counters);
+    sample_paths.par_iter()
+        .filter(|_| !interrupted() && !quota.is_met())
+        .map_init(|| archive.clone(), |archive, sample| {
+            let read = read_sample_from(archive, sample, options, filters.indices.as_ref(), message_filter);
+            let mut kept = skipped::or_skip(read, format_args!("{}: {}", log_path.display(), sample.name())).into_iter().collect();
+            filters.retain(&mut kept, counters);
+            let mut sample = kept.pop()?;
+            // Matches beyond what the quota grants are dropped as if they hadn't matched
+            let mut granted = quota.claim(sample.messages.iter().flatten().count());
+            for message in sample.messages.iter_mut().filter(|message| message.is_some()) {
+                match granted {
+                    0 => *message = None,
+                    _ => granted -= 1,
+                }
+            }
+            Some(sample)
+        })
+        .flatten()
+        .collect()
+}
+
+/// As [`process_eval_file`], keeping every message, for modes that need whole conversations.
+pub fn read_whole_samples(log_path: &Path, sample_paths: &[SampleEntries], options: ReadOptions) -> Vec<EvalSample> {
+    read_samples(log_path, sample_paths, options, None, &|_: &ChatMessage| true)
+}
+
+/// As [`process_eval_file`], keeping none of the messages, for counting them and reading
+/// the rest of each sample.
+pub fn read_sample_outlines(log_path: &Path, sample_paths: &[SampleEntries], options: ReadOptions) -> Vec<EvalSample> {
+    read_samples(log_path, sample_paths, options, None, &|_: &ChatMessage| false)
+}
+
+fn read_samples<F>(log_path: &Path, sample_paths: &[SampleEntries], options: ReadOptions, indices: Option<&MessageIndexFilter>, message_filter: &F) -> Vec<EvalSample>
+where
+    F: Fn(&ChatMessage) -> bool + Sync,
+{
+    if sample_paths.is_empty() {
+        return Vec::new();
+    }
+    // Opened once; each worker reads from its own clone of the parsed archive
+    let Some(archive) = skipped::or_skip(open_log(log_path), log_path.display()) else {
+        return Vec::new();
+    };
+    sample_paths.par_iter()
+        .filter(|_| !interrupted())
+        .map_init(|| archive.clone(), |archive, sample| {
+            let read = read_sample_from(archive, sample, options, indices, message_filter);
+            skipped::or_skip(read, format_args!("{}: {}", log_path.display(), sample.name()))
+        })
+        .flatten()
+        .collect::<Vec<EvalSample>>()
+}
+
+/// Display per-file results from a single writer thread, so files' output never
+/// interleaves. Results carry their file's position in the scan: when `ordered`, each
+/// file is displayed once every earlier one has been, its items sorted by `sort_key`;
+/// otherwise files are displayed as workers finish them. With `max_memory`, ordered
+/// results are instead collected (spilling beyond that to disk) and displayed at the end.
+/// The first error `display` returns stops the output and is returned.
+pub fn emit_results<'a, T, K, D>(
+    results: impl ParallelIterator<Item = (usize, &'a PathBuf, Vec<T>)>,
+    ordered: bool,
+    max_memory: Option<ByteSize>,
+    sort_key: K,
+    display: D,
+) -> Result<()>
+where
+    T: Send + Serialize + DeserializeOwned,
+    K: Fn(&T, &T) -> std::cmp::Ordering + Sync,
+    D: Fn(&Path, Vec<T>) -> Result<()> + Sync + Send,
+{
+    if let (true, Some(max_memory)) = (ordered, max_memory) {
+        let buffer = SpillBuffer::new(max_memory);
+        results.try_for_each(|(index, path, mut items)| {
+            items.sort_by(&sort_key);
+            buffer.push(index, path, &items)
+        })?;
+        for run in buffer.into_sorted() {
+            let (path, items) = run?;
+            display(&path, items)?;
+        }
+        return Ok(());
+    }
+    std::thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        let writer = scope.spawn(|| -> Result<()> {
+            // Files that finished ahead of an earlier one, by scan position
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+            for (index, path, items) in receiver {
+                if !ordered {
+                    display(path, items)?;
+                    continue;
+                }
+                pending.insert(index, (path, items));
+                while let Some((path, items)) = pending.remove(&next) {
+                    display(path, items)?;
+                    next += 1;
+                }
+            }
+            // Files never scanned (after an interrupt or the time budget) leave gaps
+            for (path, items) in pending.into_values() {
+                display(path, items)?;
+            }
+            Ok(())
+        });
+        let sent = results.try_for_each_with(sender, |sender, (index, path, mut items)| {
+            if ordered {
+                items.sort_by(&sort_key);
+            }
+            sender.send((index, path, items)).map_err(|_| anyhow!("Output writer stopped before {} was printed", path.display()))
+        });
+        // A writer that failed stops taking results, so its error comes before the workers'
+        writer.join().unwrap()?;
+        sent
+    })
+}
+
+/// Print a matched message under its header, with pattern matches highlighted.
+#[allow(clippy::too_many_arguments)]
+pub fn display_message(
+    source: (&Path, &str, i64),
+    message: &ChatMessage,
+    span: Option<(&str, (u64, u64))>,
+    score: Option<&str>,
+    highlight_regex: Option<&Regex>,
+    tool_calls: Option<&ToolCallFilter>,
+    images: Option<ImageProtocol>,
+    theme: &Theme,
+) {
+    // let terminal_width = term_size::dimensions().map(|(w, _)| w).unwrap_or(80);
+    display_message_header(source, message, span, score, theme);
+
+    // Process content with highlighting
+    let mut content = message.content.to_string();
+    if let Some(regex) = highlight_regex {
+        content = regex.replace_all(&content, |caps: &regex::Captures| {
+            format!("{}", theme.highlight(&caps[0]))
+        }).to_string();
+    }
+
+    println!("{}", content);
+    if let Some(tool_calls) = tool_calls {
+        tool_calls::display_tool_calls(message, tool_calls, theme);
+    }
+    if let Some(protocol) = images {
+        images::display_images(&message.content, protocol);
+    }
+
+    println!(); // Add spacing between messages
+}
+
+/// Blank line, then the source, role, label and span of a matched message
+pub fn display_message_header(source: (&Path, &str, i64), message: &ChatMessage, span: Option<(&str, (u64, u64))>, score: Option<&str>, theme: &Theme) {
+    let (log_file, sample_id, epoch) = source;
+
+    // Format role
+    let role = format!("[{}]", message.role.to_string().to_lowercase())
+        .color(theme.role_color(&message.role))
+        .bold();
+    
+    // Create header with source info and role
+    let mut header = theme.header(&log_file.file_name().unwrap().to_string_lossy(), sample_id, epoch, role);
+    if let Some(score) = score {
+        header = format!("{} {}", header, score.bold());
+    }
+
+    match span {
+        Some((entry, (start, end))) => println!("\n{} {}", header, format!("@ {entry}:{start}-{end}").dimmed()),
+        None => println!("\n{}", header),
+    }
+}
+
+/// The .eval files to search, dropping those whose header doesn't satisfy every --config-filter
+/// and --task/--model/--run-id
+pub fn search_files(path: &Path, discovery: &Discovery, config_filters: &[ConfigFilter], header_filter: &HeaderFilter, counters: &ScanCounters) -> Vec<PathBuf> {
+    let paths = eval_files(path, discovery);
+    if config_filters.is_empty() && header_filter.is_empty() {
+        return paths;
+    }
+    let kept = paths
+        .par_iter()
+        .map(|path| {
+            let header = log_index::header(path).ok().flatten();
+            header.is_some_and(|header| {
+                let spec = EvalLogHeader::deserialize(&header).map(|header| header.eval);
+                config_filters.iter().all(|filter| filter.matches(&header))
+                    && (header_filter.is_empty() || spec.is_ok_and(|spec| header_filter.matches(&spec)))
+            })
+        })
+        .collect::<Vec<_>>();
+    ScanCounters::add(&counters.files_excluded, kept.iter().filter(|kept| !**kept).count());
+    paths.into_iter().zip(kept).filter_map(|(path, kept)| kept.then_some(path)).collect()
+}
+
+/// How logs are found under a directory, and the order they're scanned in.
+#[derive(Debug, Clone, Default)]
+pub struct Discovery {
+    pub scan_order: Option<ScanOrder>,
+    /// Also take plain-JSON logs (`.json`).
+    pub include_json: bool,
+    /// Take only logs matching one of these (any log when empty). A pattern with a `/` is
+    /// matched against the path under the directory, and one without against the file name.
+    pub globs: Vec<GlobMatcher>,
+    /// Skip logs, and whole directories, matching any of these, matched the same way.
+    pub excludes: Vec<GlobMatcher>,
+    /// Go at most this many directories deep (1 is the directory's own files).
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+    /// Search exactly these logs, as --files-from lists them, instead of walking the path.
+    pub files: Option<Vec<PathBuf>>,
+}
+
+impl Discovery {
+    fn matches(patterns: &[GlobMatcher], relative: &Path) -> bool {
+        patterns.iter().any(|pattern| pattern.is_match(relative) || relative.file_name().is_some_and(|name| pattern.is_match(name)))
+    }
+}
+
+/// Log paths listed in a file, or on stdin for `-`, one per line or separated by NULs (as
+/// `find -print0` writes them). Blank lines are ignored.
+pub fn read_file_list(source: &Path) -> Result<Vec<PathBuf>> {
+    let list = match source == Path::new("-") {
+        true => {
+            let mut list = Vec::new();
+            std::io::Read::read_to_end(&mut std::io::stdin(), &mut list).context("Failed to read the list of logs from stdin")?;
+            list
+        }
+        false => std::fs::read(source).with_context(|| format!("Failed to read the list of logs in {}", source.display()))?,
+    };
+    let separator = if list.contains(&0) { 0 } else { b'\n' };
+    let paths = list.split(|byte| *byte == separator).map(|line| line.strip_suffix(b"\r").unwrap_or(line)).filter(|line| !line.is_empty());
+    Ok(paths.map(|line| PathBuf::from(String::from_utf8_lossy(line).into_owned())).collect())
+}
+
+/// Logs under a directory (.eval, and with `include_json` .json logs), or the path itself
+/// when it's a file, whichever format it's in
+pub fn eval_files(path: &Path, discovery: &Discovery) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = if let Some(files) = &discovery.files {
+        // Listed logs are taken as they are, short of those the patterns rule out
+        let selected = |file: &&PathBuf| (discovery.globs.is_empty() || Discovery::matches(&discovery.globs, file)) && !Discovery::matches(&discovery.excludes, file);
+        files.iter().filter(selected).cloned().collect()
+    } else if remote::is_remote(path) {
+        skipped::or_skip(remote::list(path, discovery.include_json), path.display()).unwrap_or_default()
+    } else if path.is_file() {
+        vec![path.to_path_buf()]
+    } else {
+        let relative = |entry: &walkdir::DirEntry| entry.path().strip_prefix(path).unwrap_or(entry.path()).to_path_buf();
+        let mut walk = WalkDir::new(path).follow_links(discovery.follow_symlinks);
+        if let Some(max_depth) = discovery.max_depth {
+            walk = walk.max_depth(max_depth);
+        }
+        // Sorted, so the default scan (and output) order doesn't depend on the filesystem
+        walk.sort_by_file_name()
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !Discovery::matches(&discovery.excludes, &relative(e)))
+            .filter_map(|e| e.ok())
+            .filter(|e| match e.path().extension() {
+                Some(ext) if ext == "eval" => true,
+                Some(ext) if ext == "json" => discovery.include_json && log_format::is_json_log(e.path()),
+                _ => false,
+            })
+            .filter(|e| discovery.globs.is_empty() || Discovery::matches(&discovery.globs, &relative(e)))
+            .map(|e| e.path().to_path_buf())
+            .collect()
+    };
+
+    if let Some(scan_order) = discovery.scan_order {
+        // Unreadable metadata sorts as oldest/empty
+        let modified = |p: &PathBuf| p.metadata().and_then(|m| m.modified()).unwrap_or(std::time::UNIX_EPOCH);
+        let size = |p: &PathBuf| p.metadata().map_or(0, |m| m.len());
+        match scan_order {
+            ScanOrder::Newest => paths.sort_by_cached_key(|p| std::cmp::Reverse(modified(p))),
+            ScanOrder::Oldest => paths.sort_by_cached_key(modified),
+            ScanOrder::Smallest => paths.sort_by_cached_key(size),
+            ScanOrder::Largest => paths.sort_by_cached_key(|p| std::cmp::Reverse(size(p))),
+        }
+    }
+
+    paths
+}
+
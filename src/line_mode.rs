@@ -0,0 +1,58 @@
+use colored::*;
+use regex::Regex;
+use std::path::Path;
+
+use crate::context::ContextWindow;
+use crate::inspect::ChatMessage;
+use crate::theme::Theme;
+
+// Indices of the lines a match touches, so matches spanning lines show each of
+// them; every line without a pattern
+fn matched_lines(content: &str, regex: Option<&Regex>) -> Vec<usize> {
+    let starts = std::iter::once(0).chain(content.match_indices('\n').map(|(i, _)| i + 1)).collect::<Vec<_>>();
+    let Some(regex) = regex else {
+        return (0..starts.len()).collect();
+    };
+    let line_of = |offset: usize| starts.partition_point(|&start| start <= offset) - 1;
+    let mut lines = Vec::new();
+    for found in regex.find_iter(content) {
+        lines.extend(line_of(found.start())..=line_of(found.end().saturating_sub(1).max(found.start())));
+    }
+    lines.dedup();
+    lines
+}
+
+// A matched message's header followed by only its matching lines, numbered like
+// `grep -n` (`12:` for matches, `12-` for context), with `--` between runs
+pub fn display_message_lines(
+    source: (&Path, &str, i64),
+    message: &ChatMessage,
+    span: Option<(&str, (u64, u64))>,
+    label: Option<&str>,
+    regex: Option<&Regex>,
+    context: ContextWindow,
+    theme: &Theme,
+) {
+    crate::display_message_header(source, message, span, label, theme);
+    let lines = message.content.split('\n').map(|line| line.trim_end_matches('\r')).collect::<Vec<_>>();
+    let matched = matched_lines(&message.content, regex);
+    for (i, group) in context.groups(&matched, lines.len()).into_iter().enumerate() {
+        if i > 0 {
+            println!("{}", "--".dimmed());
+        }
+        for index in group {
+            let line = lines[index];
+            match matched.binary_search(&index) {
+                Ok(_) => {
+                    let line = match regex {
+                        Some(regex) => regex.replace_all(line, |caps: &regex::Captures| theme.highlight(&caps[0]).to_string()),
+                        None => line.into(),
+                    };
+                    println!("{}{}", format!("{}:", index + 1).dimmed(), line);
+                }
+                Err(_) => println!("{}", format!("{}-{}", index + 1, line).dimmed()),
+            }
+        }
+    }
+    println!();
+}
@@ -0,0 +1,134 @@
+use colored::*;
+use itertools::Itertools;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::inspect::{ChatMessage, ChatMessageRole, EvalSample};
+
+// Examples listed for each kind of violation in a log
+const EXAMPLES: usize = 3;
+
+// Breaks of the chat protocol, which usually mean a scaffold bug rather than model behavior
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Violation {
+    // A tool result no earlier assistant message asked for
+    OrphanToolMessage,
+    // A run of system messages after the conversation has started
+    ConsecutiveSystemMessages,
+    // An assistant turn that says nothing and calls nothing
+    EmptyAssistantMessage,
+}
+
+impl Violation {
+    fn describe(self) -> &'static str {
+        match self {
+            Violation::OrphanToolMessage => "tool message without a preceding tool call",
+            Violation::ConsecutiveSystemMessages => "consecutive system messages mid-conversation",
+            Violation::EmptyAssistantMessage => "assistant message with no content and no tool calls",
+        }
+    }
+}
+
+// Each violation in a conversation, with the index of the message it was found at
+pub fn violations(messages: &[Option<ChatMessage>]) -> Vec<(usize, Violation)> {
+    let mut found = Vec::new();
+    let mut call_ids = HashSet::new();
+    let mut calls = 0;
+    let mut started = false;
+    let mut previous: Option<&ChatMessage> = None;
+    for (index, message) in messages.iter().enumerate() {
+        let Some(message) = message else { continue };
+        match message.role {
+            ChatMessageRole::Assistant => {
+                calls += message.tool_calls.len();
+                call_ids.extend(message.tool_calls.iter().filter_map(|call| call.id.as_deref()));
+                if message.content.trim().is_empty() && message.tool_calls.is_empty() {
+                    found.push((index, Violation::EmptyAssistantMessage));
+                }
+            }
+            // Without an id, any earlier call will do
+            ChatMessageRole::Tool => {
+                let answered = match &message.tool_call_id {
+                    Some(id) => call_ids.contains(id.as_str()),
+                    None => calls > 0,
+                };
+                if !answered {
+                    found.push((index, Violation::OrphanToolMessage));
+                }
+            }
+            ChatMessageRole::System if started && previous.is_some_and(|previous| previous.role == ChatMessageRole::System) => {
+                found.push((index, Violation::ConsecutiveSystemMessages));
+            }
+            _ => {}
+        }
+        started |= message.role != ChatMessageRole::System;
+        previous = Some(message);
+    }
+    found
+}
+
+#[derive(Default)]
+struct LogViolations {
+    samples: usize,
+    // Violation -> (sample id, epoch, message index) of each occurrence
+    found: BTreeMap<Violation, Vec<(String, i64, usize)>>,
+}
+
+// Violations by log, for the samples checked
+#[derive(Default)]
+pub struct TranscriptLint {
+    logs: BTreeMap<PathBuf, LogViolations>,
+}
+
+impl TranscriptLint {
+    pub fn add_sample(&mut self, log_path: &Path, sample: &EvalSample) {
+        let log = self.logs.entry(log_path.to_path_buf()).or_default();
+        log.samples += 1;
+        for (index, violation) in violations(&sample.messages) {
+            log.found.entry(violation).or_default().push((sample.id.clone(), sample.epoch, index));
+        }
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        for (path, other) in other.logs {
+            let log = self.logs.entry(path).or_default();
+            log.samples += other.samples;
+            for (violation, found) in other.found {
+                log.found.entry(violation).or_default().extend(found);
+            }
+        }
+        self
+    }
+
+    pub fn samples(&self) -> usize {
+        self.logs.values().map(|log| log.samples).sum()
+    }
+
+    // Logs with violations, each with per-kind counts and a few examples
+    // (`sample:epoch #message`), then the totals
+    pub fn display(mut self) {
+        let mut total = 0;
+        let mut samples_affected = 0;
+        for (path, log) in &mut self.logs {
+            if log.found.is_empty() {
+                continue;
+            }
+            let affected = log.found.values().flatten().map(|(id, epoch, _)| (id, epoch)).unique().count();
+            samples_affected += affected;
+            println!("\n{}  {}", path.display().to_string().cyan(), format!("{} of {} samples", affected, log.samples).dimmed());
+            for (violation, found) in &mut log.found {
+                found.sort();
+                total += found.len();
+                let samples = found.iter().map(|(id, epoch, _)| (id, epoch)).unique().count();
+                println!("  {}: {} (in {} samples)", violation.describe().red(), found.len(), samples);
+                let examples = found.iter().take(EXAMPLES).map(|(id, epoch, index)| format!("{}:{} #{}", id, epoch, index)).join(", ");
+                let more = found.len().saturating_sub(EXAMPLES);
+                let more = if more > 0 { format!(" (+{} more)", more) } else { String::new() };
+                println!("    {}{}", examples.dimmed(), more.dimmed());
+            }
+        }
+        let samples = self.samples();
+        let logs = self.logs.values().filter(|log| !log.found.is_empty()).count();
+        eprintln!("{} violations in {} of {} samples, in {} of {} logs", total, samples_affected, samples, logs, self.logs.len());
+    }
+}
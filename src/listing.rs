@@ -0,0 +1,123 @@
+use clap::ValueEnum;
+use colored::*;
+use itertools::Itertools;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::assembly::SampleEntries;
+use crate::inspect::{EvalLogHeader, ReadOptions};
+
+/// What --list enumerates.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ListKind {
+    /// Each sample of each log, with its epochs and their message counts.
+    Samples,
+    /// Each epoch of each sample, with its message count.
+    Epochs,
+    /// Each log, with its task, model and number of samples.
+    Files,
+    /// Each task and model, with how many logs and samples ran it.
+    Tasks,
+}
+
+impl ListKind {
+    // Counting messages means parsing the samples; the other listings only need the
+    // header and the archive's entry names
+    fn counts_messages(self) -> bool {
+        matches!(self, ListKind::Samples | ListKind::Epochs)
+    }
+}
+
+// What's inside one log
+pub struct LogListing {
+    path: PathBuf,
+    task: Option<String>,
+    model: Option<String>,
+    // Sample id -> each epoch, with its message count when counted
+    samples: BTreeMap<String, Vec<(u32, Option<usize>)>>,
+}
+
+impl LogListing {
+    pub fn read(log_path: &Path, sample_paths: &[SampleEntries], kind: ListKind, options: ReadOptions) -> Self {
+        let header = crate::log_index::header(log_path).ok().flatten();
+        let spec = header.and_then(|header| EvalLogHeader::deserialize(&header).ok()).map(|header| header.eval);
+        let mut samples: BTreeMap<String, Vec<(u32, Option<usize>)>> = BTreeMap::new();
+        match kind.counts_messages() {
+            true => {
+                for sample in crate::read_sample_outlines(log_path, sample_paths, options) {
+                    samples.entry(sample.id).or_default().push((sample.epoch as u32, Some(sample.messages.len())));
+                }
+            }
+            false => {
+                for sample in sample_paths {
+                    samples.entry(sample.sample_id.clone()).or_default().push((sample.epoch, None));
+                }
+            }
+        }
+        samples.values_mut().for_each(|epochs| epochs.sort());
+        LogListing {
+            path: log_path.to_path_buf(),
+            task: spec.as_ref().map(|spec| spec.task.clone()),
+            model: spec.map(|spec| spec.model).filter(|model| !model.is_empty()),
+            samples,
+        }
+    }
+
+    fn epochs(&self) -> usize {
+        self.samples.values().map(Vec::len).sum()
+    }
+}
+
+fn or_unknown(value: &Option<String>) -> &str {
+    value.as_deref().unwrap_or("?")
+}
+
+// One line per item on stdout (`log:sample` and `log:sample:epoch` as searches print
+// them, so they can be cut into filters), and the totals on stderr
+pub fn display(kind: ListKind, logs: &[LogListing]) {
+    match kind {
+        ListKind::Samples => {
+            for log in logs {
+                for (id, epochs) in &log.samples {
+                    let messages = epochs.iter().filter_map(|(_, messages)| *messages).join(", ");
+                    println!("{}:{}  {}", log.path.display(), id, format!("{} epochs, messages {}", epochs.len(), messages).dimmed());
+                }
+            }
+        }
+        ListKind::Epochs => {
+            for log in logs {
+                for (id, epochs) in &log.samples {
+                    for (epoch, messages) in epochs {
+                        println!("{}:{}:{}  {}", log.path.display(), id, epoch, format!("{} messages", messages.unwrap_or_default()).dimmed());
+                    }
+                }
+            }
+        }
+        ListKind::Files => {
+            for log in logs {
+                let epochs = log.samples.values().map(Vec::len).max().unwrap_or_default();
+                let contents = format!("{} samples, {} epochs", log.samples.len(), epochs);
+                println!("{}  {}  {}  {}", log.path.display().to_string().cyan(), or_unknown(&log.task).bold(), or_unknown(&log.model), contents.dimmed());
+            }
+        }
+        ListKind::Tasks => {
+            // (task, model) -> (logs, samples)
+            let mut tasks: BTreeMap<(&str, &str), (usize, usize)> = BTreeMap::new();
+            for log in logs {
+                let tally = tasks.entry((or_unknown(&log.task), or_unknown(&log.model))).or_default();
+                tally.0 += 1;
+                tally.1 += log.samples.len();
+            }
+            let task_width = tasks.keys().map(|(task, _)| task.chars().count()).max().unwrap_or_default().max("task".len());
+            let model_width = tasks.keys().map(|(_, model)| model.chars().count()).max().unwrap_or_default().max("model".len());
+            println!("{}", format!("{:<task_width$}  {:<model_width$}  {:>6}  {:>8}", "task", "model", "logs", "samples").bold());
+            for ((task, model), (logs, samples)) in &tasks {
+                println!("{:<task_width$}  {:<model_width$}  {:>6}  {:>8}", task, model, logs, samples);
+            }
+        }
+    }
+    let samples = logs.iter().map(|log| log.samples.len()).sum::<usize>();
+    let epochs = logs.iter().map(LogListing::epochs).sum::<usize>();
+    eprintln!("{} samples ({} sample epochs) in {} logs", samples, epochs, logs.len());
+}
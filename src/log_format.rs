@@ -0,0 +1,182 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::remote::RemoteObject;
+
+// How a log is stored on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    // A zip of header, sample and attachment entries
+    Eval,
+    // A single JSON document with every sample inline
+    Json,
+}
+
+impl LogFormat {
+    // By the zip magic bytes or a leading `{`, falling back to the extension (which
+    // remote logs go by alone)
+    pub fn detect(path: &Path) -> Result<Self> {
+        // Not worth a request to a remote log's first block
+        if crate::remote::is_remote(path) {
+            return Ok(if path.extension().is_some_and(|ext| ext == "json") { LogFormat::Json } else { LogFormat::Eval });
+        }
+        let start = read_start(path)?;
+        if start.starts_with(b"PK\x03\x04") {
+            return Ok(LogFormat::Eval);
+        }
+        if start.trim_ascii_start().starts_with(b"{") || path.extension().is_some_and(|ext| ext == "json") {
+            return Ok(LogFormat::Json);
+        }
+        Ok(LogFormat::Eval)
+    }
+}
+
+fn read_start(path: &Path) -> Result<Vec<u8>> {
+    let mut start = Vec::with_capacity(64);
+    reader(path)?.take(64).read_to_end(&mut start)?;
+    Ok(start)
+}
+
+// Whether a .json file is an Inspect log rather than some other JSON found next to
+// them (like `logs.json`), by Inspect writing `version` as the first field
+pub fn is_json_log(path: &Path) -> bool {
+    read_start(path).is_ok_and(|start| {
+        let rest = start.trim_ascii_start().strip_prefix(b"{").unwrap_or_default();
+        rest.trim_ascii_start().starts_with(b"\"version\"")
+    })
+}
+
+// A log laid out as the zip of an .eval file: the file itself, or for JSON logs an
+// equivalent archive built in memory. Clones share the file but each keeps its own
+// position, so one parsed archive can be cloned for every worker reading the log
+#[derive(Clone)]
+pub enum LogReader {
+    File { file: Arc<File>, position: u64 },
+    Remote { object: Arc<RemoteObject>, position: u64 },
+    Memory(Cursor<Arc<[u8]>>),
+}
+
+// The log's bytes from the start, wherever it's stored
+fn reader(path: &Path) -> Result<LogReader> {
+    Ok(match crate::remote::is_remote(path) {
+        true => LogReader::Remote { object: crate::remote::open(path)?, position: 0 },
+        false => LogReader::File { file: Arc::new(File::open(path)?), position: 0 },
+    })
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+impl Read for LogReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            LogReader::File { file, position } => {
+                let read = read_at(file, buf, *position)?;
+                *position += read as u64;
+                crate::resources::add_read(read);
+                Ok(read)
+            }
+            LogReader::Remote { object, position } => {
+                let read = object.read_at(buf, *position)?;
+                *position += read as u64;
+                Ok(read)
+            }
+            LogReader::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+// Move a reader's own position, given how to find the length for seeks from the end
+fn seek_to(position: &mut u64, pos: SeekFrom, len: impl FnOnce() -> std::io::Result<u64>) -> std::io::Result<u64> {
+    let (base, offset) = match pos {
+        SeekFrom::Start(offset) => (0, offset as i64),
+        SeekFrom::Current(offset) => (*position, offset),
+        SeekFrom::End(offset) => (len()?, offset),
+    };
+    *position = base.checked_add_signed(offset).ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "seek before the start of the file"))?;
+    Ok(*position)
+}
+
+impl Seek for LogReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            LogReader::File { file, position } => seek_to(position, pos, || Ok(file.metadata()?.len())),
+            LogReader::Remote { object, position } => seek_to(position, pos, || Ok(object.len())),
+            LogReader::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+// Open a log of either format as an archive of .eval entries
+pub fn open_log(path: &Path) -> Result<ZipArchive<LogReader>> {
+    let reader = match LogFormat::detect(path)? {
+        LogFormat::Eval => reader(path)?,
+        LogFormat::Json => LogReader::Memory(Cursor::new(converted(path)?)),
+    };
+    Ok(ZipArchive::new(reader)?)
+}
+
+// A log is opened several times over a scan (to list its samples, read its header
+// and then its samples), so recent conversions are kept; each worker usually has one
+// log open at a time
+static CONVERTED: Mutex<VecDeque<(PathBuf, Arc<[u8]>)>> = Mutex::new(VecDeque::new());
+
+fn converted(path: &Path) -> Result<Arc<[u8]>> {
+    if let Some((_, archive)) = CONVERTED.lock().unwrap().iter().find(|(cached, _)| cached == path) {
+        return Ok(archive.clone());
+    }
+    let archive = eval_archive(path)?;
+    let mut cache = CONVERTED.lock().unwrap();
+    if cache.len() >= rayon::current_num_threads().max(4) {
+        cache.pop_front();
+    }
+    cache.push_back((path.to_path_buf(), archive.clone()));
+    Ok(archive)
+}
+
+// The entries an .eval file would have for the same log: the header (everything but
+// the samples and reductions), one entry per sample and epoch, and the reductions
+fn eval_archive(path: &Path) -> Result<Arc<[u8]>> {
+    let mut log: serde_json::Map<String, Value> = serde_json::from_reader(std::io::BufReader::new(reader(path)?))
+        .with_context(|| format!("Failed to parse JSON log {}", path.display()))?;
+    if !log.contains_key("eval") {
+        bail!("{} is not an Inspect log (no \"eval\" field)", path.display());
+    }
+    let samples = log.remove("samples");
+    let reductions = log.remove("reductions");
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer.start_file("header.json", options)?;
+    serde_json::to_writer(&mut writer, &log)?;
+    for sample in samples.iter().filter_map(Value::as_array).flatten() {
+        let id = match sample.get("id") {
+            Some(Value::String(id)) => id.clone(),
+            Some(id) => id.to_string(),
+            None => bail!("A sample in {} has no id", path.display()),
+        };
+        let epoch = sample.get("epoch").and_then(Value::as_i64).unwrap_or(1);
+        writer.start_file(format!("samples/{}_epoch_{}.json", id, epoch), options)?;
+        serde_json::to_writer(&mut writer, sample)?;
+    }
+    if let Some(reductions) = reductions.filter(|r| !r.is_null()) {
+        writer.start_file("reductions.json", options)?;
+        serde_json::to_writer(&mut writer, &reductions)?;
+    }
+    writer.flush()?;
+    Ok(writer.finish()?.into_inner().into())
+}
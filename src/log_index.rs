@@ -0,0 +1,240 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::SystemTime;
+
+use crate::atomic::write_atomic;
+use crate::filter_set::RoleSet;
+use crate::inspect::ChatMessageRole;
+use crate::log_format::open_log;
+
+// The index used by this run, if any
+static INDEX: OnceLock<LogIndex> = OnceLock::new();
+
+// The user's cache directory for inspect-grep
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(cache.join("inspect-grep"))
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+enum Header {
+    Missing,
+    Found(Value),
+}
+
+// What's known about one version of a log, told apart by its modification time and size
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct IndexEntry {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    size: u64,
+    // Names of the log's zip entries, from which its samples and epochs are found
+    #[serde(default)]
+    file_names: Option<Vec<String>>,
+    #[serde(default)]
+    header: Option<Header>,
+    // Roles of the messages in its samples, once every sample has been read
+    #[serde(default)]
+    roles: Option<Vec<ChatMessageRole>>,
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl IndexEntry {
+    fn current(path: &Path) -> Self {
+        let metadata = std::fs::metadata(path).ok();
+        Self {
+            path: canonical(path),
+            modified: metadata.as_ref().and_then(|metadata| metadata.modified().ok()),
+            size: metadata.map_or(0, |metadata| metadata.len()),
+            ..Self::default()
+        }
+    }
+
+    fn describes(&self, current: &Self) -> bool {
+        self.path == current.path && self.modified.is_some() && self.modified == current.modified && self.size == current.size
+    }
+}
+
+// What scans learn about each log without reading its samples (its zip entries and
+// header), kept on disk so repeated searches over the same logs skip opening them
+// just to list samples or check headers. Each log has its own file in the index
+// directory, so concurrent runs only ever replace whole entries
+struct LogIndex {
+    dir: PathBuf,
+    // Ignore what's on disk, so every log scanned is indexed afresh
+    rebuild: bool,
+    entries: Mutex<HashMap<PathBuf, IndexEntry>>,
+    changed: Mutex<HashSet<PathBuf>>,
+}
+
+impl LogIndex {
+    fn entry_path(&self, log_path: &Path) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", crate::fingerprint::hash(&canonical(log_path).to_string_lossy())))
+    }
+
+    // The entry for the log as it is now, read from disk the first time it's asked for
+    fn get<T>(&self, log_path: &Path, field: impl FnOnce(&IndexEntry) -> Option<T>) -> Option<T> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.entry(log_path.to_path_buf()).or_insert_with(|| {
+            let current = IndexEntry::current(log_path);
+            let stored = (!self.rebuild)
+                .then(|| std::fs::read(self.entry_path(log_path)).ok())
+                .flatten()
+                .and_then(|bytes| serde_json::from_slice::<IndexEntry>(&bytes).ok());
+            stored.filter(|stored| stored.describes(&current)).unwrap_or(current)
+        });
+        field(entry)
+    }
+
+    fn update(&self, log_path: &Path, update: impl FnOnce(&mut IndexEntry)) {
+        self.get(log_path, |_| None::<()>);
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(log_path) {
+            update(entry);
+        }
+        self.changed.lock().unwrap().insert(log_path.to_path_buf());
+    }
+
+    fn save(&self) -> Result<()> {
+        let entries = self.entries.lock().unwrap();
+        for log_path in self.changed.lock().unwrap().iter() {
+            if let Some(entry) = entries.get(log_path) {
+                write_atomic(&self.entry_path(log_path), &serde_json::to_vec(entry)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Use the index in the user's cache directory for this run; `rebuild` re-indexes
+/// every log scanned instead of trusting what's stored.
+pub fn enable(rebuild: bool) {
+    if let Some(cache) = cache_dir() {
+        let index = LogIndex { dir: cache.join("index"), rebuild, entries: Mutex::default(), changed: Mutex::default() };
+        let _ = INDEX.set(index);
+    }
+}
+
+/// Names of a log's zip entries.
+pub fn file_names(log_path: &Path) -> Result<Vec<String>> {
+    let index = INDEX.get();
+    if let Some(file_names) = index.and_then(|index| index.get(log_path, |entry| entry.file_names.clone())) {
+        return Ok(file_names);
+    }
+    let file_names = open_log(log_path)?.file_names().map(String::from).collect::<Vec<_>>();
+    if let Some(index) = index {
+        index.update(log_path, |entry| entry.file_names = Some(file_names.clone()));
+    }
+    Ok(file_names)
+}
+
+/// A log's header.json, or None if it has none.
+pub fn header(log_path: &Path) -> Result<Option<Value>> {
+    let index = INDEX.get();
+    if let Some(header) = index.and_then(|index| index.get(log_path, |entry| entry.header.clone())) {
+        return Ok(match header {
+            Header::Found(header) => Some(header),
+            Header::Missing => None,
+        });
+    }
+    let header: Option<Value> = crate::read_json_entry(log_path, "header.json")?;
+    if let Some(index) = index {
+        let stored = header.clone().map_or(Header::Missing, Header::Found);
+        index.update(log_path, |entry| entry.header = Some(stored));
+    }
+    Ok(header)
+}
+
+/// Record the roles found once every sample of a log has been read.
+pub fn record_roles<'a>(log_path: &Path, roles: impl IntoIterator<Item = &'a ChatMessageRole>) {
+    if let Some(index) = INDEX.get() {
+        let mut found: Vec<ChatMessageRole> = Vec::new();
+        for role in roles {
+            if !found.contains(role) {
+                found.push(role.clone());
+            }
+        }
+        if index.get(log_path, |entry| entry.roles.clone()).as_ref() != Some(&found) {
+            index.update(log_path, |entry| entry.roles = Some(found));
+        }
+    }
+}
+
+/// Whether the index knows the log has no messages with any of these roles.
+pub fn lacks_roles(log_path: &Path, roles: RoleSet) -> bool {
+    let known = INDEX.get().and_then(|index| index.get(log_path, |entry| entry.roles.clone()));
+    known.is_some_and(|known| !known.iter().any(|role| roles.contains(role)))
+}
+
+/// Write the entries this run added to or changed.
+pub fn save() -> Result<()> {
+    INDEX.get().map_or(Ok(()), LogIndex::save)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(dir: &Path, rebuild: bool) -> LogIndex {
+        LogIndex { dir: dir.to_path_buf(), rebuild, entries: Mutex::default(), changed: Mutex::default() }
+    }
+
+    fn roles(index: &LogIndex, log_path: &Path) -> Option<Vec<ChatMessageRole>> {
+        index.get(log_path, |entry| entry.roles.clone())
+    }
+
+    #[test]
+    fn stored_entries_are_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("a.eval");
+        std::fs::write(&log, "log").unwrap();
+        let first = index(&dir.path().join("index"), false);
+        assert_eq!(roles(&first, &log), None);
+        first.update(&log, |entry| entry.roles = Some(vec![ChatMessageRole::User]));
+        first.save().unwrap();
+
+        assert_eq!(roles(&index(&dir.path().join("index"), false), &log), Some(vec![ChatMessageRole::User]));
+        // Rebuilding ignores what's stored
+        assert_eq!(roles(&index(&dir.path().join("index"), true), &log), None);
+    }
+
+    #[test]
+    fn changed_logs_are_indexed_afresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("a.eval");
+        std::fs::write(&log, "log").unwrap();
+        let first = index(&dir.path().join("index"), false);
+        first.update(&log, |entry| entry.file_names = Some(vec!["header.json".to_string()]));
+        first.save().unwrap();
+
+        std::fs::write(&log, "a longer log").unwrap();
+        let second = index(&dir.path().join("index"), false);
+        assert_eq!(second.get(&log, |entry| entry.file_names.clone()), None);
+    }
+
+    #[test]
+    fn entries_describe_one_version_of_a_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("a.eval");
+        std::fs::write(&log, "log").unwrap();
+        let current = IndexEntry::current(&log);
+        assert!(current.describes(&IndexEntry::current(&log)));
+        assert!(!IndexEntry { size: 4, ..current.clone() }.describes(&current));
+        assert!(!IndexEntry { modified: current.modified.map(|modified| modified + std::time::Duration::from_secs(1)), ..current.clone() }.describes(&current));
+        // Without a modification time there's no telling versions apart
+        let unknown = IndexEntry { modified: None, ..current.clone() };
+        assert!(!unknown.describes(&unknown));
+        // Logs that don't exist have no modification time, so are never trusted
+        let missing = IndexEntry::current(&dir.path().join("missing.eval"));
+        assert!(!missing.describes(&missing));
+    }
+}
@@ -1,38 +1,378 @@
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Parser, Subcommand};
 use colored::*;
-use indicatif::{ProgressBar, ProgressStyle};
+use globset::Glob;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
 use itertools::Itertools;
 use rayon::prelude::*;
-use regex::Regex;
-use std::collections::HashMap;
-use std::str::FromStr;
+use regex::{Regex, RegexBuilder};
+use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::{
-    collections::HashSet,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
-use walkdir::WalkDir;
-use zip::ZipArchive;
-use lazy_static::lazy_static;
 
-mod inspect;
-use inspect::{deserialize_sample_filtered, ChatMessage, ChatMessageRole, EvalSample};
+use inspect_grep::{
+    bundle, compare, dedup, environment, export, glyphs, grep_compat, history, interrupt, jsonl, line_mode, log_index, queries, resources, sample_error, scores, skipped, slim, svg, tui, turns, wizard,
+};
+use inspect_grep::{
+    display_context_message, display_message, emit_results, eval_files, matching_samples_in_log, process_eval_file, process_eval_file_within, read_whole_samples,
+    read_file_list, read_json_entry, search_files, split_oversized, streamed_sample, Discovery, IntFilter, Page, ScanOrder,
+};
+use inspect_grep::aliases::IdAliases;
+use inspect_grep::answer_variance::AnswerVariance;
+use inspect_grep::answers::AnswerTable;
+use inspect_grep::anywhere::{display_anywhere_match, search_anywhere};
+use inspect_grep::assertion::Assertion;
+use inspect_grep::assembly::SampleLayout;
+use inspect_grep::bundle::Bundle;
+use inspect_grep::config_filter::{ConfigFilter, HeaderFilter};
+use inspect_grep::counters::ScanCounters;
+use inspect_grep::encoding::Encoding;
+use inspect_grep::epoch_scores::ReducedScores;
+use inspect_grep::events::{display_sample_events, search_events, EventQuery};
+use inspect_grep::export::{ExportFormat, TranscriptStyle};
+use inspect_grep::export_eval::EvalExport;
+use inspect_grep::extract::{self, CaptureCounts};
+use inspect_grep::filter_set::{FilterSet, RoleSet};
+use inspect_grep::fingerprint::Fingerprints;
+use inspect_grep::fixtures::{self, FixtureOptions};
+use inspect_grep::gen_config::{self, GenConfigFilter};
+use inspect_grep::heatmap::display_heatmap_line;
+use inspect_grep::hot_files::HitHistory;
+use inspect_grep::images::ImageProtocol;
+use inspect_grep::inspect::{ReadOptions, ChatMessageRole, EvalLogHeader, EvalSample, FieldLimits};
+use inspect_grep::interrupt::interrupted;
+use inspect_grep::jsonl::{MessageSource, OutputFormat};
+use inspect_grep::context::ContextWindow;
+use inspect_grep::dedup::ContentGroups;
+use inspect_grep::environment::SandboxSpec;
+use inspect_grep::language::Language;
+use inspect_grep::lint::TranscriptLint;
+use inspect_grep::listing::{self, ListKind, LogListing};
+use inspect_grep::message_index::MessageIndexFilter;
+use inspect_grep::message_pattern::{MessagePattern, PatternSyntax, RolePattern};
+use inspect_grep::preset::Preset;
+use inspect_grep::provenance::Provenance;
+use inspect_grep::quota::MatchQuota;
+use inspect_grep::repl::Session;
+use inspect_grep::numbers::NumberRange;
+use inspect_grep::notify::{format_elapsed, notify_if_slow, parse_duration, NotifyAfter};
+use inspect_grep::raw::{display_raw_match, search_raw};
+use inspect_grep::reference::{display_divergence, first_divergence, load_reference};
+use inspect_grep::relevance;
+use inspect_grep::score_filter::{Correctness, ScoreFilter, ScoreSelection};
+use inspect_grep::repro::ReproCommand;
+use inspect_grep::rescore::{display_verdict, rescore, ChatJson, LabelsFile};
+use inspect_grep::rollup::Rollup;
+use inspect_grep::spill::ByteSize;
+use inspect_grep::stats::{MatchStats, Stats};
+use inspect_grep::stop_reason::StopReason;
+use inspect_grep::stream_to::MatchStream;
+use inspect_grep::streaming::display_streamed;
+use inspect_grep::summary::display_summary_line;
+use inspect_grep::template::{Template, TemplateValues};
+use inspect_grep::theme::Theme;
+use inspect_grep::tool_calls::ToolCallFilter;
+use inspect_grep::truncation::truncation_reasons;
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    search: Option<Args>,
+
+    /// Build a search interactively, printing the equivalent command line before running it
+    #[arg(long, exclusive = true)]
+    wizard: bool,
+
+    /// Don't use the on-disk index of logs' samples and headers (in ~/.cache/inspect-grep/index)
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Index every log scanned afresh, replacing what the on-disk index holds for it
+    #[arg(long, global = true, conflicts_with = "no_cache")]
+    rebuild_index: bool,
 
-lazy_static! {
-    static ref SAMPLE_ID_EPOCH_RE: Regex =
-        Regex::new(r"^samples/(.*)_epoch_(\d+)\.json$").expect("Failed to compile regex");
+    /// Stop at the first log or sample that can't be read, instead of skipping it and reporting it at the end
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Skip logs and samples that can't be read and list them at the end (the default)
+    #[arg(long, global = true, conflicts_with = "strict")]
+    skip_errors: bool,
+
+    /// Draw progress bars, markers and borders with ASCII only (the default when the locale isn't UTF-8 or the terminal is the Linux console)
+    #[arg(long, global = true)]
+    ascii: bool,
+
+    /// After the run, print peak memory, CPU time, bytes read and decompressed, and samples parsed to stderr
+    #[arg(long, global = true)]
+    resource_report: bool,
+
+    /// Output format for matched messages: colored `text`, or `jsonl` with one JSON object per message
+    #[arg(long, value_enum, default_value = "text", conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "heatmap", "turns", "grep_compat", "queries", "preset", "show_env", "show_tools", "show_images", "dedup_content", "line_mode", "count", "quiet", "answer_variance", "tui", "samples_without_match", "events", "extract", "extract_count", "emit_repro", "template"])]
+    format: OutputFormat,
 }
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print aggregate statistics over matching samples
+    Stats(Box<StatsArgs>),
+    /// Print per-log score tables from log headers, without reading samples
+    Scores(ScoresArgs),
+    /// Show word-level diffs of assistant messages between two runs of the same samples
+    Compare(CompareArgs),
+    /// Write matching samples out for reading or processing elsewhere
+    Export(Box<ExportArgs>),
+    /// Show how one sample's score and final answer changed across every log containing it
+    History(HistoryArgs),
+    /// Group matching samples by conversation structure (roles and tool calls, ignoring content)
+    Fingerprint(Box<Args>),
+    /// Write a copy of a log with messages of some roles removed and long content truncated
+    Slim(SlimArgs),
+    /// Package the raw JSON of matching samples, their attachments and their logs' headers into a .tar.gz
+    Bundle(Box<BundleArgs>),
+    /// Flag chat protocol violations in matching samples (orphan tool messages, system messages mid-conversation, empty assistant turns)
+    LintTranscripts(Box<Args>),
+    /// List each matching sample's final answer next to its target and score
+    Answers(Box<AnswersArgs>),
+    /// Find where a string first appeared in each matching sample: the input, a tool's output, or an assistant message
+    Provenance(Box<ProvenanceArgs>),
+    /// Load matching samples once, then search them again for each pattern typed at a prompt
+    Repl(Box<ReplArgs>),
+    /// Write a synthetic .eval log with chosen samples, tool calls, scores and errors, for tests and demos
+    Fixtures(FixturesArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct FixturesArgs {
+    /// Where to write the log
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Task name in the header
+    #[arg(long, default_value = "fixture")]
+    task: String,
+
+    /// Model name in the header and on every model call
+    #[arg(long, default_value = "mockllm/model")]
+    model: String,
+
+    /// Number of samples, with ids 1 to N
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    samples: usize,
+
+    /// Epochs of each sample
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    epochs: usize,
+
+    /// Rounds of bash tool calls before each final answer
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    turns: usize,
+
+    /// Share of the answered samples scored correct (the first ones by id)
+    #[arg(long, value_name = "FRACTION", default_value_t = 0.5)]
+    accuracy: f64,
+
+    /// Number of samples (the last ones by id) that end in an error instead of an answer
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    errors: usize,
+
+    /// Seed for the numbers in the questions
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(clap::Args, Debug)]
+struct ReplArgs {
+    #[command(flatten)]
+    search: Args,
+
+    /// Keep up to this much message text in memory (e.g. 512M, 4G); samples beyond it are re-read for each query
+    #[arg(long, value_name = "SIZE", default_value = "1G")]
+    cache_messages: ByteSize,
+}
+
+#[derive(clap::Args, Debug)]
+struct ProvenanceArgs {
+    /// The string to trace (matched literally; -i ignores case)
+    string: String,
+
+    #[command(flatten)]
+    search: Args,
+}
+
+#[derive(clap::Args, Debug)]
+struct AnswersArgs {
+    #[command(flatten)]
+    search: Args,
+
+    /// Print CSV rows (log, sample_id, epoch, target, final_answer, score) for a regrading spreadsheet
+    #[arg(long)]
+    csv: bool,
+}
+
+#[derive(clap::Args, Debug)]
+struct SlimArgs {
+    /// The .eval file to slim down
+    log: PathBuf,
+
+    /// Where to write the slimmed log
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Remove messages with these roles (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    drop_roles: Vec<ChatMessageRole>,
+
+    /// Truncate message content and attachments longer than this many characters
+    #[arg(long, value_name = "N")]
+    max_chars: Option<usize>,
+
+    /// Regex naming sample entries, with `id` and optional `epoch`/`part` groups (auto-detected by default)
+    #[arg(long)]
+    sample_layout: Option<SampleLayout>,
+}
+
+#[derive(clap::Args, Debug)]
+struct HistoryArgs {
+    /// Sample ID to follow (matched exactly)
+    sample_id: String,
+
+    /// Path to .eval file or directory containing .eval files
+    path: PathBuf,
+
+    /// Filter by epoch number
+    #[arg(short, long, default_value = "all")]
+    epochs: IntFilter,
+
+    /// Regex naming sample entries, with `id` and optional `epoch`/`part` groups (auto-detected by default)
+    #[arg(long)]
+    sample_layout: Option<SampleLayout>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ExportArgs {
+    #[command(flatten)]
+    search: Args,
+
+    /// Export format
+    #[arg(long, value_enum, default_value = "files")]
+    format: ExportFormat,
+
+    /// Directory to write exported files to
+    #[arg(long, required = true)]
+    dir: PathBuf,
+
+    /// How transcripts are rendered
+    #[arg(long, value_enum, default_value = "markdown")]
+    style: TranscriptStyle,
+}
+
+#[derive(clap::Args, Debug)]
+struct BundleArgs {
+    #[command(flatten)]
+    search: Args,
+
+    /// Where to write the bundle (.tar.gz)
+    #[arg(long, required = true)]
+    out: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    /// The baseline .eval file
+    left: PathBuf,
+
+    /// The .eval file to compare against the baseline
+    right: PathBuf,
+
+    /// Filter by sample ID
+    #[arg(short, long)]
+    samples: Option<String>,
+
+    /// Filter by epoch number
+    #[arg(short, long, default_value = "all")]
+    epochs: IntFilter,
+
+    /// Regex naming sample entries, with `id` and optional `epoch`/`part` groups (auto-detected by default)
+    #[arg(long)]
+    sample_layout: Option<SampleLayout>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ScoresArgs {
     /// Path to .eval file or directory containing .eval files
-    #[arg(required = true)]
+    path: PathBuf,
+}
+
+#[derive(clap::Args, Debug)]
+struct StatsArgs {
+    #[command(flatten)]
+    search: Args,
+
+    /// Render histograms and score distributions to an SVG file
+    #[arg(long)]
+    svg: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+struct Args {
+    /// Path to .eval file or directory containing .eval files, or an s3:// prefix or http(s):// log URL (with --files-from, the directory --rollup totals up to)
+    #[arg(required_unless_present = "files_from", default_value = ".", hide_default_value = true)]
     path: PathBuf,
 
-    /// Search pattern (regex)
+    /// Search pattern (regex); repeat to search for several, matching any of them (or all of them with --all)
     #[arg(short, long)]
-    message_regex: Option<String>,
+    message_regex: Vec<String>,
+
+    /// Search pattern for messages of some roles only, as ROLES=PATTERN (e.g. assistant=refuse, user,system=ignore previous); repeat and mix with -m for cross-role queries
+    #[arg(long, value_name = "ROLES=PATTERN")]
+    role_regex: Vec<RolePattern>,
+
+    /// With several -m patterns, a message must match every one of them
+    #[arg(long, conflicts_with = "any")]
+    all: bool,
+
+    /// With several -m patterns, a message must match at least one of them (the default)
+    #[arg(long)]
+    any: bool,
+
+    /// Match -m, --raw-regex and --anywhere patterns case-insensitively
+    #[arg(short = 'i', long, conflicts_with = "smart_case")]
+    ignore_case: bool,
+
+    /// Match patterns case-insensitively unless they contain an uppercase letter
+    #[arg(long)]
+    smart_case: bool,
+
+    /// Treat patterns as literal strings rather than regexes
+    #[arg(short = 'F', long)]
+    fixed_strings: bool,
+
+    /// Select the messages that don't match -m (the other filters still apply)
+    #[arg(short = 'v', long, requires = "message_regex")]
+    invert_match: bool,
+
+    /// List the samples where no message matched, as `path:sample:epoch`, e.g. runs that never called the submit tool
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries", "count", "quiet", "dedup_content", "answer_variance", "tui", "stream_to", "export_eval"])]
+    samples_without_match: bool,
+
+    /// Search each sample's event transcript (model, tool, sandbox, logger events...) instead of its messages, e.g. for tool stderr that never reached the chat
+    #[arg(long, conflicts_with_all = ["roles", "reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries", "count", "quiet", "dedup_content", "answer_variance", "tui", "samples_without_match", "stream_to", "export_eval"])]
+    events: bool,
+
+    /// Only search events of this type (e.g. tool, sandbox, logger); can be given several times
+    #[arg(long, value_name = "TYPE", requires = "events")]
+    event_type: Vec<String>,
 
     /// Filter by sample ID
     #[arg(short, long)]
@@ -46,214 +386,1859 @@ struct Args {
     #[arg(short, long, value_delimiter = ',', num_args = 0..)]
     roles: Vec<ChatMessageRole>,
 
+    /// Skip messages with these roles (comma-separated), e.g. tool,system
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["malformed_only", "events", "queries", "preset"])]
+    exclude_roles: Vec<ChatMessageRole>,
+
+    /// Only match assistant messages, like --roles assistant
+    #[arg(long, conflicts_with_all = ["roles", "user_only", "exclude_roles", "malformed_only", "events", "queries", "preset"])]
+    assistant_only: bool,
+
+    /// Only match user messages, like --roles user
+    #[arg(long, conflicts_with_all = ["roles", "exclude_roles", "malformed_only", "events", "queries", "preset"])]
+    user_only: bool,
+
+    /// Only match malformed messages, whose role was missing, empty or unrecognized (shown as `unknown`)
+    #[arg(long, conflicts_with_all = ["roles", "raw_regex", "anywhere", "queries"])]
+    malformed_only: bool,
+
+    /// Print N messages after each matching message (N lines within it, with --line-mode)
+    #[arg(short = 'A', long, value_name = "N", conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries"])]
+    after_context: Option<usize>,
+
+    /// Print N messages before each matching message (N lines within it, with --line-mode)
+    #[arg(short = 'B', long, value_name = "N", conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries"])]
+    before_context: Option<usize>,
+
+    /// Print N messages before and after each matching message (N lines within it, with --line-mode)
+    #[arg(short = 'C', long, value_name = "N", conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries"])]
+    context: Option<usize>,
+
+    /// Print the whole conversation of each sample with a matching message, marking the other messages as context
+    #[arg(long, conflicts_with_all = ["after_context", "before_context", "context", "reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries"])]
+    whole_sample: bool,
+
+    /// Print only the lines of each matching message that contain a match, like grep, instead of its whole content
+    #[arg(long, conflicts_with_all = ["whole_sample", "reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries", "dedup_content"])]
+    line_mode: bool,
+
+    /// Never print message content, only counts: totals of matches, samples, messages and files
+    #[arg(long, conflicts_with_all = ["rescore_cmd", "labels"])]
+    aggregate_only: bool,
+
+    /// After the matches, report where they fell: files and samples scanned, matches per role, task and epoch, the top matching samples, and throughput
+    #[arg(long, conflicts_with_all = ["queries", "answer_variance", "tui", "samples_without_match", "events", "quiet"])]
+    stats: bool,
+
+    /// After the matches, total the logs, matching samples and matches in each directory under the path, nested by level
+    #[arg(long, conflicts_with_all = ["queries", "answer_variance", "tui", "samples_without_match", "events", "quiet"])]
+    rollup: bool,
+
+    /// Report samples whose epochs gave different final answers (a scorer's extracted answer, else the last assistant message), with each answer
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries", "count", "quiet", "dedup_content"])]
+    answer_variance: bool,
+
+    /// Browse matches in a full-screen view: the list of matches beside the selected message, with roles toggled live
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries", "count", "quiet", "dedup_content", "answer_variance", "aggregate_only", "stream_to"])]
+    tui: bool,
+
+    /// Print how many messages matched in each sample (`path:sample:epoch:count`) and each log (`path:count`) instead of the messages
+    #[arg(long, conflicts_with_all = ["limit", "offset", "after_context", "before_context", "context", "whole_sample", "line_mode", "reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries", "show_env", "show_tools", "dedup_content", "aggregate_only"])]
+    count: bool,
+
+    /// Print only the text each match's capture groups captured (the whole match without groups), one line per capture
+    #[arg(long, requires = "message_regex", conflicts_with_all = ["extract_count", "invert_match", "limit", "offset", "after_context", "before_context", "context", "whole_sample", "line_mode", "reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "heatmap", "turns", "grep_compat", "queries", "dedup_content", "count", "aggregate_only", "answer_variance", "tui", "samples_without_match", "events"])]
+    extract: bool,
+
+    /// Print a table of how often each distinct captured value occurred, most frequent first, instead of the messages
+    #[arg(long, requires = "message_regex", conflicts_with_all = ["invert_match", "limit", "offset", "after_context", "before_context", "context", "whole_sample", "line_mode", "reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "heatmap", "turns", "grep_compat", "queries", "dedup_content", "count", "aggregate_only", "answer_variance", "tui", "samples_without_match", "events"])]
+    extract_count: bool,
+
+    /// Print nothing, and exit with status 0 if anything matched and 1 otherwise; stops at the first log with a match
+    #[arg(short, long, conflicts_with_all = ["count", "aggregate_only", "assertions", "limit", "offset", "queries", "rescore_cmd", "labels", "dedup_content"])]
+    quiet: bool,
+
+    /// Only match samples where SCORER gave this score (e.g. match=C, accuracy=1); can be given several times
+    #[arg(long = "score", value_name = "SCORER=VALUE", conflicts_with_all = ["raw_regex", "anywhere"])]
+    score_filters: Vec<ScoreFilter>,
+
+    /// Only match samples every scorer marked correct (C, 1 or true)
+    #[arg(long, conflicts_with_all = ["incorrect", "raw_regex", "anywhere"])]
+    correct: bool,
+
+    /// Only match samples some scorer marked less than fully correct
+    #[arg(long, conflicts_with_all = ["raw_regex", "anywhere"])]
+    incorrect: bool,
+
+    /// Only match samples where some scorer's explanation of its score matches this regex (e.g. 'format')
+    #[arg(long, value_name = "REGEX", conflicts_with_all = ["raw_regex", "anywhere"])]
+    explanation_regex: Option<Regex>,
+
     /// Number of threads to use (default: number of CPU cores)
     #[arg(short, long)]
     threads: Option<usize>,
-}
 
-trait Filter<T> {
-    fn filter(&self, item: &T) -> bool;
-}
+    /// Order in which files are handed to worker threads
+    #[arg(long, value_enum)]
+    scan_order: Option<ScanOrder>,
+
+    /// Scan logs that matched earlier similar --hot-first searches first (remembered in ~/.cache/inspect-grep)
+    #[arg(long, conflicts_with = "queries")]
+    hot_first: bool,
+
+    /// Also search plain-JSON Inspect logs (`.json`) found when walking a directory
+    #[arg(long)]
+    include_json: bool,
+
+    /// Only search logs matching this glob, e.g. '*swe_bench*' (against the file name, or the path under the directory when it has a `/`); repeat to allow several
+    #[arg(long, value_name = "GLOB")]
+    glob: Vec<Glob>,
+
+    /// Skip logs and directories matching this glob, matched as --glob is; repeat to skip several
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<Glob>,
+
+    /// Go at most N directories deep when walking the path (1 searches only its own files)
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Walk into directories reached through symbolic links
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Search the logs listed in this file (`-` for stdin), one per line or NUL-separated, instead of walking PATH
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["max_depth", "follow_symlinks"])]
+    files_from: Option<PathBuf>,
+
+    /// Print the logs that would be searched, one per line, without searching them
+    #[arg(long)]
+    list_files: bool,
+
+    /// Print what's in the logs instead of searching them: sample IDs, epochs, logs, or tasks and models
+    #[arg(long, value_name = "WHAT", conflicts_with = "list_files")]
+    list: Option<ListKind>,
+
+    /// Color theme: a built-in name (dark, light) or the path to a TOML theme file
+    #[arg(long, default_value = "dark")]
+    theme: Theme,
+
+    /// Print at most N matches; pages are stable for the same --scan-order and --rank (logs in scan order, then sample, epoch and message)
+    #[arg(long)]
+    limit: Option<usize>,
+
+    /// Skip the first N matches; pages are stable for the same --scan-order and --rank (logs in scan order, then sample, epoch and message)
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
 
-#[derive(Debug, Clone)]
-enum IntFilter {
-    All,
-    Some(HashSet<u32>),
-    Range(u32, u32),
+    /// Stop searching once N matches are found, reading no further samples or logs (which N depends on which are read first)
+    #[arg(long, value_name = "N", conflicts_with_all = ["raw_regex", "anywhere", "reference", "rescore_cmd", "truncated_only", "after_context", "before_context", "context", "queries", "answer_variance", "tui", "samples_without_match", "events"])]
+    max_results: Option<usize>,
+
+    /// Find at most N matches in each log, reading no more of its samples once they're found
+    #[arg(long, value_name = "N", conflicts_with_all = ["raw_regex", "anywhere", "reference", "rescore_cmd", "truncated_only", "after_context", "before_context", "context", "queries", "answer_variance", "tui", "samples_without_match", "events"])]
+    max_per_file: Option<usize>,
+
+    /// Show at most N matching messages from each sample
+    #[arg(long, value_name = "N", conflicts_with_all = ["raw_regex", "anywhere", "reference", "rescore_cmd", "truncated_only", "after_context", "before_context", "context", "queries", "answer_variance", "tui", "samples_without_match", "events"])]
+    max_per_sample: Option<usize>,
+
+    /// Reference transcript (JSON) to compare matched samples against
+    #[arg(long)]
+    reference: Option<PathBuf>,
+
+    /// Report samples whose raw JSON matches this pattern (regex), including fields not otherwise searched
+    #[arg(long, conflicts_with = "reference")]
+    raw_regex: Option<String>,
+
+    /// Print the raw JSON of samples matched by --raw-regex
+    #[arg(long, requires = "raw_regex")]
+    dump_raw: bool,
+
+    /// Name logs and samples by stable aliases (log-1.eval, sample-1) recorded in this JSON mapping file, for sharing results outside the team
+    #[arg(long, value_name = "MAP", conflicts_with_all = ["dump_raw", "queries", "tui", "answer_variance"])]
+    alias_ids: Option<PathBuf>,
+
+    /// Also write every matching sample, whole, into a new .eval log with the source log's header, to share or open in Inspect View
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["queries", "tui", "answer_variance", "quiet"])]
+    export_eval: Option<PathBuf>,
+
+    /// Report samples where this pattern (regex) appears in any field (messages, tool args, input, target, output, metadata, scores, attachments)
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex"])]
+    anywhere: Option<String>,
+
+    /// Pipe each matched sample (as chat JSON on stdin) to this shell command and report its output as a verdict
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere"])]
+    rescore_cmd: Option<String>,
+
+    /// JSONL file to record --rescore-cmd verdicts in
+    #[arg(long, requires = "rescore_cmd")]
+    labels: Option<PathBuf>,
+
+    /// Only show samples that ended abnormally (cut off by a length limit, unanswered tool call, no final answer)
+    #[arg(long)]
+    truncated_only: bool,
+
+    /// Show where each matched message lives in the archive (zip entry and byte range within it)
+    #[arg(long)]
+    source_map: bool,
+
+    /// Regex naming sample entries, with `id` and optional `epoch`/`part` groups (auto-detected by default)
+    #[arg(long)]
+    sample_layout: Option<SampleLayout>,
+
+    /// YAML file of named queries to run together in a single pass, reporting results per query
+    #[arg(long, conflicts_with_all = ["message_regex", "role_regex", "roles", "reference", "raw_regex", "anywhere", "rescore_cmd", "truncated_only"])]
+    queries: Option<PathBuf>,
+
+    /// Run a built-in bundle of patterns with severities like --queries, ranking results by severity (repeat for several)
+    #[arg(long, value_enum, conflicts_with_all = ["message_regex", "role_regex", "roles", "reference", "raw_regex", "anywhere", "rescore_cmd", "truncated_only", "samples_without_match", "events", "malformed_only", "after_context", "before_context", "context", "whole_sample", "line_mode", "stats", "rollup", "answer_variance", "tui", "count", "extract", "extract_count", "quiet", "hot_first", "max_results", "max_per_file", "max_per_sample", "alias_ids", "export_eval", "assertions", "summary_line", "emit_repro", "heatmap", "tool_name", "tool_args", "numbers", "message_index", "languages", "time_budget", "grep_compat", "show_env", "sandbox_filter", "show_tools", "show_images", "has_tool", "stop_reason", "errors_only", "no_errors", "dedup_content", "turns", "min_turn_tokens", "show_gen_config", "temperature", "seed", "stream_to"])]
+    preset: Vec<Preset>,
+
+    /// Condition on the totals to check after scanning, e.g. 'matches == 0'; exits with status 1 if any fails
+    #[arg(long = "assert", value_name = "EXPR", allow_hyphen_values = true, conflicts_with = "queries")]
+    assertions: Vec<Assertion>,
+
+    /// Print one line per matched sample (id, epoch, score and a preview of the first match) instead of every message
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "queries"])]
+    summary_line: bool,
+
+    /// Print the `inspect eval` command re-running each matched sample (task, model, -T/-M args and limits from the log's header) instead of its messages
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "queries", "summary_line", "heatmap", "turns", "grep_compat", "count", "extract", "extract_count", "dedup_content", "answer_variance", "tui", "samples_without_match", "events"])]
+    emit_repro: bool,
+
+    /// Print one line per matched sample with a strip showing where in its conversation the matches fall, start to end, instead of the messages
+    #[arg(long, conflicts_with_all = ["summary_line", "reference", "raw_regex", "anywhere", "rescore_cmd", "turns", "grep_compat", "queries", "count", "dedup_content"])]
+    heatmap: bool,
+
+    /// Memory to hold results in while putting them in order (e.g. 512M, 2G); the rest are spilled to temp files and everything is printed at the end
+    #[arg(long, value_name = "SIZE")]
+    max_memory: Option<ByteSize>,
+
+    /// Print each log's results as soon as it has been read, rather than in scan order, for maximum throughput
+    #[arg(long, conflicts_with_all = ["limit", "offset", "max_memory"])]
+    unordered: bool,
+
+    /// Print matching samples most relevant first, by a BM25-style score over the -m and --role-regex patterns (matching samples are held in memory until the scan ends)
+    #[arg(long, conflicts_with_all = ["invert_match", "unordered", "max_memory", "max_results", "raw_regex", "anywhere", "queries", "preset", "events", "tui", "samples_without_match", "answer_variance", "dedup_content", "extract_count"])]
+    rank: bool,
+
+    /// Search and show `attachment://<hash>` references in message content as they are, rather than the content they refer to
+    #[arg(long)]
+    no_resolve_attachments: bool,
+
+    /// Search and show tool output wrapped in a JSON envelope (`{"stdout": ..., "stderr": ...}`) as labelled plain text
+    #[arg(long)]
+    unwrap_tool_output: bool,
+
+    /// Only search logs whose header config has KEY matching REGEX in full (e.g. temperature=0, sandbox=docker); KEY!=REGEX excludes them instead
+    #[arg(long = "config-filter", value_name = "KEY=REGEX")]
+    config_filters: Vec<ConfigFilter>,
+
+    /// Only search logs whose task name matches this regex
+    #[arg(long, value_name = "REGEX")]
+    task: Option<Regex>,
+
+    /// Only search logs whose model (e.g. openai/gpt-4o) matches this regex
+    #[arg(long, value_name = "REGEX")]
+    model: Option<Regex>,
+
+    /// Only search logs whose run id matches this regex
+    #[arg(long, value_name = "REGEX")]
+    run_id: Option<Regex>,
+
+    /// Only match messages making a tool call whose function name matches this regex
+    #[arg(long, value_name = "REGEX", conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    tool_name: Option<Regex>,
+
+    /// Only match messages making a tool call whose arguments (rendered as `name: value` lines) match this regex
+    #[arg(long, value_name = "REGEX", conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    tool_args: Option<Regex>,
+
+    /// Only match messages mentioning a number in this inclusive range, e.g. 95..100, 0.9.. or ..-1
+    #[arg(long, value_name = "RANGE", allow_hyphen_values = true, conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    numbers: Option<NumberRange>,
+
+    /// Only match messages at these positions in the conversation, counting from 0, e.g. 0, 0-5, 2,4,7 or all; negative ones count from the end (-1 is the last message)
+    #[arg(long, value_name = "INDICES", allow_hyphen_values = true, conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    message_index: Option<MessageIndexFilter>,
+
+    /// Only match messages detected as one of these languages (comma-separated ISO codes, e.g. en,zh)
+    #[arg(long = "lang", value_name = "LANGS", value_delimiter = ',', conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    languages: Vec<Language>,
+
+    /// Ring the bell and send a desktop notification with a summary when the scan takes longer than this (e.g. 90s, 5m)
+    #[arg(long, value_name = "DURATION")]
+    notify_after: Option<NotifyAfter>,
+
+    /// Stop starting new logs once the scan has run this long (e.g. 30s, 2m) and report how many were covered; --scan-order and --hot-first pick which go first
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with = "queries")]
+    time_budget: Option<Duration>,
+
+    /// Print `path:sample:epoch:msgidx:line` for each matching line, like `grep -H -n`, for editors and CI annotators
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "queries", "source_map"])]
+    grep_compat: bool,
+
+    /// Print each matching message as one line shaped by a format string, e.g. '{file}:{sample}:{epoch}:{role}: {content:.200}' (placeholders: file, path, sample, epoch, index, role, content, match, score, entry; {field:.N} keeps N characters)
+    #[arg(long, value_name = "FORMAT", conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "heatmap", "turns", "grep_compat", "queries", "preset", "count", "quiet", "extract", "extract_count", "dedup_content", "line_mode", "after_context", "before_context", "context", "emit_repro", "source_map"])]
+    template: Option<Template>,
+
+    /// Print each matched sample's sandbox (type and config or image) and the tools offered to the model
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries"])]
+    show_env: bool,
+
+    /// Only match samples whose sandbox, described as `type (config)` (e.g. `docker (compose.yaml)`), matches this regex
+    #[arg(long, value_name = "REGEX", conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    sandbox_filter: Option<Regex>,
+
+    /// Print the tools offered to each matched sample, with the first line of their descriptions
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries"])]
+    show_tools: bool,
+
+    /// Draw image content inline below each message, with the terminal's image protocol (detected by default)
+    #[arg(long, value_name = "PROTOCOL", num_args = 0..=1, require_equals = true, default_missing_value = "auto", conflicts_with_all = ["line_mode", "max_memory", "summary_line", "turns", "grep_compat", "queries", "dedup_content"])]
+    show_images: Option<ImageProtocol>,
+
+    /// Only match samples offered this tool, whether or not they called it (repeat to require several)
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    has_tool: Vec<String>,
+
+    /// Only match samples where the model stopped for one of these reasons, in its final output or any model call
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    stop_reason: Vec<StopReason>,
+
+    /// Only match samples that ended in an error, and show each one's error message and traceback after its matches
+    #[arg(long, conflicts_with_all = ["no_errors", "raw_regex", "anywhere", "queries"])]
+    errors_only: bool,
+
+    /// Only match samples that completed without an error
+    #[arg(long, conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    no_errors: bool,
+
+    /// Append a hash of each matched message's exact content (`#<16 hex digits>`) to its header
+    #[arg(long)]
+    show_content_hash: bool,
+
+    /// Report byte-identical matched messages once, most repeated first, with how often and where they appeared
+    #[arg(long, conflicts_with_all = ["limit", "offset", "after_context", "before_context", "context", "whole_sample", "reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "turns", "grep_compat", "queries"])]
+    dedup_content: bool,
+
+    /// Show each matched sample's model calls with their input/output token counts and resulting message
+    #[arg(long, conflicts_with_all = ["reference", "raw_regex", "anywhere", "rescore_cmd", "summary_line", "queries"])]
+    turns: bool,
+
+    /// Only match samples where some model call used at least this many tokens (input + output)
+    #[arg(long, value_name = "N", conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    min_turn_tokens: Option<u64>,
+
+    /// Show the generation settings (temperature, seed, ...) of the model call behind each matched assistant message
+    #[arg(long, conflicts_with_all = ["raw_regex", "anywhere", "summary_line", "grep_compat", "queries"])]
+    show_gen_config: bool,
+
+    /// Only match assistant messages whose model call was made at this temperature
+    #[arg(long, value_name = "T", conflicts_with_all = ["raw_regex", "anywhere", "queries", "max_results", "max_per_file", "max_per_sample"])]
+    temperature: Option<f64>,
+
+    /// Only match assistant messages whose model call was made with this seed
+    #[arg(long, value_name = "N", conflicts_with_all = ["raw_regex", "anywhere", "queries", "max_results", "max_per_file", "max_per_sample"])]
+    seed: Option<i64>,
+
+    /// Also send every match, as it's found, as a line of JSON to this Unix socket or named pipe (for a live UI or notebook)
+    #[arg(long, value_name = "SOCKET|FIFO", conflicts_with_all = ["raw_regex", "anywhere", "queries"])]
+    stream_to: Option<PathBuf>,
+
+    /// Samples larger than this uncompressed (e.g. 512M) are never parsed: their raw JSON is searched for -m in bounded windows instead
+    #[arg(long, value_name = "SIZE")]
+    max_sample_size: Option<ByteSize>,
+
+    /// Cut message content or attachments longer than this short as they're read, with a marker: `SIZE` for both, or `content=SIZE` / `attachment=SIZE`
+    #[arg(long, value_name = "[FIELD=]SIZE")]
+    max_field_bytes: Vec<FieldLimits>,
+
+    /// How to read samples and attachments that aren't valid UTF-8: `auto` also repairs escaped and mis-decoded text
+    #[arg(long, value_enum, default_value = "utf8")]
+    encoding: Encoding,
 }
 
-impl FromStr for IntFilter {
-    type Err = anyhow::Error;
+impl Args {
+    // Malformed messages are read with the unknown role, so --malformed-only is a filter on it;
+    // the shorthands and exclusions are folded into --roles too, for what shows the roles asked for
+    fn resolve_roles(&mut self) {
+        if self.malformed_only {
+            self.roles = vec![ChatMessageRole::Unknown];
+        }
+        if self.assistant_only {
+            self.roles = vec![ChatMessageRole::Assistant];
+        }
+        if self.user_only {
+            self.roles = vec![ChatMessageRole::User];
+        }
+        if !self.exclude_roles.is_empty() {
+            self.roles = RoleSet::of(&self.roles).without(&self.exclude_roles).iter().cloned().collect();
+        }
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s == "all" {
-            return Ok(IntFilter::All);
+    fn discovery(&self) -> Result<Discovery> {
+        Ok(Discovery {
+            scan_order: self.scan_order,
+            include_json: self.include_json,
+            globs: self.glob.iter().map(Glob::compile_matcher).collect(),
+            excludes: self.exclude.iter().map(Glob::compile_matcher).collect(),
+            max_depth: self.max_depth,
+            follow_symlinks: self.follow_symlinks,
+            files: self.files_from.as_deref().map(read_file_list).transpose()?,
+        })
+    }
+
+    // A -s regex that doesn't compile is an error, not a filter that lets every sample through
+    fn sample_ids(&self) -> Result<Option<Regex>> {
+        self.samples
+            .as_deref()
+            .map(|samples| Regex::new(samples).with_context(|| format!("Failed to compile sample regex '{}'", samples)))
+            .transpose()
+    }
+
+    // Every message and score filter, compiled once for the whole scan
+    fn filter_set(&self) -> Result<FilterSet> {
+        Ok(FilterSet {
+            // Excluding every role leaves --roles empty, which would otherwise mean any
+            roles: RoleSet::of(&self.roles).without(&self.exclude_roles),
+            languages: self.languages.clone(),
+            pattern: self.message_pattern()?,
+            numbers: self.numbers,
+            tool_calls: self.tool_call_filter(),
+            indices: self.message_index.clone().filter(|indices| !indices.is_all()),
+            scores: self.score_selection(),
+            stop_reasons: self.stop_reason.clone(),
+            errored: (self.errors_only || self.no_errors).then_some(self.errors_only),
+        })
+    }
+
+    fn tool_call_filter(&self) -> Option<ToolCallFilter> {
+        (self.tool_name.is_some() || self.tool_args.is_some())
+            .then(|| ToolCallFilter { name: self.tool_name.clone(), arguments: self.tool_args.clone() })
+    }
+
+    // The protocol --show-images draws with, warning when it's left to detection and the terminal isn't recognized
+    fn image_protocol(&self) -> Option<ImageProtocol> {
+        let protocol = self.show_images?;
+        let resolved = protocol.resolve();
+        if resolved.is_none() && std::io::stdout().is_terminal() {
+            eprintln!("{} can't tell which image protocol this terminal supports; pass --show-images=kitty, iterm or sixel", "warning:".yellow().bold());
         }
-        if let Some((start, end)) = s.split_once('-') {
-            return Ok(IntFilter::Range(
-                start.parse()?,
-                end.parse()?,
-            ));
+        resolved
+    }
+
+    fn header_filter(&self) -> HeaderFilter {
+        HeaderFilter { task: self.task.clone(), model: self.model.clone(), run_id: self.run_id.clone() }
+    }
+
+    fn score_selection(&self) -> ScoreSelection {
+        let correctness = match (self.correct, self.incorrect) {
+            (true, _) => Some(Correctness::Correct),
+            (_, true) => Some(Correctness::Incorrect),
+            _ => None,
+        };
+        ScoreSelection { filters: self.score_filters.clone(), correctness, explanation: self.explanation_regex.clone() }
+    }
+
+    // Messages shown around each matching message; with --line-mode, -A/-B/-C count lines instead
+    fn context_window(&self) -> ContextWindow {
+        if self.whole_sample {
+            return ContextWindow::WHOLE_SAMPLE;
         }
-        let nums = s.split(',')
-            .map(|n| n.trim().parse::<u32>())
-            .collect::<std::result::Result<HashSet<_>, _>>()?;
-        Ok(IntFilter::Some(nums))
+        if self.line_mode {
+            return ContextWindow::default();
+        }
+        self.line_context()
     }
-}
 
-impl Filter<u32> for IntFilter {
-    fn filter(&self, item: &u32) -> bool {
-        match self {
-            IntFilter::All => true,
-            IntFilter::Some(ids) => ids.contains(item),
-            IntFilter::Range(start, end) => item >= start && item <= end,
+    fn line_context(&self) -> ContextWindow {
+        ContextWindow {
+            before: self.before_context.or(self.context).unwrap_or(0),
+            after: self.after_context.or(self.context).unwrap_or(0),
         }
     }
+
+    fn pattern_syntax(&self) -> PatternSyntax {
+        PatternSyntax { ignore_case: self.ignore_case, smart_case: self.smart_case, fixed_strings: self.fixed_strings }
+    }
+
+    fn message_pattern(&self) -> Result<Option<MessagePattern>> {
+        let syntax = self.pattern_syntax();
+        let patterns = self
+            .message_regex
+            .iter()
+            .map(|pattern| (syntax.apply(pattern), RoleSet::all()))
+            .chain(self.role_regex.iter().map(|scoped| (syntax.apply(&scoped.pattern), RoleSet::of(&scoped.roles))))
+            .collect::<Vec<_>>();
+        MessagePattern::with_roles(&patterns, self.all, self.invert_match)
+    }
+
+    fn read_options(&self) -> ReadOptions {
+        ReadOptions {
+            unwrap_tool_output: self.unwrap_tool_output,
+            model_calls: self.turns || self.min_turn_tokens.is_some() || self.show_gen_config || self.temperature.is_some() || self.seed.is_some(),
+            tools: self.show_env || self.show_tools || !self.has_tool.is_empty(),
+            stop_reasons: !self.stop_reason.is_empty(),
+            max_field_bytes: self.max_field_bytes.iter().fold(FieldLimits::default(), |limits, spec| spec.or(limits)),
+            keep_attachment_refs: self.no_resolve_attachments,
+            encoding: self.encoding,
+        }
+    }
+}
+
+fn run_stats(stats_args: StatsArgs) -> Result<()> {
+    let args = stats_args.search;
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
+
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let stats = paths
+        .iter()
+        .par_bridge()
+        .filter(|_| !interrupted())
+        .map(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            let mut stats = Stats::default();
+            let mut samples = process_eval_file(path, &sample_paths, read_options, &filters, &counters);
+            filters.retain(&mut samples, &counters);
+            for sample in samples {
+                stats.add_sample(&sample);
+            }
+            stats
+        })
+        .reduce(Stats::default, Stats::merge);
+
+    stats.display();
+    if stats.samples_matched == 0 {
+        eprintln!("{} {}", "hint:".yellow().bold(), counters.hint(paths.len(), &filters));
+    }
+    if interrupted() {
+        println!("{}", "(interrupted)".red().bold());
+    }
+
+    if let Some(svg_path) = stats_args.svg {
+        std::fs::write(&svg_path, svg::render_charts(&stats.charts()))?;
+        println!("Wrote charts to {}", svg_path.display());
+    }
+
+    Ok(())
 }
 
-fn sample_id_and_epoch_from_filename(filename: String) -> Option<(String, u32)> {
-    let caps = SAMPLE_ID_EPOCH_RE.captures(&filename);
-    if let Some(caps) = caps {
-        let sample_id = caps.get(1).unwrap().as_str().to_string();
-        let epoch = caps.get(2).unwrap().as_str().parse::<u32>().unwrap();
-        Some((sample_id, epoch))
+fn run_fingerprint(args: Args) -> Result<()> {
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
+
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let fingerprints = paths
+        .iter()
+        .par_bridge()
+        .filter(|_| !interrupted())
+        .map(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            let mut fingerprints = Fingerprints::default();
+            // The structure covers the whole conversation, so the filters only pick which samples to include
+            let mut samples = read_whole_samples(path, &sample_paths, read_options);
+            filters.retain(&mut samples, &counters);
+            for sample in samples {
+                if filters.any_message(&sample, &counters) {
+                    fingerprints.add_sample(path, &sample);
+                }
+            }
+            fingerprints
+        })
+        .reduce(Fingerprints::default, Fingerprints::merge);
+
+    if fingerprints.samples() == 0 {
+        eprintln!("{} {}", "hint:".yellow().bold(), counters.hint(paths.len(), &filters));
     } else {
-        None
+        fingerprints.display();
+    }
+    if interrupted() {
+        println!("{}", "(interrupted)".red().bold());
     }
+    Ok(())
 }
 
-fn matching_samples_in_log<'a>(
-    log_path: &Path,
-    sample_regex: &'a Option<Regex>,
-    epoch_filter: &'a IntFilter,
-) -> Result<Vec<String>> {
-    let reader = std::fs::File::open(log_path)?;
-    let archive: ZipArchive<std::fs::File> = ZipArchive::new(reader)?;
+fn run_lint_transcripts(args: Args) -> Result<()> {
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
 
-    // Collect file names into owned String values
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
 
-    let file_names: Vec<String> = archive.file_names().map(|s| s.to_string()).collect();
-    let file_name_matches = move |name: &String| {
-        sample_id_and_epoch_from_filename(name.clone()).map_or(false, |(sample_id, epoch)| {
-            (sample_regex.as_ref().map_or(true, |re| re.is_match(&sample_id))) && epoch_filter.filter(&epoch)
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let lint = paths
+        .iter()
+        .par_bridge()
+        .filter(|_| !interrupted())
+        .map(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            let mut lint = TranscriptLint::default();
+            // Violations are between messages, so the filters only pick which samples to check
+            let mut samples = read_whole_samples(path, &sample_paths, read_options);
+            filters.retain(&mut samples, &counters);
+            for sample in samples {
+                if filters.any_message(&sample, &counters) {
+                    lint.add_sample(path, &sample);
+                }
+            }
+            lint
         })
-    };
+        .reduce(TranscriptLint::default, TranscriptLint::merge);
+
+    if lint.samples() == 0 {
+        eprintln!("{} {}", "hint:".yellow().bold(), counters.hint(paths.len(), &filters));
+    } else {
+        lint.display();
+    }
+    if interrupted() {
+        println!("{}", "(interrupted)".red().bold());
+    }
+    Ok(())
+}
+
+fn run_answers(answers_args: AnswersArgs) -> Result<()> {
+    let args = answers_args.search;
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
+
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let table = paths
+        .iter()
+        .par_bridge()
+        .filter(|_| !interrupted())
+        .map(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            let mut table = AnswerTable::default();
+            // The final answer needs the whole conversation, so the filters only pick the samples
+            let mut samples = read_whole_samples(path, &sample_paths, read_options);
+            filters.retain(&mut samples, &counters);
+            for sample in samples.iter().filter(|sample| filters.any_message(sample, &counters)) {
+                table.add_sample(path, sample);
+            }
+            table
+        })
+        .reduce(AnswerTable::default, AnswerTable::merge);
+
+    if table.is_empty() {
+        eprintln!("{} {}", "hint:".yellow().bold(), counters.hint(paths.len(), &filters));
+    }
+    let samples = table.len();
+    match answers_args.csv {
+        true => table.write_csv(),
+        false => table.display(),
+    }
+    eprintln!("{} samples{}", samples, if interrupted() { " (interrupted)".red().bold().to_string() } else { String::new() });
+    Ok(())
+}
+
+fn run_provenance(provenance_args: ProvenanceArgs) -> Result<()> {
+    let args = provenance_args.search;
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
+    let needle = RegexBuilder::new(&regex::escape(&provenance_args.string)).case_insensitive(args.ignore_case).build()?;
+
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let provenance = paths
+        .iter()
+        .par_bridge()
+        .filter(|_| !interrupted())
+        .map(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            let mut provenance = Provenance::new(needle.clone());
+            // First occurrences need every message in order, so the filters only pick the samples
+            let mut samples = read_whole_samples(path, &sample_paths, read_options);
+            filters.retain(&mut samples, &counters);
+            for sample in samples.iter().filter(|sample| filters.any_message(sample, &counters)) {
+                provenance.add_sample(path, sample);
+            }
+            provenance
+        })
+        .reduce(|| Provenance::new(needle.clone()), Provenance::merge);
+
+    if provenance.samples() == 0 {
+        eprintln!("{} {}", "hint:".yellow().bold(), counters.hint(paths.len(), &filters));
+    }
+    provenance.display();
+    if interrupted() {
+        println!("{}", "(interrupted)".red().bold());
+    }
+    Ok(())
+}
+
+fn run_repl(repl_args: ReplArgs) -> Result<()> {
+    let args = repl_args.search;
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    // Each query brings its own pattern
+    let filters = FilterSet { pattern: None, ..args.filter_set()? };
 
-    // Create an iterator of owned Strings
-    Ok(file_names
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let logs = paths
         .into_iter()
-        .filter(move |name| file_name_matches(name))
-        .collect())
+        .filter_map(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(&path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display())?;
+            Some((path, sample_paths))
+        })
+        .collect();
+    let session = Session::load(logs, read_options, filters, args.pattern_syntax(), repl_args.cache_messages.0);
+    // What couldn't be read while loading is reported before the first prompt
+    skipped::report();
+    session.run(&args.theme)
 }
 
-fn read_sample_filtered<F>(log_path: &Path, sample_filename: &str, message_filter: F) -> Result<EvalSample>
-where
-    F: Fn(&ChatMessage) -> bool,
-{
-    let reader = std::fs::File::open(log_path)?;
-    let mut archive: ZipArchive<std::fs::File> = ZipArchive::new(reader)?;
+fn run_answer_variance(args: Args) -> Result<()> {
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
+
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let variance = paths
+        .iter()
+        .par_bridge()
+        .filter(|_| !interrupted())
+        .map(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            let mut variance = AnswerVariance::default();
+            // Final answers need the whole conversation, so the filters only pick which samples to include,
+            // and a sample is included when any of its epochs has a match
+            let mut samples = read_whole_samples(path, &sample_paths, read_options);
+            filters.retain(&mut samples, &counters);
+            for sample in samples {
+                let matched = filters.any_message(&sample, &counters);
+                variance.add_sample(path, &sample, matched);
+            }
+            variance
+        })
+        .reduce(AnswerVariance::default, AnswerVariance::merge);
 
-    let file = archive.by_name(sample_filename)?;
-    let sample = deserialize_sample_filtered(file, message_filter)?;
-    Ok(sample)
+    if variance.samples() == 0 {
+        let hint = match variance.matched() {
+            0 => counters.hint(paths.len(), &filters),
+            matched => format!("none of the {} matching samples was run for more than one epoch", matched),
+        };
+        eprintln!("{} {}", "hint:".yellow().bold(), hint);
+    } else {
+        variance.display();
+    }
+    if interrupted() {
+        println!("{}", "(interrupted)".red().bold());
+    }
+    Ok(())
 }
 
-fn process_eval_file(log_path: &Path, sample_paths: &Vec<String>, roles: &Option<Vec<ChatMessageRole>>, pattern: Option<&Regex>) -> Vec<EvalSample> {
-    let message_filter = move |message: &ChatMessage| {
-        if let Some(roles) = roles {
-            if !roles.contains(&message.role){ return false }
-        }
-        if let Some(pattern) = pattern {
-            if !pattern.is_match(&message.content) { return false }
-        }
-        true
-    };
+fn run_list_files(args: Args) -> Result<()> {
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let counters = ScanCounters::default();
+    for path in search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters) {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+fn run_list(args: Args) -> Result<()> {
+    let kind = args.list.expect("run_list needs --list");
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let logs = paths
+        .par_iter()
+        .filter(|_| !interrupted())
+        .map(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            LogListing::read(path, &sample_paths, kind, read_options)
+        })
+        .collect::<Vec<_>>();
+    listing::display(kind, &logs);
+    if interrupted() {
+        println!("{}", "(interrupted)".red().bold());
+    }
+    Ok(())
+}
+
+fn run_samples_without_match(args: Args) -> Result<()> {
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
+
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let searched = AtomicUsize::new(0);
+    let mut unmatched = paths
+        .par_iter()
+        .filter(|_| !interrupted())
+        .flat_map_iter(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            let mut samples = process_eval_file(path, &sample_paths, read_options, &filters, &counters);
+            filters.retain(&mut samples, &counters);
+            searched.fetch_add(samples.len(), Ordering::Relaxed);
+            samples.into_iter().filter(|sample| sample.messages.iter().all(Option::is_none)).map(move |sample| (path, sample.id, sample.epoch))
+        })
+        .collect::<Vec<_>>();
+    unmatched.sort();
+
+    for (path, sample_id, epoch) in &unmatched {
+        println!("{}:{}:{}", path.display(), sample_id, epoch);
+    }
+    let searched = searched.into_inner();
+    let interrupted_marker = if interrupted() { " (interrupted)".red().bold().to_string() } else { String::new() };
+    eprintln!("{} of {} samples had no matching message, in {} files{}", unmatched.len(), searched, paths.len(), interrupted_marker);
+    Ok(())
+}
+
+fn run_events(args: Args) -> Result<()> {
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let message_pattern = args.message_pattern()?;
+
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+    let query = EventQuery { types: &args.event_type, pattern: message_pattern.as_ref() };
+    let highlight = message_pattern.as_ref().filter(|pattern| pattern.is_single_regex()).map(|pattern| pattern.regex());
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let (samples, events) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    let results = paths.iter().enumerate().par_bridge().filter(|_| !interrupted()).map(|(index, path)| {
+        let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+        let found = skipped::or_skip(search_events(path, &sample_paths, &query), path.display()).unwrap_or_default();
+        (index, path, found)
+    });
+    emit_results(
+        results,
+        !args.unordered,
+        args.max_memory,
+        |a, b| (&a.sample_id, a.epoch).cmp(&(&b.sample_id, b.epoch)),
+        |path, found| {
+            for sample in &found {
+                samples.fetch_add(1, Ordering::Relaxed);
+                events.fetch_add(sample.events.len(), Ordering::Relaxed);
+                display_sample_events(path, sample, highlight, &args.theme);
+            }
+            Ok(())
+        },
+    )?;
+
+    let interrupted_marker = if interrupted() { " (interrupted)".red().bold().to_string() } else { String::new() };
+    eprintln!("{} events matched in {} samples, in {} files{}", events.into_inner(), samples.into_inner(), paths.len(), interrupted_marker);
+    Ok(())
+}
+
+fn run_tui(args: Args) -> Result<()> {
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
 
-    return sample_paths.par_iter()
-        .map(|file| {
-            read_sample_filtered(&log_path, file, &message_filter).expect(&format!("Failed to read sample {}", file))
+    let sample_ids = args.sample_ids()?;
+    let message_regex = filters.pattern.as_ref().map(|pattern| pattern.regex().clone());
+    let counters = ScanCounters::default();
+    // Roles can be toggled while browsing, so matches are found for every role and --role only sets the ones shown first
+    let filters = FilterSet { roles: RoleSet::all(), ..filters };
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let pb = ProgressBar::new(paths.len() as u64);
+    if glyphs::ascii() {
+        pb.set_style(ProgressStyle::default_bar().progress_chars("#>-"));
+    }
+    let mut transcripts = paths
+        .par_iter()
+        .filter(|_| !interrupted())
+        .flat_map_iter(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            // The whole sample is kept, to open it from any of its matches
+            let mut samples = read_whole_samples(path, &sample_paths, read_options);
+            filters.retain(&mut samples, &counters);
+            pb.inc(1);
+            samples.into_iter().filter_map(|sample| {
+                let hits = filters.matched(&sample.messages, &counters).iter().positions(Option::is_some).collect::<Vec<_>>();
+                (!hits.is_empty()).then(|| tui::Transcript { path: path.clone(), sample, hits })
+            })
+        })
+        .collect::<Vec<_>>();
+    pb.finish_and_clear();
+    if interrupted() {
+        return Ok(());
+    }
+    if transcripts.is_empty() {
+        eprintln!("{} {}", "hint:".yellow().bold(), counters.hint(paths.len(), &filters));
+        return Ok(());
+    }
+    transcripts.sort_by(|a, b| (&a.path, &a.sample.id, a.sample.epoch).cmp(&(&b.path, &b.sample.id, b.sample.epoch)));
+    tui::browse(transcripts, message_regex.as_ref(), &args.roles, &args.theme)
+}
+
+fn run_export(export_args: ExportArgs) -> Result<()> {
+    let args = export_args.search;
+    if args.aggregate_only {
+        bail!("--aggregate-only can't be used with export, which writes transcripts");
+    }
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
+    let mut metadata = export::ScanMetadata::new(export::ScanQuery {
+        path: args.path.clone(),
+        message_regex: args.message_regex.clone(),
+        all: args.all,
+        invert_match: args.invert_match,
+        samples: args.samples.clone(),
+        epochs: args.epochs.to_string(),
+        roles: args.roles.iter().map(ToString::to_string).collect(),
+    });
+
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let exported = AtomicUsize::new(0);
+    paths
+        .iter()
+        .par_bridge()
+        .filter(|_| !interrupted())
+        .try_for_each(|path| -> Result<()> {
+            let Some(header) = skipped::or_skip(read_json_entry::<EvalLogHeader>(path, "header.json"), path.display()) else {
+                return Ok(());
+            };
+            let task = header.map_or_else(|| path.file_stem().unwrap().to_string_lossy().to_string(), |h| h.eval.task);
+            let Some(sample_paths) = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()) else {
+                return Ok(());
+            };
+
+            // Transcripts include every message, marking the ones that matched
+            let mut samples = read_whole_samples(path, &sample_paths, read_options);
+            filters.retain(&mut samples, &counters);
+            for sample in samples {
+                let matched = filters.matched(&sample.messages, &counters);
+                let messages = sample.messages.iter().zip(matched).filter_map(|(m, matched)| Some((m.as_ref()?, matched.is_some()))).collect::<Vec<_>>();
+                if !messages.iter().any(|(_, matched)| *matched) {
+                    continue;
+                }
+                match export_args.format {
+                    ExportFormat::Files => {
+                        let transcript = export::render_transcript(path, &task, &sample, &messages, export_args.style, &metadata);
+                        export::write_transcript(&export::transcript_path(&export_args.dir, &task, &sample, export_args.style), &transcript)?;
+                    }
+                }
+                exported.fetch_add(1, Ordering::Relaxed);
+            }
+            Ok(())
+        })?;
+
+    let exported = exported.into_inner();
+    metadata.files_scanned = paths.len();
+    metadata.samples_exported = exported;
+    export::write_metadata(&export_args.dir, &metadata)?;
+    eprintln!("Exported {} samples to {}", exported, export_args.dir.display());
+    if exported == 0 && !interrupted() {
+        eprintln!("{} {}", "hint:".yellow().bold(), counters.hint(paths.len(), &filters));
+    }
+    Ok(())
+}
+
+fn run_bundle(bundle_args: BundleArgs) -> Result<()> {
+    let args = bundle_args.search;
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
+    let mut metadata = export::ScanMetadata::new(export::ScanQuery {
+        path: args.path.clone(),
+        message_regex: args.message_regex.clone(),
+        all: args.all,
+        invert_match: args.invert_match,
+        samples: args.samples.clone(),
+        epochs: args.epochs.to_string(),
+        roles: args.roles.iter().map(ToString::to_string).collect(),
+    });
+
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+
+    // Logs are searched in parallel, then their matches copied into the bundle one log at a time
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    let mut matched = paths
+        .par_iter()
+        .filter(|_| !interrupted())
+        .filter_map(|path| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display())?;
+            let mut samples = process_eval_file(path, &sample_paths, read_options, &filters, &counters);
+            filters.retain(&mut samples, &counters);
+            let matched = samples
+                .iter()
+                .filter(|sample| sample.messages.iter().any(Option::is_some))
+                .map(|sample| (sample.id.as_str(), sample.epoch))
+                .collect::<HashSet<_>>();
+            let entries = sample_paths.into_iter().filter(|entry| matched.contains(&(entry.sample_id.as_str(), entry.epoch as i64))).collect::<Vec<_>>();
+            Some((path, entries))
         })
-        .collect::<Vec<EvalSample>>();
-        // .collect::<HashMap<String, Vec<Option<ChatMessage>>>>();
-
-    // sample_messages
-}
-
-fn display_message(source: (&Path, &str, i64), message: &ChatMessage, highlight_regex: Option<&Regex>) {
-    let (log_file, sample_id, epoch) = source;
-    // let terminal_width = term_size::dimensions().map(|(w, _)| w).unwrap_or(80);
-    
-    // Determine role-based color
-    let role_color = match message.role {
-        ChatMessageRole::System => Color::Magenta,
-        ChatMessageRole::User => Color::Blue,
-        ChatMessageRole::Assistant => Color::Green,
-        ChatMessageRole::Tool => Color::Yellow,
+        .filter(|(_, entries)| !entries.is_empty())
+        .collect::<Vec<_>>();
+    if interrupted() {
+        return Ok(());
+    }
+    matched.sort_by(|a, b| a.0.cmp(b.0));
+
+    let bundled = matched.iter().map(|(_, entries)| entries.len()).sum::<usize>();
+    if bundled == 0 {
+        eprintln!("{} {}", "hint:".yellow().bold(), counters.hint(paths.len(), &filters));
+        return Ok(());
+    }
+    let mut bundle = Bundle::create(&bundle_args.out)?;
+    for (path, entries) in &matched {
+        bundle.add_log(path, &bundle::log_dir(&args.path, path), entries)?;
+    }
+    metadata.files_scanned = paths.len();
+    metadata.samples_exported = bundled;
+    bundle.finish(&metadata)?;
+    eprintln!("Bundled {} samples from {} logs into {}", bundled, matched.len(), bundle_args.out.display());
+    Ok(())
+}
+
+// Run a batch of queries in one pass, reading each sample that any of them selects once
+fn run_queries(args: Args, queries_path: Option<&Path>) -> Result<()> {
+    let read_options = args.read_options();
+    let score_selection = args.score_selection();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let mut queries = queries_path.map(queries::load_queries).transpose()?.unwrap_or_default();
+    for preset in &args.preset {
+        queries.extend(preset.queries()?);
+    }
+    // Most severe first, in the order they were given otherwise
+    queries.sort_by_key(|query| std::cmp::Reverse(query.severity));
+    let sample_ids = args.sample_ids()?;
+    let counters = ScanCounters::default();
+    let started = Instant::now();
+
+    let paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    paths.iter().par_bridge().filter(|_| !interrupted()).for_each(|path| {
+        let entries = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+        let selected = entries.iter().filter(|sample| queries.iter().any(|q| q.selects(sample))).cloned().collect::<Vec<_>>();
+        let mut samples = read_whole_samples(path, &selected, read_options);
+        score_selection.retain(&mut samples, &counters);
+        for query in &queries {
+            query.scan(path, &entries, &samples);
+        }
+    });
+
+    for query in queries.iter().filter(|_| !args.aggregate_only) {
+        query.display(&args.theme);
+    }
+    queries::display_summary(&queries, paths.len());
+    let matches = queries.iter().map(queries::Query::match_count).sum::<usize>();
+    notify_if_slow(started, args.notify_after, &format!("{} matches for {} queries in {} files", matches, queries.len(), paths.len()));
+    Ok(())
+}
+
+fn run_compare(args: CompareArgs) -> Result<()> {
+    let sample_ids = args.samples.as_deref().map(Regex::new).transpose()?;
+    let counters = ScanCounters::default();
+    // A log that can't be read is reported, and compared as having no samples
+    let read_all = |path: &Path| -> Vec<EvalSample> {
+        let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &args.epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+        read_whole_samples(path, &sample_paths, ReadOptions::default())
     };
 
-    // Format role
-    let role = format!("[{}]", message.role.to_string().to_lowercase())
-        .color(role_color)
-        .bold();
-    
-    // Create header with source info and role
-    let header = format!("{} sample {} epoch {} | {}", 
-        log_file.file_name().unwrap().to_string_lossy().cyan(),
-        sample_id.yellow(),
-        epoch.to_string().green(),
-        role
-    );
-    
-    // Process content with highlighting
-    let mut content = message.content.clone();
-    if let Some(regex) = highlight_regex {
-        content = regex.replace_all(&content, |caps: &regex::Captures| {
-            format!("{}", caps[0].red().bold())
-        }).to_string();
+    let (left, right) = rayon::join(|| read_all(&args.left), || read_all(&args.right));
+    compare::display_comparison(&args.left, left, &args.right, right);
+    Ok(())
+}
+
+fn main() -> Result<ExitCode> {
+    let mut cli = Cli::parse();
+    if cli.wizard {
+        match wizard::run()? {
+            Some(args) => cli = Cli::parse_from(args),
+            None => return Ok(ExitCode::SUCCESS),
+        }
+    }
+    interrupt::install_handler()?;
+    glyphs::init(cli.ascii);
+    skipped::set_strict(cli.strict);
+    if !cli.no_cache {
+        log_index::enable(cli.rebuild_index);
+    }
+    match cli.command.as_mut() {
+        Some(Command::Stats(stats_args)) => stats_args.search.resolve_roles(),
+        Some(Command::Export(export_args)) => export_args.search.resolve_roles(),
+        Some(Command::Fingerprint(args)) => args.resolve_roles(),
+        Some(Command::Bundle(bundle_args)) => bundle_args.search.resolve_roles(),
+        Some(Command::LintTranscripts(args)) => args.resolve_roles(),
+        Some(Command::Answers(answers_args)) => answers_args.search.resolve_roles(),
+        Some(Command::Provenance(provenance_args)) => provenance_args.search.resolve_roles(),
+        Some(Command::Repl(repl_args)) => repl_args.search.resolve_roles(),
+        Some(_) => {}
+        None => cli.search.iter_mut().for_each(Args::resolve_roles),
     }
 
-    // Print header
-    println!("\n{}", header);
-    
-    println!("{}", content);
+    // A search can end in a failure status without an error, e.g. --quiet finding nothing,
+    // which is only returned once the cleanup below has run
+    let mut status = ExitCode::SUCCESS;
+    let result = match cli.command {
+        Some(Command::Stats(stats_args)) => run_stats(*stats_args),
+        Some(Command::Scores(scores_args)) => scores::run(&eval_files(&scores_args.path, &Discovery::default())),
+        Some(Command::Compare(compare_args)) => run_compare(compare_args),
+        Some(Command::Export(export_args)) => run_export(*export_args),
+        Some(Command::Fingerprint(args)) => run_fingerprint(*args),
+        Some(Command::Bundle(bundle_args)) => run_bundle(*bundle_args),
+        Some(Command::LintTranscripts(args)) => run_lint_transcripts(*args),
+        Some(Command::Answers(answers_args)) => run_answers(*answers_args),
+        Some(Command::Provenance(provenance_args)) => run_provenance(*provenance_args),
+        Some(Command::Repl(repl_args)) => run_repl(*repl_args),
+        Some(Command::Fixtures(fixtures_args)) => run_fixtures(fixtures_args),
+        Some(Command::Slim(slim_args)) => slim::slim(
+            &slim_args.log,
+            &slim_args.output,
+            slim_args.sample_layout.as_ref(),
+            &slim::SlimOptions { drop_roles: slim_args.drop_roles, max_chars: slim_args.max_chars },
+        ),
+        Some(Command::History(history_args)) => history::run(
+            &history_args.sample_id,
+            &eval_files(&history_args.path, &Discovery::default()),
+            &history_args.epochs,
+            history_args.sample_layout.as_ref(),
+        ),
+        None => {
+            let args = cli.search.expect("search arguments are required without a subcommand");
+            match args.queries.clone() {
+                Some(queries) => run_queries(args, Some(&queries)),
+                None if !args.preset.is_empty() => run_queries(args, None),
+                None if args.list_files => run_list_files(args),
+                None if args.list.is_some() => run_list(args),
+                None if args.answer_variance => run_answer_variance(args),
+                None if args.tui => run_tui(args),
+                None if args.samples_without_match => run_samples_without_match(args),
+                None if args.events => run_events(args),
+                None => run_search(args, cli.format).map(|search_status| status = search_status),
+            }
+        }
+    };
 
-    println!(); // Add spacing between messages
+    skipped::report();
+    if cli.resource_report {
+        resources::report();
+    }
+    if let Err(e) = log_index::save() {
+        eprintln!("{} failed to update the log index: {:#}", "warning:".yellow().bold(), e);
+    }
+    interrupt::finish();
+    if interrupted() {
+        std::process::exit(130);
+    }
+    result.map(|()| status)
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn run_fixtures(args: FixturesArgs) -> Result<()> {
+    let options = FixtureOptions {
+        task: args.task,
+        model: args.model,
+        samples: args.samples,
+        epochs: args.epochs,
+        turns: args.turns,
+        accuracy: args.accuracy,
+        errors: args.errors,
+        seed: args.seed,
+    };
+    fixtures::write(&args.output, &options)?;
+    eprintln!("Wrote {}: {} samples × {} epochs", args.output.display(), options.samples, options.epochs);
+    Ok(())
+}
 
+fn run_search(args: Args, format: OutputFormat) -> Result<ExitCode> {
+    let read_options = args.read_options();
+    let header_filter = args.header_filter();
+    let discovery = args.discovery()?;
+    let filters = args.filter_set()?;
+    if args.rank && filters.pattern.is_none() {
+        bail!("--rank needs -m or --role-regex patterns to rank samples by");
+    }
+    // Transcoding changes byte lengths, so offsets into the converted text miss in the entry
+    if args.source_map && args.encoding != Encoding::Utf8 {
+        bail!("--source-map gives byte ranges in the raw entry, which transcoding with --encoding would shift; leave --encoding at utf8");
+    }
+    let pattern_syntax = args.pattern_syntax();
+    let context = args.context_window();
+    let line_context = args.line_context();
+    let images = args.image_protocol();
     // Parse filters
-    let sample_ids = args.samples.map(|s| Regex::new(&s).ok()).flatten();
+    let sample_ids = args.sample_ids()?;
     let epochs = args.epochs;
-    let roles = (!args.roles.is_empty()).then_some(args.roles);
+    let gen_config_filter = GenConfigFilter { temperature: args.temperature, seed: args.seed };
 
     // Compile regex pattern
-    let message_regex = args.message_regex.map(|s| Regex::new(&s).expect("Failed to compile message regex"));
+    let message_regex = filters.pattern.as_ref().map(|pattern| pattern.regex().clone());
+    let counters = ScanCounters::default();
+
+    // Load the reference transcript, if comparing against one
+    let reference = args.reference.as_deref().map(load_reference).transpose()?;
+    let labels = args.labels.as_deref().map(LabelsFile::create).transpose()?;
+    let id_aliases = args.alias_ids.as_deref().map(IdAliases::load).transpose()?;
+    let eval_export = args.export_eval.is_some().then(EvalExport::default);
+    // A named pipe blocks here until its reader opens it
+    let match_stream = args.stream_to.as_deref().map(MatchStream::open).transpose()?;
+    // Divergence checks and rescoring need the full transcript, so keep every message and filter afterwards
+    // as does judging how a transcript ended
+    // as does showing the messages around each match
+    let whole_samples = reference.is_some() || args.rescore_cmd.is_some() || args.truncated_only || !context.is_empty();
 
     // Collect all .eval files
-    let paths: Vec<PathBuf> = if args.path.is_file() {
-        vec![args.path]
-    } else {
-        WalkDir::new(&args.path)
-            .into_iter()
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().extension().map_or(false, |ext| ext == "eval"))
-            .map(|e| e.path().to_path_buf())
-            .collect()
+    let started = Instant::now();
+    let mut paths = search_files(&args.path, &discovery, &args.config_filters, &header_filter, &counters);
+    // Logs that matched earlier searches for similar patterns go first, and this search's matches are remembered
+    let hot_pattern = message_regex.as_ref().map(Regex::as_str).or(args.raw_regex.as_deref()).or(args.anywhere.as_deref()).unwrap_or_default().to_string();
+    let hit_history = args.hot_first.then(HitHistory::load);
+    if let Some(hit_history) = &hit_history {
+        hit_history.sort(&mut paths, &hot_pattern);
+    }
+    let rollup = args.rollup.then(|| Rollup::new(&args.path, &paths));
+    let matched_files = Mutex::new(HashSet::new());
+    let hit = |path: &Path| {
+        if hit_history.is_some() {
+            matched_files.lock().unwrap().insert(path.to_path_buf());
+        }
     };
 
     // Setup progress bar
     let pb = ProgressBar::new(paths.len() as u64);
+    if args.quiet {
+        pb.set_draw_target(ProgressDrawTarget::hidden());
+    }
     pb.set_style(
         ProgressStyle::default_bar()
             .template(
-                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}",
             )
             .unwrap()
             .progress_chars("#>-"),
     );
+    if glyphs::ascii() {
+        pb.set_style(pb.style().tick_chars("|/-\\ "));
+    }
+
+    let page = Page::new(args.offset, args.limit, args.aggregate_only || args.quiet);
+    let quota = MatchQuota::new(args.max_results, args.max_per_file, args.max_per_sample);
+    let content_groups = ContentGroups::default();
+    let match_stats = args.stats.then(MatchStats::default);
+    let capture_counts = CaptureCounts::default();
+
+    // Raw JSON is searched with one regex, which can't require several patterns at once
+    let stream_regex = filters.pattern.as_ref().filter(|pattern| pattern.is_single_regex());
+    let stream_regex = stream_regex.map(|pattern| regex::bytes::Regex::new(pattern.regex().as_str())).transpose()?;
+    // Everything printed for a matching sample names it and its log by their aliases
+    let alias_sample = |id: &mut String, entry: &mut String| {
+        if let Some(id_aliases) = &id_aliases {
+            let alias = id_aliases.sample(id);
+            // Entries are usually named for the sample, as in samples/{id}_epoch_{epoch}.json
+            let name_start = entry.rfind('/').map_or(0, |slash| slash + 1);
+            let at = entry[name_start..].starts_with(id.as_str()).then_some(name_start).or_else(|| entry.find(id.as_str()));
+            if let Some(at) = at {
+                entry.replace_range(at..at + id.len(), &alias);
+            }
+            *id = alias;
+        }
+    };
+    // Captures are printed as they're found with --extract, and tallied with --extract-count
+    let display_captures = |captures: &[&str]| match args.extract_count {
+        true => capture_counts.add(captures),
+        false => captures.iter().for_each(|capture| println!("{}", extract::one_line(capture))),
+    };
+    // Each ranked sample's relevance, by log, id and epoch, to show with its matches
+    let relevance_scores = Mutex::new(HashMap::<(PathBuf, String, i64), f64>::new());
+    let display_samples = |log_path: &Path, samples: Vec<EvalSample>| {
+        let reduced_scores = ReducedScores::load(log_path);
+        let log_sandbox = (args.show_env || args.sandbox_filter.is_some()).then(|| SandboxSpec::of_log(log_path)).flatten();
+        let log_tools = if read_options.tools { environment::log_tools(log_path) } else { Vec::new() };
+        let aliased = id_aliases.as_ref().map(|id_aliases| id_aliases.log(log_path));
+        let path = aliased.as_deref().unwrap_or(log_path);
+        let mut log_count = 0;
+        let repro = args.emit_repro.then(|| ReproCommand::of_log(log_path)).flatten();
+        // Epochs of a sample are re-run by the same command, printed once
+        let mut reproduced = HashSet::new();
+        for mut sample in samples {
+            let sandbox = SandboxSpec::of_sample(&sample, log_sandbox.as_ref());
+            if let Some(sandbox_filter) = &args.sandbox_filter {
+                if !sandbox.as_ref().is_some_and(|sandbox| sandbox_filter.is_match(&sandbox.describe())) {
+                    ScanCounters::add(&counters.samples_outside_sandbox, 1);
+                    continue;
+                }
+            }
+            let tools = environment::sample_tools(&sample, &log_tools).to_vec();
+            if !args.has_tool.iter().all(|name| tools.iter().any(|tool| &tool.name == name)) {
+                ScanCounters::add(&counters.samples_without_tool, 1);
+                continue;
+            }
+            if let Some(streamed) = &sample.streamed {
+                if streamed.count > 0 {
+                    ScanCounters::add(&counters.samples_matched, 1);
+                    hit(log_path);
+                    if let Some(eval_export) = &eval_export {
+                        eval_export.add(log_path, &sample.id, sample.epoch);
+                    }
+                    alias_sample(&mut sample.id, &mut sample.entry);
+                    let source = (path, sample.id.as_str(), sample.epoch);
+                    if let Some(match_stats) = &match_stats {
+                        // Raw JSON has no roles, and counts as one match
+                        match_stats.add(log_path, source, ["raw JSON".to_string()]);
+                    }
+                    if let Some(rollup) = &rollup {
+                        rollup.add(log_path, 1);
+                    }
+                    if let Some(match_stream) = &match_stream {
+                        match_stream.send(&jsonl::streamed_json(source, streamed));
+                    }
+                    if args.count {
+                        // Streamed samples count as one match, as they do in the totals
+                        page.admit();
+                        log_count += streamed.count;
+                        println!("{}:{}:{}:{}", path.display(), sample.id, sample.epoch, streamed.count);
+                        continue;
+                    }
+                    // Streamed samples only kept snippets around their first matches to capture from
+                    if args.extract || args.extract_count {
+                        page.admit();
+                        let regex = message_regex.as_ref().unwrap();
+                        let captures = streamed.snippets.iter().flat_map(|snippet| extract::captures(regex, snippet)).collect::<Vec<_>>();
+                        display_captures(&captures);
+                        continue;
+                    }
+                    if page.admit() {
+                        match (&args.template, args.grep_compat, format) {
+                            (Some(template), _, _) => template.render_streamed(source, &sample.entry, streamed, message_regex.as_ref()).iter().for_each(|line| println!("{}", line)),
+                            (None, true, _) => grep_compat::display_grep_snippets(source, streamed),
+                            (None, false, OutputFormat::Jsonl) => jsonl::display_streamed_json(source, streamed),
+                            (None, false, OutputFormat::Text) => display_streamed(path, &sample.id, sample.epoch, streamed, message_regex.as_ref(), &args.theme),
+                        }
+                    }
+                }
+                continue;
+            }
+            // Whole samples keep every message, so the message filter is applied here instead
+            let mut matched = match whole_samples {
+                true => filters.matched(&sample.messages, &counters),
+                false => sample.messages.iter().map(Option::as_ref).collect::<Vec<_>>(),
+            };
+            let calls = match args.show_gen_config || !gen_config_filter.is_empty() {
+                true => gen_config::producing_calls(&sample.messages, &sample.model_calls),
+                false => Vec::new(),
+            };
+            gen_config_filter.retain(&mut matched, &calls);
+            let truncation = args.truncated_only.then(|| truncation_reasons(&sample));
+            match &truncation {
+                Some(reasons) if reasons.is_empty() => continue,
+                Some(_) => ScanCounters::add(&counters.samples_truncated, 1),
+                None => {}
+            }
+            if matched.iter().all(Option::is_none) {
+                continue;
+            }
+            if args.min_turn_tokens.is_some_and(|min| !turns::has_turn_over(&sample, min)) {
+                continue;
+            }
+            ScanCounters::add(&counters.samples_matched, 1);
+            hit(log_path);
+            if let Some(eval_export) = &eval_export {
+                eval_export.add(log_path, &sample.id, sample.epoch);
+            }
+            let score = reduced_scores.label(&sample);
+            let relevance = args.rank.then(|| relevance_scores.lock().unwrap().get(&(log_path.to_path_buf(), sample.id.clone(), sample.epoch)).copied()).flatten();
+            // The command needs the sample's own id, not its alias
+            let repro_id = args.emit_repro.then(|| sample.id.clone());
+            alias_sample(&mut sample.id, &mut sample.entry);
+            if let Some(match_stats) = &match_stats {
+                match_stats.add(log_path, (path, &sample.id, sample.epoch), matched.iter().flatten().map(|m| m.role.to_string()));
+            }
+            if let Some(rollup) = &rollup {
+                rollup.add(log_path, matched.iter().flatten().count());
+            }
+            // Streamed matches aren't paginated, and are sent whatever is printed for them
+            if let Some(match_stream) = &match_stream {
+                for (index, message) in matched.iter().enumerate().filter_map(|(i, m)| Some((i, (*m)?))) {
+                    let content_hash = args.show_content_hash.then(|| dedup::content_hash(message));
+                    let at = MessageSource { index, entry: &sample.entry, span: sample.message_spans[index], score: score.as_deref(), is_context: false, content_hash };
+                    match_stream.send(&jsonl::message_json((path, &sample.id, sample.epoch), message, at, message_regex.as_ref()));
+                }
+            }
+            if args.count {
+                let count = matched.iter().flatten().filter(|_| page.admit()).count();
+                log_count += count;
+                println!("{}:{}:{}:{}", path.display(), sample.id, sample.epoch, count);
+                continue;
+            }
+            if args.extract || args.extract_count {
+                let regex = message_regex.as_ref().unwrap();
+                let captures = matched.iter().flatten().filter(|_| page.admit()).flat_map(|message| extract::captures(regex, &message.content)).collect::<Vec<_>>();
+                display_captures(&captures);
+                continue;
+            }
+            let messages = sample.messages.iter().flatten().collect::<Vec<_>>();
+            // Modes below print one entry per sample, which pages count, but the totals count its matches
+            let match_count = matched.iter().flatten().count();
 
-    // Process files in parallel
+            if let Some(reference) = &reference {
+                if page.admit_entry(match_count) {
+                    let divergence = first_divergence(reference, &messages);
+                    display_divergence((path, &sample.id, sample.epoch), divergence.as_ref());
+                }
+                continue;
+            }
+            if let Some(command) = &args.rescore_cmd {
+                if page.admit_entry(match_count) {
+                    let chat_json = ChatJson {
+                        log: path.display().to_string(),
+                        id: &sample.id,
+                        epoch: sample.epoch,
+                        messages: &messages,
+                        scores: &sample.scores,
+                    };
+                    let verdict = rescore(command, &chat_json);
+                    if let (Some(labels), Ok(verdict)) = (&labels, &verdict) {
+                        labels.record(&chat_json, verdict).with_context(|| format!("Failed to write labels file {}", args.labels.as_ref().unwrap().display()))?;
+                    }
+                    display_verdict(path, &chat_json, &verdict, &args.theme);
+                }
+                continue;
+            }
+            if args.turns {
+                if page.admit_entry(match_count) {
+                    turns::display_turns(path, &sample, args.min_turn_tokens, &args.theme);
+                }
+                continue;
+            }
+            if let Some(repro_id) = repro_id {
+                if !reproduced.insert(repro_id.clone()) {
+                    // Another epoch of the sample already printed the command
+                    page.count(match_count);
+                } else if page.admit_entry(match_count) {
+                    println!("{}", format!("# {}:{}:{}", path.display(), sample.id, sample.epoch).dimmed());
+                    match &repro {
+                        Some(repro) => println!("{}", repro.for_sample(&repro_id)),
+                        None => println!("{}", "# no header.json to read the task from".dimmed()),
+                    }
+                }
+                continue;
+            }
+            if args.summary_line {
+                if page.admit_entry(match_count) {
+                    let matched = matched.into_iter().flatten().collect::<Vec<_>>();
+                    display_summary_line(path, &sample, &matched, score.as_deref(), message_regex.as_ref(), &args.theme);
+                }
+                continue;
+            }
+            if args.heatmap {
+                if page.admit_entry(match_count) {
+                    display_heatmap_line(path, &sample, &matched, score.as_deref(), &args.theme);
+                }
+                continue;
+            }
+            if let Some(template) = &args.template {
+                for (index, message) in matched.into_iter().enumerate().filter_map(|(i, m)| Some((i, m?))) {
+                    if page.admit() {
+                        let values = TemplateValues {
+                            path,
+                            sample_id: &sample.id,
+                            epoch: sample.epoch,
+                            index: Some(index),
+                            message: Some(message),
+                            content: &message.content,
+                            score: score.as_deref(),
+                            entry: &sample.entry,
+                        };
+                        println!("{}", template.render(&values, message_regex.as_ref()));
+                    }
+                }
+                continue;
+            }
+            if args.grep_compat {
+                for (index, message) in matched.into_iter().enumerate().filter_map(|(i, m)| Some((i, m?))) {
+                    if page.admit() {
+                        grep_compat::display_grep_lines((path, &sample.id, sample.epoch), index, message, message_regex.as_ref());
+                    }
+                }
+                continue;
+            }
+            // Pagination counts matches, and context is shown around the matches on the page
+            let admitted = matched.iter().positions(Option::is_some).filter(|_| page.admit()).collect::<Vec<_>>();
+            let groups = context.groups(&admitted, sample.messages.len());
+            if args.dedup_content {
+                let messages = admitted.iter().filter_map(|&index| matched[index]).collect::<Vec<_>>();
+                content_groups.add_sample((path, &sample.id, sample.epoch), &messages);
+                continue;
+            }
+            if format == OutputFormat::Jsonl {
+                for index in groups.into_iter().flatten() {
+                    let Some(message) = &sample.messages[index] else { continue };
+                    let is_context = matched[index].is_none();
+                    let content_hash = args.show_content_hash.then(|| dedup::content_hash(message));
+                    let at = MessageSource { index, entry: &sample.entry, span: sample.message_spans[index], score: score.as_deref(), is_context, content_hash };
+                    jsonl::display_message_json((path, &sample.id, sample.epoch), message, at, message_regex.as_ref());
+                }
+                continue;
+            }
+            if let (Some(reasons), false) = (&truncation, admitted.is_empty()) {
+                let reasons = format!("[truncated: {}]", reasons.join(", ")).red().bold();
+                println!("\n{}", args.theme.header(&path.file_name().unwrap().to_string_lossy(), &sample.id, sample.epoch, reasons));
+            }
+            if args.show_env && !groups.is_empty() {
+                environment::display_environment((path, &sample.id, sample.epoch), sandbox.as_ref(), &tools, &args.theme);
+            }
+            if args.show_tools && !groups.is_empty() {
+                environment::display_tools((path, &sample.id, sample.epoch), &tools, &args.theme);
+            }
+            let error = sample.error.as_ref().filter(|_| args.errors_only && !groups.is_empty());
+            for (i, group) in groups.into_iter().enumerate() {
+                if i > 0 && !context.is_empty() {
+                    println!("{}", "--".dimmed());
+                }
+                for index in group {
+                    let Some(message) = &sample.messages[index] else { continue };
+                    let span = args.source_map.then_some((sample.entry.as_str(), sample.message_spans[index]));
+                    let source = (path, sample.id.as_str(), sample.epoch);
+                    let hash = args.show_content_hash.then(|| format!("#{:016x}", dedup::content_hash(message)));
+                    let settings = calls.get(index).copied().flatten().filter(|_| args.show_gen_config).map(gen_config::label);
+                    let relevance = relevance.map(|relevance| format!("relevance {:.2}", relevance));
+                    let label = [score.clone(), hash, settings, relevance].into_iter().flatten().reduce(|label, part| format!("{} {}", label, part));
+                    match matched[index] {
+                        Some(_) if args.line_mode => line_mode::display_message_lines(source, message, span, label.as_deref(), message_regex.as_ref(), line_context, &args.theme),
+                        Some(_) => display_message(source, message, span, label.as_deref(), message_regex.as_ref(), filters.tool_calls.as_ref(), images, &args.theme),
+                        None => display_context_message(source, message, span, images, &args.theme),
+                    }
+                }
+            }
+            if let Some(error) = error {
+                sample_error::display((path, &sample.id, sample.epoch), error, &args.theme);
+            }
+        }
+        if args.count && log_count > 0 {
+            println!("{}:{}", path.display(), log_count);
+        }
+        Ok(())
+    };
+
+    // Process files in parallel, handing them to workers in scan order
     // let m = MultiProgress::new();
-    paths
-        .par_iter()
-        .map(|path| {
-            let sample_paths = matching_samples_in_log(&path, &sample_ids, &epochs).unwrap();
-            (path, process_eval_file(path, &sample_paths, &roles, message_regex.as_ref()))
-        })
-        .for_each(|(path, samples)| {
-            for sample in samples {
-                for message in sample.messages.iter().dedup_by(|a, b| a.is_none() && b.is_none()) {
-                    if let Some(message) = message {
-                        display_message((path, &sample.id, sample.epoch), message, message_regex.as_ref());
+    // With --quiet one match settles the exit status, so no further logs are started
+    let settled = || args.quiet && page.matches.load(Ordering::Relaxed) > 0;
+    // Past the time budget, logs already started are finished but no more are
+    let out_of_time = || args.time_budget.is_some_and(|budget| started.elapsed() >= budget);
+    let files = paths.iter().enumerate().par_bridge().filter(|_| !interrupted() && !settled() && !out_of_time() && !quota.is_met());
+
+    if let Some(raw_regex) = &args.raw_regex {
+        let raw_regex = regex::bytes::Regex::new(&pattern_syntax.apply(raw_regex))?;
+        let results = files.map(|(index, path)| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            let raw_matches = skipped::or_skip(search_raw(path, &sample_paths, &raw_regex, args.dump_raw), path.display()).unwrap_or_default();
+            pb.inc(1);
+            (index, path, raw_matches)
+        });
+        emit_results(
+            results,
+            !args.unordered,
+            args.max_memory,
+            |a, b| (&a.sample_id, a.epoch).cmp(&(&b.sample_id, b.epoch)),
+            |log_path, raw_matches| {
+                ScanCounters::add(&counters.samples_matched, raw_matches.len());
+                if !raw_matches.is_empty() {
+                    hit(log_path);
+                }
+                if let Some(eval_export) = &eval_export {
+                    for raw_match in &raw_matches {
+                        eval_export.add(log_path, &raw_match.sample_id, raw_match.epoch as i64);
+                    }
+                }
+                let aliased = id_aliases.as_ref().map(|id_aliases| id_aliases.log(log_path));
+                let path = aliased.as_deref().unwrap_or(log_path);
+                for mut raw_match in raw_matches {
+                    if let Some(id_aliases) = &id_aliases {
+                        raw_match.sample_id = id_aliases.sample(&raw_match.sample_id);
+                    }
+                    if let Some(match_stats) = &match_stats {
+                        match_stats.add(log_path, (path, &raw_match.sample_id, raw_match.epoch as i64), ["raw JSON".to_string()]);
+                    }
+                    if let Some(rollup) = &rollup {
+                        rollup.add(log_path, 1);
+                    }
+                    if page.admit() {
+                        display_raw_match(path, &raw_match, &args.theme);
                     }
                 }
+                Ok(())
+            },
+        )?;
+    } else if let Some(anywhere) = &args.anywhere {
+        let anywhere = Regex::new(&pattern_syntax.apply(anywhere))?;
+        let results = files.map(|(index, path)| {
+            let sample_paths = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &epochs, args.sample_layout.as_ref(), &counters), path.display()).unwrap_or_default();
+            let anywhere_matches = skipped::or_skip(search_anywhere(path, &sample_paths, &anywhere), path.display()).unwrap_or_default();
+            pb.inc(1);
+            (index, path, anywhere_matches)
+        });
+        emit_results(
+            results,
+            !args.unordered,
+            args.max_memory,
+            |a, b| (&a.sample_id, a.epoch).cmp(&(&b.sample_id, b.epoch)),
+            |log_path, anywhere_matches| {
+                ScanCounters::add(&counters.samples_matched, anywhere_matches.len());
+                if !anywhere_matches.is_empty() {
+                    hit(log_path);
+                }
+                if let Some(eval_export) = &eval_export {
+                    for anywhere_match in &anywhere_matches {
+                        eval_export.add(log_path, &anywhere_match.sample_id, anywhere_match.epoch as i64);
+                    }
+                }
+                let aliased = id_aliases.as_ref().map(|id_aliases| id_aliases.log(log_path));
+                let path = aliased.as_deref().unwrap_or(log_path);
+                for mut anywhere_match in anywhere_matches {
+                    if let Some(id_aliases) = &id_aliases {
+                        anywhere_match.sample_id = id_aliases.sample(&anywhere_match.sample_id);
+                    }
+                    if let Some(match_stats) = &match_stats {
+                        match_stats.add(log_path, (path, &anywhere_match.sample_id, anywhere_match.epoch as i64), ["sample fields".to_string()]);
+                    }
+                    if let Some(rollup) = &rollup {
+                        rollup.add(log_path, 1);
+                    }
+                    if page.admit() {
+                        display_anywhere_match(path, &anywhere_match, &args.theme);
+                    }
+                }
+                Ok(())
+            },
+        )?;
+    } else {
+        let results = files.map(|(index, path)| {
+            // Logs the index knows have no messages with the roles asked for aren't opened
+            if !filters.roles.is_all() && log_index::lacks_roles(path, filters.roles) {
+                ScanCounters::add(&counters.files_without_role, 1);
+                pb.inc(1);
+                return (index, path, Vec::new());
+            }
+            let listed = skipped::or_skip(matching_samples_in_log(path, &sample_ids, &epochs, args.sample_layout.as_ref(), &counters), path.display())
+                .and_then(|sample_paths| skipped::or_skip(split_oversized(path, sample_paths, args.max_sample_size, &counters), path.display()));
+            let mut listed_fully = listed.is_some();
+            let (mut sample_paths, oversized) = listed.unwrap_or_default();
+            // A sharded sample's spans would run across its stitched-together shards, not within any one entry
+            if args.source_map {
+                let listed_count = sample_paths.len();
+                sample_paths.retain(|sample| {
+                    let unsharded = match sample.entries.len() {
+                        1 => Ok(()),
+                        shards => Err(anyhow!("split across {} shards, which --source-map can't give byte ranges in", shards)),
+                    };
+                    skipped::or_skip(unsharded, format_args!("{}: {}", path.display(), sample.name())).is_some()
+                });
+                listed_fully &= sample_paths.len() == listed_count;
+            }
+            let mut samples = match whole_samples {
+                true => read_whole_samples(path, &sample_paths, read_options),
+                false if quota.is_unlimited() => process_eval_file(path, &sample_paths, read_options, &filters, &counters),
+                false => process_eval_file_within(path, &sample_paths, read_options, &filters, &counters, &quota.file()),
+            };
+            // Every sample listed is read unless it's skipped as unreadable; a log with skipped
+            // samples, or read only until the quota was met, hasn't shown all of its roles
+            let read_fully = listed_fully && samples.len() == sample_paths.len();
+            if read_fully && sample_ids.is_none() && matches!(epochs, IntFilter::All) && oversized.is_empty() && quota.is_unlimited() && !interrupted() {
+                log_index::record_roles(path, samples.iter().flat_map(|sample| &sample.roles));
             }
+            filters.retain(&mut samples, &counters);
+            // Oversized samples can only be searched as raw JSON, which needs a pattern and no whole-sample checks
+            if let Some(stream_regex) = stream_regex.as_ref().filter(|_| !whole_samples && !args.turns) {
+                samples.extend(oversized.iter().filter_map(|sample| streamed_sample(path, sample, stream_regex)));
+            }
+            pb.inc(1);
+            (index, path, samples)
         });
+        let ranked = args.rank.then(|| Mutex::new(Vec::new()));
+        emit_results(
+            results,
+            !args.unordered,
+            args.max_memory,
+            |a, b| (&a.id, a.epoch).cmp(&(&b.id, b.epoch)),
+            |log_path, samples| match &ranked {
+                // Ranking needs every match, so nothing is shown until the scan ends
+                Some(ranked) => {
+                    ranked.lock().unwrap().extend(samples.into_iter().map(|sample| (log_path.to_path_buf(), sample)));
+                    Ok(())
+                }
+                None => display_samples(log_path, samples),
+            },
+        )?;
+        if let (Some(ranked), Some(pattern)) = (ranked, &filters.pattern) {
+            let ranked = ranked.into_inner().unwrap();
+            let terms = ranked
+                .iter()
+                .map(|(_, sample)| {
+                    let matched = match whole_samples {
+                        true => filters.matched(&sample.messages, &ScanCounters::default()),
+                        false => sample.messages.iter().map(Option::as_ref).collect::<Vec<_>>(),
+                    };
+                    relevance::sample_terms(pattern, sample, &matched)
+                })
+                .collect::<Vec<_>>();
+            let mut ranked = ranked.into_iter().zip(relevance::scores(&terms)).collect::<Vec<_>>();
+            // Stable, so equally relevant samples stay in scan order
+            ranked.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+            relevance_scores.lock().unwrap().extend(ranked.iter().map(|((path, sample), relevance)| ((path.clone(), sample.id.clone(), sample.epoch), *relevance)));
+            // Runs of samples from one log are shown together, so its reduced scores are loaded once a run
+            for (path, run) in &ranked.into_iter().group_by(|((path, _), _)| path.clone()) {
+                display_samples(&path, run.map(|((_, sample), _)| sample).collect())?;
+            }
+        }
+    }
 
-    pb.finish_with_message("Search complete");
+    if args.dedup_content {
+        content_groups.display(args.show_content_hash, message_regex.as_ref(), &args.theme);
+    }
+    let distinct_values = capture_counts.len();
+    if args.extract_count {
+        capture_counts.display();
+    }
+
+    interrupt::finish();
+    let interrupted_marker = if interrupted() { " (interrupted)".red().bold().to_string() } else { String::new() };
+    let out_of_budget = !interrupted() && !quota.is_met() && (pb.position() as usize) < paths.len();
+    if interrupted() {
+        pb.abandon_with_message("Search interrupted");
+    } else if quota.is_met() {
+        pb.abandon_with_message("Result limit reached");
+    } else if out_of_budget {
+        pb.abandon_with_message("Time budget reached");
+    } else {
+        pb.finish_with_message("Search complete");
+    }
+    if let Some(id_aliases) = &id_aliases {
+        id_aliases.save()?;
+    }
+    // An interrupted scan found only some of the samples, so nothing is exported
+    if let (Some(eval_export), Some(output), false) = (eval_export, &args.export_eval, interrupted()) {
+        eval_export.write(output, args.sample_layout.as_ref())?;
+    }
+    if hit_history.is_some() && !interrupted() {
+        if let Err(e) = HitHistory::record(&hot_pattern, matched_files.into_inner().unwrap()) {
+            eprintln!("{} failed to remember matching logs: {:#}", "warning:".yellow().bold(), e);
+        }
+    }
+    let shown = page.shown.into_inner();
+    let entries = page.seen.into_inner();
+    let matches = page.matches.into_inner();
+    if args.quiet && !interrupted() {
+        return Ok(if matches > 0 { ExitCode::SUCCESS } else { ExitCode::FAILURE });
+    }
+    let distinct_marker = match (args.dedup_content, args.extract_count) {
+        (true, _) => format!(" ({} distinct)", content_groups.len()),
+        (_, true) => format!(" ({} distinct values)", distinct_values),
+        _ => String::new(),
+    };
+    let page_marker = if shown < entries && !args.aggregate_only { format!(" (showing {}-{})", args.offset + 1, args.offset + shown) } else { String::new() };
+    eprintln!("{} matches{}{} in {} of {} files{}", matches, distinct_marker, page_marker, pb.position(), paths.len(), interrupted_marker);
+    if let Some(budget) = args.time_budget.filter(|_| out_of_budget) {
+        let coverage = 100.0 * pb.position() as f64 / paths.len() as f64;
+        eprintln!(
+            "{} the {} time budget ran out with {} of {} files scanned ({:.1}%); raise --time-budget or narrow the scan to cover the rest",
+            "note:".yellow().bold(),
+            format_elapsed(budget),
+            pb.position(),
+            paths.len(),
+            coverage
+        );
+    }
+    if let Some(max_results) = args.max_results.filter(|_| quota.is_met() && !interrupted()) {
+        eprintln!("{} stopped at --max-results {} with {} of {} files scanned; there may be more matches", "note:".yellow().bold(), max_results, pb.position(), paths.len());
+    }
+    let oversized = counters.samples_oversized.load(Ordering::Relaxed);
+    if oversized > 0 {
+        let handling = match stream_regex.is_some() && !whole_samples && !args.turns {
+            true => "searched as raw JSON without parsing (role filters don't apply to them)",
+            false => "skipped (only -m searches without --all or -v can stream them)",
+        };
+        eprintln!("{} {} samples over --max-sample-size were {}", "note:".yellow().bold(), oversized, handling);
+    }
+    if matches == 0 && !interrupted() {
+        let selected = counters.samples_selected.load(Ordering::Relaxed);
+        let hint = match (&args.raw_regex, &args.anywhere) {
+            (Some(raw_regex), _) if selected > 0 => {
+                format!("{} samples matched id/epoch filters but none of their raw JSON matched '{}'", selected, raw_regex)
+            }
+            (_, Some(anywhere)) if selected > 0 => {
+                format!("{} samples matched id/epoch filters but '{}' appeared in none of their fields", selected, anywhere)
+            }
+            _ if args.truncated_only && selected > 0 && counters.samples_truncated.load(Ordering::Relaxed) == 0 => {
+                format!("{} samples matched id/epoch filters but none of them ended abnormally", selected)
+            }
+            _ => counters.hint(paths.len(), &filters),
+        };
+        eprintln!("{} {}", "hint:".yellow().bold(), hint);
+    }
+    if args.malformed_only && matches > 0 {
+        let samples = counters.samples_matched.load(Ordering::Relaxed);
+        eprintln!("{} {} malformed messages (missing or unrecognized role) in {} samples", "note:".yellow().bold(), matches, samples);
+    }
+    if let Some(rollup) = &rollup {
+        rollup.display();
+    }
+    if let Some(match_stats) = &match_stats {
+        match_stats.display((pb.position() as usize, paths.len()), counters.samples_selected.load(Ordering::Relaxed), started.elapsed());
+    }
+    if args.aggregate_only {
+        for (name, value) in counters.assertion_variables(matches, paths.len()) {
+            println!("{}: {}", name, value);
+        }
+    }
+    if !interrupted() {
+        notify_if_slow(started, args.notify_after, &format!("{} matches in {} files", matches, paths.len()));
+    }
+
+    // Check assertions against the totals; an interrupted scan has nothing to assert about
+    if !args.assertions.is_empty() && !interrupted() {
+        let vars = counters.assertion_variables(matches, paths.len());
+        let violations = args.assertions.iter().filter_map(|a| a.check(&vars)).collect::<Vec<_>>();
+        for violation in &violations {
+            eprintln!("{}", violation.to_string().red().bold());
+        }
+        if !violations.is_empty() {
+            return Ok(ExitCode::FAILURE);
+        }
+    }
 
     // Display results
     // for (run_id, task, sample, message) in results {
@@ -266,5 +2251,5 @@ fn main() -> Result<()> {
     //     println!("{}", message);
     // }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,60 @@
+use anyhow::{bail, Context};
+use std::str::FromStr;
+
+// Positions of messages in a conversation, as for --epochs (`0`, `0-5`, `2,4,7`, `all`),
+// where negative positions count from the end: `-1` is the last message, `-3--1` the last three
+#[derive(Debug, Clone, Default)]
+pub struct MessageIndexFilter {
+    // Inclusive ranges; none for every message
+    ranges: Vec<(i64, i64)>,
+}
+
+fn parse_index(s: &str) -> anyhow::Result<i64> {
+    s.trim().parse().with_context(|| format!("invalid message index '{}'", s.trim()))
+}
+
+impl FromStr for MessageIndexFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "all" {
+            return Ok(Self::default());
+        }
+        let ranges = s
+            .split(',')
+            .map(|item| {
+                let item = item.trim();
+                // A range's dash is the first one after its start, which may itself be negative
+                let dash = item.char_indices().skip(1).find(|(_, c)| *c == '-').map(|(i, _)| i);
+                match dash {
+                    Some(dash) => Ok((parse_index(&item[..dash])?, parse_index(&item[dash + 1..])?)),
+                    None => parse_index(item).map(|index| (index, index)),
+                }
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if let Some((start, end)) = ranges.iter().find(|(start, end)| start.signum() == end.signum() && start > end) {
+            bail!("empty range: {} is after {}", start, end);
+        }
+        Ok(Self { ranges })
+    }
+}
+
+impl MessageIndexFilter {
+    pub fn is_all(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    // Whether the message at `index` of a conversation of `total` messages is selected
+    pub fn contains(&self, index: usize, total: usize) -> bool {
+        let resolve = |position: i64| if position < 0 { total as i64 + position } else { position };
+        let index = index as i64;
+        self.is_all() || self.ranges.iter().any(|(start, end)| (resolve(*start)..=resolve(*end)).contains(&index))
+    }
+
+    // Whether the message at `index` could be selected before the conversation's length
+    // is known: ranges counting from the end might reach any message
+    pub fn may_contain(&self, index: usize) -> bool {
+        let index = index as i64;
+        self.is_all() || self.ranges.iter().any(|(start, end)| *start < 0 || *end < 0 || (*start..=*end).contains(&index))
+    }
+}
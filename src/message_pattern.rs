@@ -0,0 +1,236 @@
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use itertools::Itertools;
+use regex::Regex;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::filter_set::RoleSet;
+use crate::inspect::ChatMessageRole;
+
+/// The patterns a message's content is searched for: it matches when it matches
+/// any one of them, or with `all` every one; `invert` selects the messages that don't.
+/// A pattern may apply only to messages of some roles, for --role-regex.
+#[derive(Debug, Clone)]
+pub struct MessagePattern {
+    patterns: Vec<Regex>,
+    // The roles each pattern applies to, by position
+    roles: Vec<RoleSet>,
+    all: bool,
+    invert: bool,
+    // Matches wherever any of the patterns does, for highlighting and locating matches
+    combined: Regex,
+}
+
+impl MessagePattern {
+    /// `None` when there are no patterns, so every message matches.
+    pub fn new(patterns: &[String], all: bool, invert: bool) -> Result<Option<Self>> {
+        let patterns = patterns.iter().map(|pattern| (pattern.clone(), RoleSet::all())).collect::<Vec<_>>();
+        Self::with_roles(&patterns, all, invert)
+    }
+
+    /// Like [`Self::new`], with each pattern applying only to messages of its roles.
+    pub fn with_roles(patterns: &[(String, RoleSet)], all: bool, invert: bool) -> Result<Option<Self>> {
+        let compiled = patterns
+            .iter()
+            .map(|(pattern, _)| Regex::new(pattern).with_context(|| format!("Failed to compile message regex '{}'", pattern)))
+            .collect::<Result<Vec<_>>>()?;
+        let combined = match compiled.as_slice() {
+            [] => return Ok(None),
+            [pattern] => pattern.clone(),
+            _ => Regex::new(&patterns.iter().map(|(pattern, _)| format!("(?:{})", pattern)).join("|"))?,
+        };
+        let roles = patterns.iter().map(|(_, roles)| *roles).collect();
+        Ok(Some(Self { patterns: compiled, roles, all, invert, combined }))
+    }
+
+    /// Whether a text matches, whatever the roles of the patterns.
+    pub fn is_match(&self, text: &str) -> bool {
+        let matched = match self.all {
+            true => self.patterns.iter().all(|pattern| pattern.is_match(text)),
+            false => self.combined.is_match(text),
+        };
+        matched != self.invert
+    }
+
+    /// Whether a message of `role` matches the patterns that apply to its role; when
+    /// none do, it doesn't match.
+    pub fn is_match_for(&self, role: &ChatMessageRole, text: &str) -> bool {
+        if !self.is_scoped() {
+            return self.is_match(text);
+        }
+        let applying = self.patterns.iter().zip(&self.roles).filter(|(_, roles)| roles.contains(role)).map(|(pattern, _)| pattern).collect::<Vec<_>>();
+        let matched = match self.all {
+            true => !applying.is_empty() && applying.iter().all(|pattern| pattern.is_match(text)),
+            false => applying.iter().any(|pattern| pattern.is_match(text)),
+        };
+        matched != self.invert
+    }
+
+    // Whether some pattern applies only to some roles
+    fn is_scoped(&self) -> bool {
+        !self.roles.iter().all(|roles| roles.is_all())
+    }
+
+    /// Each pattern, with the roles it applies to.
+    pub fn terms(&self) -> impl Iterator<Item = (&Regex, RoleSet)> {
+        self.patterns.iter().zip(self.roles.iter().copied())
+    }
+
+    /// A regex matching any of the patterns.
+    pub fn regex(&self) -> &Regex {
+        &self.combined
+    }
+
+    /// Whether a text matches exactly when [`Self::regex`] does, which searches of
+    /// raw sample JSON rely on.
+    pub fn is_single_regex(&self) -> bool {
+        !self.invert && !self.is_scoped() && (!self.all || self.patterns.len() == 1)
+    }
+}
+
+/// How search patterns given on the command line are read, as in ripgrep.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PatternSyntax {
+    /// Match case-insensitively.
+    pub ignore_case: bool,
+    /// Match case-insensitively unless the pattern has an uppercase letter.
+    pub smart_case: bool,
+    /// Match the pattern literally rather than as a regex.
+    pub fixed_strings: bool,
+}
+
+impl PatternSyntax {
+    /// The regex source for `pattern`.
+    pub fn apply(self, pattern: &str) -> String {
+        let ignore_case = self.ignore_case || (self.smart_case && !has_uppercase(pattern, self.fixed_strings));
+        let pattern = if self.fixed_strings { regex::escape(pattern) } else { pattern.to_string() };
+        if ignore_case { format!("(?i){}", pattern) } else { pattern }
+    }
+}
+
+// Escapes like \S and \W name classes rather than letters, so they don't count
+fn has_uppercase(pattern: &str, literal: bool) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && !literal {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+impl From<Regex> for MessagePattern {
+    fn from(regex: Regex) -> Self {
+        Self { patterns: vec![regex.clone()], roles: vec![RoleSet::all()], all: false, invert: false, combined: regex }
+    }
+}
+
+// Quoted, as in hints: 'a' and 'b', 'a' in assistant messages, or anything but 'a'
+impl fmt::Display for MessagePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.all { " and " } else { " or " };
+        let patterns = self
+            .patterns
+            .iter()
+            .zip(&self.roles)
+            .map(|(pattern, roles)| match roles.is_all() {
+                true => format!("'{}'", pattern),
+                false => format!("'{}' in {} messages", pattern, roles.iter().join("/")),
+            })
+            .join(separator);
+        match (self.invert, self.patterns.len()) {
+            (false, _) => write!(f, "{}", patterns),
+            (true, 1) => write!(f, "anything but {}", patterns),
+            (true, _) => write!(f, "anything but ({})", patterns),
+        }
+    }
+}
+
+/// A pattern for messages of some roles only, given to --role-regex as `ROLES=PATTERN`,
+/// e.g. `assistant=I can.?t help` or `user,system=ignore previous`.
+#[derive(Debug, Clone)]
+pub struct RolePattern {
+    pub roles: Vec<ChatMessageRole>,
+    pub pattern: String,
+}
+
+impl FromStr for RolePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((roles, pattern)) = s.split_once('=') else { bail!("expected ROLES=PATTERN, e.g. assistant=refuse") };
+        let roles = roles.split(',').map(|role| <ChatMessageRole as ValueEnum>::from_str(role.trim(), true).map_err(|_| anyhow!("unknown role '{}'", role))).collect::<Result<Vec<_>>>()?;
+        if pattern.is_empty() {
+            bail!("expected a pattern after '='");
+        }
+        Ok(Self { roles, pattern: pattern.to_string() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(patterns: &[(&str, &[ChatMessageRole])], all: bool, invert: bool) -> MessagePattern {
+        let patterns = patterns
+            .iter()
+            .map(|(pattern, roles)| (pattern.to_string(), if roles.is_empty() { RoleSet::all() } else { RoleSet::of(roles) }))
+            .collect::<Vec<_>>();
+        MessagePattern::with_roles(&patterns, all, invert).unwrap().unwrap()
+    }
+
+    #[test]
+    fn smart_case_looks_past_escapes() {
+        assert!(!has_uppercase(r"error\s+\d", false));
+        assert!(!has_uppercase(r"\S\W\D", false));
+        assert!(has_uppercase(r"\sError", false));
+        // Literally, a backslash is just a backslash
+        assert!(has_uppercase(r"\S", true));
+        let smart = PatternSyntax { smart_case: true, ..PatternSyntax::default() };
+        assert_eq!(smart.apply(r"\w+ing"), r"(?i)\w+ing");
+        assert_eq!(smart.apply("Refuse"), "Refuse");
+        let literal = PatternSyntax { smart_case: true, fixed_strings: true, ..PatternSyntax::default() };
+        assert_eq!(literal.apply("a.b"), r"(?i)a\.b");
+    }
+
+    #[test]
+    fn no_patterns_is_none() {
+        assert!(MessagePattern::new(&[], false, false).unwrap().is_none());
+        assert!(MessagePattern::new(&["(".to_string()], false, false).unwrap_err().to_string().contains("Failed to compile message regex '('"));
+    }
+
+    #[test]
+    fn patterns_apply_to_their_roles() {
+        let pattern = pattern(&[("refuse", &[ChatMessageRole::Assistant]), ("ignore", &[ChatMessageRole::User, ChatMessageRole::System])], false, false);
+        assert!(pattern.is_match_for(&ChatMessageRole::Assistant, "I refuse"));
+        assert!(!pattern.is_match_for(&ChatMessageRole::User, "I refuse"));
+        assert!(pattern.is_match_for(&ChatMessageRole::System, "ignore previous"));
+        assert!(!pattern.is_match_for(&ChatMessageRole::Assistant, "ignore previous"));
+        assert!(!pattern.is_match_for(&ChatMessageRole::Tool, "refuse and ignore"));
+        assert!(!pattern.is_single_regex());
+    }
+
+    #[test]
+    fn all_needs_a_pattern_for_the_role() {
+        let both = pattern(&[("a", &[ChatMessageRole::Assistant]), ("b", &[ChatMessageRole::Assistant])], true, false);
+        assert!(both.is_match_for(&ChatMessageRole::Assistant, "a b"));
+        assert!(!both.is_match_for(&ChatMessageRole::Assistant, "a"));
+        // No pattern applies to user messages, so they never match, and inverted they all do
+        assert!(!both.is_match_for(&ChatMessageRole::User, "a b"));
+        let inverted = pattern(&[("a", &[ChatMessageRole::Assistant])], true, true);
+        assert!(inverted.is_match_for(&ChatMessageRole::User, "a"));
+        assert!(!inverted.is_match_for(&ChatMessageRole::Assistant, "a"));
+    }
+
+    #[test]
+    fn unscoped_patterns_match_any_role() {
+        let pattern = pattern(&[("a", &[]), ("b", &[])], true, false);
+        assert!(pattern.is_match_for(&ChatMessageRole::Tool, "b a"));
+        assert!(!pattern.is_match_for(&ChatMessageRole::Tool, "b"));
+        assert!(!pattern.is_single_regex());
+        assert_eq!(pattern.to_string(), "'a' and 'b'");
+    }
+}
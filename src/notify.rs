@@ -0,0 +1,65 @@
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+// How long a scan must run before its completion is announced, e.g. 90s, 5m, 1h
+#[derive(Debug, Clone, Copy)]
+pub struct NotifyAfter(Duration);
+
+impl FromStr for NotifyAfter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_duration(s).map(Self)
+    }
+}
+
+// A number of seconds, minutes or hours, e.g. 90s, 5m, 1.5h (seconds without a unit)
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit) = s.split_at(s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len()));
+    let number: f64 = number.parse().map_err(|_| format!("invalid duration '{}'", s))?;
+    let seconds = match unit.to_ascii_lowercase().as_str() {
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(format!("invalid duration unit '{}' (expected s, m or h)", unit)),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    match seconds {
+        0..=59 => format!("{:.1}s", elapsed.as_secs_f64()),
+        60..=3599 => format!("{}m{:02}s", seconds / 60, seconds % 60),
+        _ => format!("{}h{:02}m{:02}s", seconds / 3600, seconds % 3600 / 60, seconds % 60),
+    }
+}
+
+// Ring the terminal bell and raise a desktop notification once a long scan finishes.
+// The OSC 9 escape is shown as a notification by terminals that support it and ignored by the rest;
+// notify-send/osascript cover the desktop when they're installed
+pub fn notify_if_slow(started: Instant, threshold: Option<NotifyAfter>, summary: &str) {
+    let elapsed = started.elapsed();
+    let Some(NotifyAfter(threshold)) = threshold else { return };
+    if elapsed < threshold {
+        return;
+    }
+    let message = format!("Scan finished in {}: {}", format_elapsed(elapsed), summary);
+    eprintln!("{}", message);
+
+    let mut stderr = std::io::stderr();
+    if stderr.is_terminal() {
+        let _ = write!(stderr, "\x1b]9;inspect-grep: {}\x07\x07", message);
+        let _ = stderr.flush();
+    }
+    // No notifier installed (e.g. on a headless machine): the bell and summary line will have to do
+    let _ = if cfg!(target_os = "macos") {
+        let script = format!("display notification {:?} with title \"inspect-grep\"", message);
+        Command::new("osascript").args(["-e", &script]).stdout(Stdio::null()).stderr(Stdio::null()).status()
+    } else {
+        Command::new("notify-send").args(["inspect-grep", &message]).stdout(Stdio::null()).stderr(Stdio::null()).status()
+    };
+}
@@ -0,0 +1,52 @@
+use anyhow::{anyhow, bail};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::str::FromStr;
+
+lazy_static! {
+    // Integers and decimals, allowing thousands separators (1,234.5)
+    static ref NUMBER: Regex = Regex::new(r"\d+(?:,\d{3})*(?:\.\d+)?").unwrap();
+}
+
+// An inclusive range of numbers, either end of which may be left open: 95..100, 0.9.., ..-1
+#[derive(Debug, Clone, Copy)]
+pub struct NumberRange {
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl FromStr for NumberRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min, max) = s.split_once("..").ok_or_else(|| anyhow!("expected a range like 95..100, 0.9.. or ..10"))?;
+        let bound = |b: &str| -> Result<Option<f64>, Self::Err> {
+            let b = b.trim();
+            (!b.is_empty()).then(|| b.parse().map_err(|_| anyhow!("invalid number '{}'", b))).transpose()
+        };
+        let range = Self { min: bound(min)?, max: bound(max)? };
+        if let (Some(min), Some(max)) = (range.min, range.max) {
+            if min > max {
+                bail!("empty range: {} is greater than {}", min, max);
+            }
+        }
+        Ok(range)
+    }
+}
+
+impl NumberRange {
+    fn contains(&self, n: f64) -> bool {
+        self.min.is_none_or(|min| n >= min) && self.max.is_none_or(|max| n <= max)
+    }
+
+    // Whether any number written in the text falls in the range. A leading '-' makes a number
+    // negative unless it follows a letter or digit, as in ranges (2-3) or identifiers (gpt-4)
+    pub fn any_in(&self, text: &str) -> bool {
+        NUMBER.find_iter(text).any(|m| {
+            let Ok(n) = m.as_str().replace(',', "").parse::<f64>() else { return false };
+            let mut before = text[..m.start()].chars().rev();
+            let negative = before.next() == Some('-') && !before.next().is_some_and(char::is_alphanumeric);
+            self.contains(if negative { -n } else { n })
+        })
+    }
+}
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use clap::ValueEnum;
+
+use crate::queries::Query;
+
+/// A maintained bundle of patterns with severities, run like a --queries file.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Preset {
+    /// Assistant output that may give uplift for serious harm: weapons, malware, self-harm methods, leaked secrets
+    HarmfulContent,
+    /// Prompt-injection and jailbreak attempts, and assistant replies that play along
+    JailbreakMarkers,
+}
+
+impl Preset {
+    fn source(self) -> &'static str {
+        match self {
+            Preset::HarmfulContent => include_str!("presets/harmful-content.toml"),
+            Preset::JailbreakMarkers => include_str!("presets/jailbreak-markers.toml"),
+        }
+    }
+
+    /// The preset's patterns as queries named `<preset>/<pattern>`.
+    pub fn queries(self) -> Result<Vec<Query>> {
+        let name = self.to_possible_value().expect("presets are never skipped");
+        crate::queries::load_preset(name.get_name(), self.source())
+    }
+}
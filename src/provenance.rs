@@ -0,0 +1,154 @@
+use colored::*;
+use itertools::Itertools;
+use regex::Regex;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::inspect::{ChatMessage, ChatMessageRole, EvalSample};
+
+// Where a string entered a conversation: given to the model, returned by a tool, or
+// written by the model itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Origin {
+    Input,
+    ToolOutput,
+    Assistant,
+}
+
+impl Origin {
+    fn of(role: &ChatMessageRole) -> Self {
+        match role {
+            ChatMessageRole::Assistant => Origin::Assistant,
+            ChatMessageRole::Tool => Origin::ToolOutput,
+            _ => Origin::Input,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Origin::Input => "input",
+            Origin::ToolOutput => "tool output",
+            Origin::Assistant => "assistant message",
+        }
+    }
+
+    fn colored(self) -> ColoredString {
+        match self {
+            Origin::Input => self.describe().blue(),
+            Origin::ToolOutput => self.describe().yellow(),
+            Origin::Assistant => self.describe().green(),
+        }
+    }
+}
+
+// A message the string occurs in. `turn` counts the assistant messages up to and
+// including it, so the input is turn 0 and a tool's output shares its call's turn
+struct Occurrence {
+    index: usize,
+    role: ChatMessageRole,
+    turn: usize,
+    count: usize,
+}
+
+// The string values in a tool call's arguments, which is where the model writes them
+fn argument_strings(value: &Value) -> Vec<&str> {
+    match value {
+        Value::String(s) => vec![s],
+        Value::Array(values) => values.iter().flat_map(argument_strings).collect(),
+        Value::Object(map) => map.values().flat_map(argument_strings).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// How many times the string occurs in the message's content and, for the model's
+// messages, the arguments of its tool calls
+fn count_in(message: &ChatMessage, needle: &Regex) -> usize {
+    let arguments = message.tool_calls.iter().flat_map(|call| argument_strings(&call.arguments));
+    std::iter::once(&*message.content).chain(arguments).map(|text| needle.find_iter(text).count()).sum()
+}
+
+// Each message of the conversation the string occurs in, in order
+fn occurrences(messages: &[Option<ChatMessage>], needle: &Regex) -> Vec<Occurrence> {
+    let mut found = Vec::new();
+    let mut turn = 0;
+    for (index, message) in messages.iter().enumerate() {
+        let Some(message) = message else { continue };
+        if message.role == ChatMessageRole::Assistant {
+            turn += 1;
+        }
+        let count = count_in(message, needle);
+        if count > 0 {
+            found.push(Occurrence { index, role: message.role.clone(), turn, count });
+        }
+    }
+    found
+}
+
+// Occurrences of one string across the samples scanned, for the `provenance` subcommand
+pub struct Provenance {
+    needle: Regex,
+    samples: usize,
+    // Log -> (sample id, epoch, occurrences) of the samples containing the string
+    logs: BTreeMap<PathBuf, Vec<(String, i64, Vec<Occurrence>)>>,
+}
+
+impl Provenance {
+    pub fn new(needle: Regex) -> Self {
+        Self { needle, samples: 0, logs: BTreeMap::new() }
+    }
+
+    pub fn add_sample(&mut self, log_path: &Path, sample: &EvalSample) {
+        self.samples += 1;
+        let found = occurrences(&sample.messages, &self.needle);
+        if !found.is_empty() {
+            self.logs.entry(log_path.to_path_buf()).or_default().push((sample.id.clone(), sample.epoch, found));
+        }
+    }
+
+    pub fn merge(mut self, other: Self) -> Self {
+        self.samples += other.samples;
+        for (path, found) in other.logs {
+            self.logs.entry(path).or_default().extend(found);
+        }
+        self
+    }
+
+    // Samples scanned, whether or not they contain the string
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    // Each sample containing the string, by log, with where it first appeared and every
+    // message it appears in (`role #index (turn n)`), then how many samples it first
+    // appeared in each kind of message
+    pub fn display(mut self) {
+        let mut first_in: BTreeMap<Origin, usize> = BTreeMap::new();
+        for (path, samples) in &mut self.logs {
+            samples.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+            println!("\n{}", path.display().to_string().cyan());
+            for (id, epoch, found) in samples.iter() {
+                let first = &found[0];
+                let origin = Origin::of(&first.role);
+                *first_in.entry(origin).or_default() += 1;
+                let listed = found
+                    .iter()
+                    .map(|occurrence| {
+                        let times = match occurrence.count {
+                            1 => String::new(),
+                            count => format!(" {}{}", crate::glyphs::glyph("×", "x"), count),
+                        };
+                        format!("{} #{} (turn {}){}", occurrence.role, occurrence.index, occurrence.turn, times)
+                    })
+                    .join(", ");
+                println!("  {}:{}  first in {} at turn {}  {}", id, epoch, origin.colored().bold(), first.turn, listed.dimmed());
+            }
+        }
+        let found = self.logs.values().map(Vec::len).sum::<usize>();
+        let origins = first_in.iter().map(|(origin, samples)| format!("{} {}", samples, origin.describe())).join(", ");
+        match found {
+            0 => eprintln!("found in none of {} samples", self.samples),
+            _ => eprintln!("found in {} of {} samples; first in: {}", found, self.samples, origins),
+        }
+    }
+}
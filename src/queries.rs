@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use colored::*;
+use itertools::Itertools;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::assembly::SampleEntries;
+use crate::counters::ScanCounters;
+use crate::filter_set::{FilterSet, RoleSet};
+use crate::inspect::{ChatMessage, ChatMessageRole, EvalSample};
+use crate::message_pattern::MessagePattern;
+use crate::theme::Theme;
+use crate::{Filter, IntFilter};
+
+/// How serious a query's matches are, for ranking them; presets give every pattern one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+            Severity::Critical => "critical",
+        }
+    }
+
+    // `[high]`, redder the more severe
+    pub fn tag(self) -> ColoredString {
+        let tag = format!("[{}]", self.name());
+        match self {
+            Severity::Low => tag.cyan(),
+            Severity::Medium => tag.yellow(),
+            Severity::High => tag.red(),
+            Severity::Critical => tag.red().bold().reversed(),
+        }
+    }
+}
+
+// A named query as written in a --queries file or a preset
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuerySpec {
+    name: String,
+    message_regex: Option<String>,
+    samples: Option<String>,
+    epochs: Option<String>,
+    #[serde(default)]
+    roles: Vec<ChatMessageRole>,
+    severity: Option<Severity>,
+}
+
+// One query of a batch, with the matches and counters it has accumulated
+pub struct Query {
+    pub name: String,
+    pub severity: Option<Severity>,
+    samples: Option<Regex>,
+    epochs: IntFilter,
+    filters: FilterSet,
+    pub counters: ScanCounters,
+    // (log, sample id, epoch, message)
+    matches: Mutex<Vec<(PathBuf, String, i64, ChatMessage)>>,
+}
+
+impl Query {
+    fn compile(spec: QuerySpec) -> Result<Self> {
+        let context = || format!("in query '{}'", spec.name);
+        Ok(Self {
+            samples: spec.samples.as_deref().map(Regex::new).transpose().with_context(context)?,
+            epochs: spec.epochs.as_deref().unwrap_or("all").parse().with_context(context)?,
+            filters: FilterSet {
+                roles: RoleSet::of(&spec.roles),
+                pattern: spec.message_regex.as_deref().map(Regex::new).transpose().with_context(context)?.map(MessagePattern::from),
+                ..FilterSet::default()
+            },
+            name: spec.name,
+            severity: spec.severity,
+            counters: ScanCounters::default(),
+            matches: Mutex::new(Vec::new()),
+        })
+    }
+
+    fn selects_id(&self, sample_id: &str, epoch: u32) -> bool {
+        self.samples.as_ref().is_none_or(|re| re.is_match(sample_id)) && self.epochs.filter(&epoch)
+    }
+
+    pub fn selects(&self, sample: &SampleEntries) -> bool {
+        self.selects_id(&sample.sample_id, sample.epoch)
+    }
+
+    // Record this query's matches within samples read in full, which must include
+    // every sample the query selects
+    pub fn scan(&self, log_path: &Path, entries: &[SampleEntries], samples: &[EvalSample]) {
+        let selected = entries.iter().filter(|sample| self.selects(sample)).count();
+        ScanCounters::add(&self.counters.samples_found, entries.len());
+        ScanCounters::add(&self.counters.samples_selected, selected);
+
+        let mut found = Vec::new();
+        for sample in samples {
+            if !self.selects_id(&sample.id, u32::try_from(sample.epoch).unwrap_or_default()) {
+                continue;
+            }
+            for message in self.filters.matched(&sample.messages, &self.counters).into_iter().flatten() {
+                found.push((log_path.to_path_buf(), sample.id.clone(), sample.epoch, message.clone()));
+            }
+        }
+        self.matches.lock().unwrap().extend(found);
+    }
+
+    pub fn match_count(&self) -> usize {
+        self.matches.lock().unwrap().len()
+    }
+
+    // Print this query's matches, ordered by file, sample and epoch, each tagged with
+    // the query's severity
+    pub fn display(&self, theme: &Theme) {
+        let mut matches = self.matches.lock().unwrap();
+        // Stable, so messages keep their order within a sample
+        matches.sort_by(|a, b| (&a.0, &a.1, a.2).cmp(&(&b.0, &b.1, b.2)));
+        let tag = self.severity.map(|severity| severity.tag().to_string());
+        let title = format!("=== {} ({} matches) ===", self.name, matches.len()).bold();
+        match &tag {
+            Some(tag) => println!("\n{} {}", title, tag),
+            None => println!("\n{}", title),
+        }
+        for (path, sample_id, epoch, message) in matches.iter() {
+            crate::display_message((path, sample_id, *epoch), message, None, tag.as_deref(), self.filters.pattern.as_ref().map(MessagePattern::regex), None, None, theme);
+        }
+    }
+
+    pub fn hint(&self, files: usize) -> String {
+        self.counters.hint(files, &self.filters)
+    }
+
+    pub fn samples_matched(&self) -> usize {
+        self.matched_samples().len()
+    }
+
+    fn matched_samples(&self) -> HashSet<(PathBuf, String, i64)> {
+        self.matches.lock().unwrap().iter().map(|(path, id, epoch, _)| (path.clone(), id.clone(), *epoch)).collect()
+    }
+}
+
+// Load a YAML list of queries, each with a `name` and any of `message_regex`,
+// `samples`, `epochs` and `roles`
+pub fn load_queries(path: &Path) -> Result<Vec<Query>> {
+    let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let specs: Vec<QuerySpec> =
+        serde_yaml::from_reader(file).with_context(|| format!("Failed to parse queries in {}", path.display()))?;
+    specs.into_iter().map(Query::compile).collect()
+}
+
+// A preset's patterns, from its TOML: `[[pattern]]` tables with the same fields as a
+// query, each named `<preset>/<pattern>`
+pub fn load_preset(preset: &str, source: &str) -> Result<Vec<Query>> {
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct PresetFile {
+        pattern: Vec<QuerySpec>,
+    }
+    let file: PresetFile = toml::from_str(source).with_context(|| format!("Failed to parse preset {}", preset))?;
+    file.pattern.into_iter().map(|spec| Query::compile(QuerySpec { name: format!("{}/{}", preset, spec.name), ..spec })).collect()
+}
+
+// Per-query summary lines for stderr, then when queries have severities, the matches
+// and samples at each severity, most severe first
+pub fn display_summary(queries: &[Query], files: usize) {
+    for query in queries {
+        let matches = query.match_count();
+        let tag = query.severity.map(|severity| format!(" {}", severity.tag())).unwrap_or_default();
+        eprintln!("{}{}: {} matches in {} samples", query.name.bold(), tag, matches, query.samples_matched());
+        if matches == 0 {
+            eprintln!("  {} {}", "hint:".yellow().bold(), query.hint(files));
+        }
+    }
+    let by_severity = queries.iter().filter_map(|query| Some((query.severity?, query))).into_group_map();
+    let by_severity = by_severity.into_iter().collect::<BTreeMap<_, _>>();
+    if by_severity.is_empty() {
+        return;
+    }
+    eprintln!("{}", "By severity:".bold());
+    for (severity, queries) in by_severity.iter().rev() {
+        let matches = queries.iter().map(|query| query.match_count()).sum::<usize>();
+        let samples = queries.iter().flat_map(|query| query.matched_samples()).collect::<HashSet<_>>();
+        let matching = queries.iter().filter(|query| query.match_count() > 0).count();
+        eprintln!("  {} {} matches in {} samples ({} of {} patterns matched)", severity.tag(), matches, samples.len(), matching, queries.len());
+    }
+}
@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Take up to `wanted` from what's left of `max` in `used`, returning how much was taken
+fn take(used: &AtomicUsize, max: Option<usize>, wanted: usize) -> usize {
+    let Some(max) = max else {
+        used.fetch_add(wanted, Ordering::Relaxed);
+        return wanted;
+    };
+    match used.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| (used < max).then(|| used + wanted.min(max - used))) {
+        Ok(before) => wanted.min(max - before),
+        Err(_) => 0,
+    }
+}
+
+/// Caps on how many matching messages a search finds, per --max-results, --max-per-file and
+/// --max-per-sample. Workers claim each sample's matches as they read it, and stop reading
+/// once a cap leaves no room, rather than finding everything and showing the first few.
+#[derive(Debug, Default)]
+pub struct MatchQuota {
+    pub max_results: Option<usize>,
+    pub max_per_file: Option<usize>,
+    pub max_per_sample: Option<usize>,
+    found: AtomicUsize,
+}
+
+impl MatchQuota {
+    pub fn new(max_results: Option<usize>, max_per_file: Option<usize>, max_per_sample: Option<usize>) -> Self {
+        Self { max_results, max_per_file, max_per_sample, found: AtomicUsize::new(0) }
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        self.max_results.is_none() && self.max_per_file.is_none() && self.max_per_sample.is_none()
+    }
+
+    /// Whether --max-results matches have been found, so no more logs need reading.
+    pub fn is_met(&self) -> bool {
+        self.max_results.is_some_and(|max| self.found.load(Ordering::Relaxed) >= max)
+    }
+
+    /// The share of the quota for one log.
+    pub fn file(&self) -> FileQuota<'_> {
+        FileQuota { quota: self, found: AtomicUsize::new(0) }
+    }
+}
+
+/// A log's share of a [`MatchQuota`], claimed by the workers reading its samples.
+pub struct FileQuota<'a> {
+    quota: &'a MatchQuota,
+    found: AtomicUsize,
+}
+
+impl FileQuota<'_> {
+    /// Whether no more of the log's samples need reading.
+    pub fn is_met(&self) -> bool {
+        self.quota.is_met() || self.quota.max_per_file.is_some_and(|max| self.found.load(Ordering::Relaxed) >= max)
+    }
+
+    /// Count a sample's `matches` against every cap, returning how many of them fit.
+    pub fn claim(&self, matches: usize) -> usize {
+        let wanted = self.quota.max_per_sample.map_or(matches, |max| matches.min(max));
+        let for_file = take(&self.found, self.quota.max_per_file, wanted);
+        let granted = take(&self.quota.found, self.quota.max_results, for_file);
+        // What the log claimed but the search had no room for goes back
+        self.found.fetch_sub(for_file - granted, Ordering::Relaxed);
+        granted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claims_at_most_the_room_left() {
+        let quota = MatchQuota::new(Some(5), Some(3), Some(2));
+        let first = quota.file();
+        assert_eq!(first.claim(4), 2);
+        assert_eq!(first.claim(4), 1);
+        assert!(first.is_met());
+        assert_eq!(first.claim(1), 0);
+        // The second log has its own --max-per-file, but only 2 of --max-results are left
+        let second = quota.file();
+        assert_eq!(second.claim(2), 2);
+        assert!(quota.is_met());
+        assert_eq!(second.claim(1), 0);
+    }
+
+    #[test]
+    fn unlimited_takes_everything() {
+        let quota = MatchQuota::default();
+        assert!(quota.is_unlimited());
+        let file = quota.file();
+        assert_eq!(file.claim(1000), 1000);
+        assert!(!file.is_met());
+        assert!(!quota.is_met());
+    }
+
+    #[test]
+    fn gives_back_what_the_search_has_no_room_for() {
+        let quota = MatchQuota::new(Some(3), Some(10), None);
+        let file = quota.file();
+        assert_eq!(file.claim(5), 3);
+        // The 2 that didn't fit the search don't count against the log
+        assert_eq!(file.found.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn contended_claims_never_exceed_the_caps() {
+        let quota = MatchQuota::new(Some(100), Some(60), None);
+        let files = [quota.file(), quota.file()];
+        let granted = AtomicUsize::new(0);
+        std::thread::scope(|scope| {
+            for file in &files {
+                for _ in 0..4 {
+                    scope.spawn(|| {
+                        for _ in 0..50 {
+                            granted.fetch_add(file.claim(3), Ordering::Relaxed);
+                        }
+                    });
+                }
+            }
+        });
+        assert_eq!(granted.load(Ordering::Relaxed), 100);
+        assert_eq!(quota.found.load(Ordering::Relaxed), 100);
+        for file in &files {
+            assert!(file.found.load(Ordering::Relaxed) <= 60);
+        }
+        // Room given back by one log was taken up by the other, so the two account for every match
+        assert_eq!(files.iter().map(|file| file.found.load(Ordering::Relaxed)).sum::<usize>(), 100);
+    }
+}
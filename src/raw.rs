@@ -0,0 +1,49 @@
+use anyhow::Result;
+use colored::*;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+use crate::assembly::SampleEntries;
+use crate::log_format::open_log;
+use crate::theme::Theme;
+
+// A sample whose raw JSON matched, before any deserialization
+#[derive(Serialize, Deserialize)]
+pub struct RawMatch {
+    pub sample_id: String,
+    pub epoch: u32,
+    pub count: usize,
+    // The sample JSON, kept only when it's going to be dumped
+    pub json: Option<Vec<u8>>,
+}
+
+pub fn search_raw(log_path: &Path, samples: &[SampleEntries], regex: &Regex, keep_json: bool) -> Result<Vec<RawMatch>> {
+    let mut archive = open_log(log_path)?;
+
+    let mut matches = Vec::new();
+    for sample in samples {
+        let mut json = Vec::new();
+        sample.reader(&mut archive)?.read_to_end(&mut json)?;
+        let count = regex.find_iter(&json).count();
+        if count > 0 {
+            matches.push(RawMatch {
+                sample_id: sample.sample_id.clone(),
+                epoch: sample.epoch,
+                count,
+                json: keep_json.then_some(json),
+            });
+        }
+    }
+    Ok(matches)
+}
+
+pub fn display_raw_match(log_file: &Path, raw_match: &RawMatch, theme: &Theme) {
+    let hits = format!("[{} raw match{}]", raw_match.count, if raw_match.count == 1 { "" } else { "es" }).bold();
+    let header = theme.header(&log_file.file_name().unwrap().to_string_lossy(), &raw_match.sample_id, raw_match.epoch as i64, hits);
+    println!("{}", header);
+    if let Some(json) = &raw_match.json {
+        println!("{}\n", String::from_utf8_lossy(json));
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use colored::*;
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::inspect::ChatMessage;
+
+// A reference transcript is either a bare list of messages or any object
+// (e.g. a sample JSON) carrying a `messages` list.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ReferenceFile {
+    Messages(Vec<ChatMessage>),
+    Sample { messages: Vec<ChatMessage> },
+}
+
+pub fn load_reference(path: &Path) -> Result<Vec<ChatMessage>> {
+    let reader = std::fs::File::open(path)
+        .with_context(|| format!("Failed to open reference transcript {}", path.display()))?;
+    let reference: ReferenceFile = serde_json::from_reader(std::io::BufReader::new(reader))
+        .with_context(|| format!("Failed to parse reference transcript {}", path.display()))?;
+    Ok(match reference {
+        ReferenceFile::Messages(messages) => messages,
+        ReferenceFile::Sample { messages } => messages,
+    })
+}
+
+#[derive(Debug)]
+pub enum Divergence<'a> {
+    // Both transcripts have a message at `index` but they differ
+    Differs { index: usize, expected: &'a ChatMessage, actual: &'a ChatMessage },
+    // The sample ends before the reference does
+    Shorter { index: usize, expected: &'a ChatMessage },
+    // The sample continues after the reference ends
+    Longer { index: usize, actual: &'a ChatMessage },
+}
+
+// Transcripts are compared on what was said, not on incidental ids
+fn same_message(a: &ChatMessage, b: &ChatMessage) -> bool {
+    a.role == b.role && a.content == b.content
+}
+
+pub fn first_divergence<'a>(reference: &'a [ChatMessage], messages: &[&'a ChatMessage]) -> Option<Divergence<'a>> {
+    let mut index = 0;
+    loop {
+        match (reference.get(index), messages.get(index)) {
+            (Some(expected), Some(actual)) if !same_message(expected, actual) => {
+                return Some(Divergence::Differs { index, expected, actual })
+            }
+            (Some(expected), None) => return Some(Divergence::Shorter { index, expected }),
+            (None, Some(actual)) => return Some(Divergence::Longer { index, actual }),
+            (None, None) => return None,
+            _ => index += 1,
+        }
+    }
+}
+
+fn display_reference_message(label: ColoredString, message: &ChatMessage) {
+    println!("{} [{}]", label, message.role);
+    println!("{}", message.content);
+}
+
+pub fn display_divergence(source: (&Path, &str, i64), divergence: Option<&Divergence>) {
+    let (log_file, sample_id, epoch) = source;
+
+    let header = format!("{} sample {} epoch {}",
+        log_file.file_name().unwrap().to_string_lossy().cyan(),
+        sample_id.yellow(),
+        epoch.to_string().green(),
+    );
+
+    match divergence {
+        None => println!("\n{} | {}", header, "matches reference".green().bold()),
+        Some(Divergence::Differs { index, expected, actual }) => {
+            println!("\n{} | {}", header, format!("diverges at message {}", index).red().bold());
+            display_reference_message("-".red().bold(), expected);
+            display_reference_message("+".green().bold(), actual);
+        }
+        Some(Divergence::Shorter { index, expected }) => {
+            println!("\n{} | {}", header, format!("ends early at message {}", index).red().bold());
+            display_reference_message("-".red().bold(), expected);
+        }
+        Some(Divergence::Longer { index, actual }) => {
+            println!("\n{} | {}", header, format!("continues past reference at message {}", index).red().bold());
+            display_reference_message("+".green().bold(), actual);
+        }
+    }
+    println!();
+}
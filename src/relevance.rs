@@ -0,0 +1,64 @@
+use crate::inspect::{ChatMessage, EvalSample};
+use crate::message_pattern::MessagePattern;
+
+// BM25's saturation of repeated matches, and how much longer samples are discounted
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// How often each search pattern matched in a sample, and how much text it was
+/// searched in: what a sample's relevance is computed from, for --rank.
+#[derive(Debug, Clone, Default)]
+pub struct SampleTerms {
+    counts: Vec<usize>,
+    length: usize,
+}
+
+impl SampleTerms {
+    /// The matches of each pattern in a sample's matched messages, each pattern counted
+    /// only in the messages of its roles.
+    pub fn of_messages(pattern: &MessagePattern, matched: &[Option<&ChatMessage>]) -> Self {
+        let messages = matched.iter().flatten().collect::<Vec<_>>();
+        let counts = pattern
+            .terms()
+            .map(|(regex, roles)| messages.iter().filter(|message| roles.contains(&message.role)).map(|message| regex.find_iter(&message.content).count()).sum())
+            .collect();
+        Self { counts, length: messages.iter().map(|message| message.content.len()).sum() }
+    }
+
+    /// The matches of each pattern in a streamed sample's snippets, which have no roles.
+    pub fn of_snippets(pattern: &MessagePattern, snippets: &[String]) -> Self {
+        let counts = pattern.terms().map(|(regex, _)| snippets.iter().map(|snippet| regex.find_iter(snippet).count()).sum()).collect();
+        Self { counts, length: snippets.iter().map(String::len).sum() }
+    }
+}
+
+/// The terms of a sample as the search kept it: its streamed snippets, or the messages
+/// that matched (`matched`, when only some of its messages did).
+pub fn sample_terms(pattern: &MessagePattern, sample: &EvalSample, matched: &[Option<&ChatMessage>]) -> SampleTerms {
+    match &sample.streamed {
+        Some(streamed) => SampleTerms::of_snippets(pattern, &streamed.snippets),
+        None => SampleTerms::of_messages(pattern, matched),
+    }
+}
+
+/// A BM25 score for each sample: every pattern is a term, weighted by how few of the
+/// samples it matched in, with repeated matches counting for less and less and long
+/// samples discounted. Scores compare samples within one search only.
+pub fn scores(samples: &[SampleTerms]) -> Vec<f64> {
+    let total = samples.len() as f64;
+    let average_length = (samples.iter().map(|sample| sample.length).sum::<usize>() as f64 / total).max(1.0);
+    let terms = samples.iter().map(|sample| sample.counts.len()).max().unwrap_or_default();
+    let idf = (0..terms)
+        .map(|term| {
+            let containing = samples.iter().filter(|sample| sample.counts.get(term).is_some_and(|count| *count > 0)).count() as f64;
+            ((total - containing + 0.5) / (containing + 0.5)).ln_1p()
+        })
+        .collect::<Vec<_>>();
+    samples
+        .iter()
+        .map(|sample| {
+            let norm = K1 * (1.0 - B + B * sample.length as f64 / average_length);
+            sample.counts.iter().zip(&idf).map(|(count, idf)| idf * (*count as f64 * (K1 + 1.0)) / (*count as f64 + norm)).sum()
+        })
+        .collect()
+}
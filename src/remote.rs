@@ -0,0 +1,197 @@
+use anyhow::{bail, Context, Result};
+use aws_sdk_s3::error::DisplayErrorContext;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+// Bytes fetched per range request: enough that the central directory at the end of a
+// log usually comes in one, without fetching much of a sample that isn't read
+const BLOCK: u64 = 1 << 20;
+// Blocks kept for each open object
+const CACHED_BLOCKS: usize = 16;
+
+// Where a remote log lives, from the URL given as its path
+enum Location {
+    S3 { bucket: String, key: String },
+    Http(String),
+}
+
+impl Location {
+    fn parse(path: &Path) -> Option<Self> {
+        let url = path.to_str()?;
+        if let Some(rest) = url.strip_prefix("s3://") {
+            let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+            return Some(Location::S3 { bucket: bucket.to_string(), key: key.to_string() });
+        }
+        (url.starts_with("http://") || url.starts_with("https://")).then(|| Location::Http(url.to_string()))
+    }
+}
+
+/// Whether a log path is an `s3://` or `http(s)://` URL rather than a local path.
+pub fn is_remote(path: &Path) -> bool {
+    Location::parse(path).is_some()
+}
+
+// The SDK is async; every request blocks the worker making it on this runtime
+fn runtime() -> &'static tokio::runtime::Runtime {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| tokio::runtime::Builder::new_multi_thread().worker_threads(2).enable_all().build().expect("Failed to start the S3 client's runtime"))
+}
+
+// Credentials and region come from the standard AWS chain (environment, profile, SSO,
+// instance metadata), as the AWS CLI finds them
+fn s3() -> &'static aws_sdk_s3::Client {
+    static CLIENT: OnceLock<aws_sdk_s3::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| aws_sdk_s3::Client::new(&runtime().block_on(aws_config::load_from_env())))
+}
+
+fn is_log_name(key: &str, include_json: bool) -> bool {
+    key.ends_with(".eval") || (include_json && key.ends_with(".json"))
+}
+
+/// The logs at a remote location: the object itself when the URL names a log, or else
+/// every `.eval` object (and `.json` log, with `include_json`) under an `s3://` prefix.
+/// HTTP(S) servers can't be listed, so their URLs must name a log.
+pub fn list(path: &Path, include_json: bool) -> Result<Vec<PathBuf>> {
+    match Location::parse(path) {
+        Some(Location::S3 { key, .. }) | Some(Location::Http(key)) if is_log_name(&key, true) => Ok(vec![path.to_path_buf()]),
+        Some(Location::S3 { bucket, key }) => {
+            let prefix = match key.is_empty() || key.ends_with('/') {
+                true => key,
+                false => format!("{}/", key),
+            };
+            let pages = runtime().block_on(s3().list_objects_v2().bucket(&bucket).prefix(&prefix).into_paginator().send().collect::<Vec<_>>());
+            let mut logs = Vec::new();
+            for page in pages {
+                let page = page.map_err(|e| anyhow::anyhow!("{}", DisplayErrorContext(e))).with_context(|| format!("Failed to list s3://{}/{}", bucket, prefix))?;
+                let keys = page.contents().iter().filter_map(|object| object.key()).filter(|key| is_log_name(key, include_json));
+                logs.extend(keys.map(|key| PathBuf::from(format!("s3://{}/{}", bucket, key))));
+            }
+            // Sorted like a local walk; JSON objects that aren't logs are dropped like local ones
+            logs.sort();
+            logs.retain(|log| !log.to_string_lossy().ends_with(".json") || crate::log_format::is_json_log(log));
+            Ok(logs)
+        }
+        Some(Location::Http(url)) => bail!("{} doesn't name a log, and HTTP(S) locations can't be listed (give each log's URL, e.g. with --files-from)", url),
+        None => bail!("{} is not an s3:// or http(s):// URL", path.display()),
+    }
+}
+
+fn io_error(e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::other(e.to_string())
+}
+
+/// A remote log, read with range requests a block at a time so that opening it only
+/// fetches the zip's central directory and reading a sample only fetches its entry.
+/// When the server ignores ranges the whole object is fetched once instead.
+pub struct RemoteObject {
+    location: Location,
+    len: u64,
+    // The whole object, when it couldn't be fetched by range
+    whole: Option<Arc<[u8]>>,
+    blocks: Mutex<VecDeque<(u64, Arc<[u8]>)>>,
+}
+
+impl RemoteObject {
+    fn open(path: &Path) -> Result<Self> {
+        let Some(location) = Location::parse(path) else { bail!("{} is not an s3:// or http(s):// URL", path.display()) };
+        let (len, whole) = match &location {
+            Location::S3 { bucket, key } => {
+                let head = runtime().block_on(s3().head_object().bucket(bucket).key(key).send()).map_err(|e| anyhow::anyhow!("{}", DisplayErrorContext(e)))?;
+                (head.content_length().unwrap_or_default().max(0) as u64, None)
+            }
+            Location::Http(url) => {
+                let head = ureq::head(url).call()?;
+                let header = |name: &str| head.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+                let len = header("content-length").and_then(|len| len.parse().ok());
+                match (len, header("accept-ranges").as_deref()) {
+                    (Some(len), Some("bytes")) => (len, None),
+                    _ => {
+                        let body: Arc<[u8]> = ureq::get(url).call()?.body_mut().with_config().limit(u64::MAX).read_to_vec()?.into();
+                        (body.len() as u64, Some(body))
+                    }
+                }
+            }
+        };
+        Ok(Self { location, len, whole, blocks: Mutex::default() })
+    }
+
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Bytes `start..end` of the object
+    fn fetch(&self, start: u64, end: u64) -> std::io::Result<Vec<u8>> {
+        let range = format!("bytes={}-{}", start, end - 1);
+        match &self.location {
+            Location::S3 { bucket, key } => runtime().block_on(async {
+                let object = s3().get_object().bucket(bucket).key(key).range(range).send().await.map_err(|e| io_error(DisplayErrorContext(e)))?;
+                Ok(object.body.collect().await.map_err(io_error)?.into_bytes().to_vec())
+            }),
+            Location::Http(url) => {
+                let mut response = ureq::get(url).header("Range", &range).call().map_err(io_error)?;
+                let partial = response.status() == 206;
+                let body = response.body_mut().with_config().limit(u64::MAX).read_to_vec().map_err(io_error)?;
+                match partial {
+                    true => Ok(body),
+                    // The server sent everything after all
+                    false => Ok(body.get(start as usize..end as usize).unwrap_or_default().to_vec()),
+                }
+            }
+        }
+    }
+
+    fn block(&self, index: u64) -> std::io::Result<Arc<[u8]>> {
+        if let Some((_, block)) = self.blocks.lock().unwrap().iter().find(|(cached, _)| *cached == index) {
+            return Ok(block.clone());
+        }
+        let start = index * BLOCK;
+        let block: Arc<[u8]> = self.fetch(start, (start + BLOCK).min(self.len))?.into();
+        crate::resources::add_read(block.len());
+        let mut blocks = self.blocks.lock().unwrap();
+        if blocks.len() >= CACHED_BLOCKS {
+            blocks.pop_front();
+        }
+        blocks.push_back((index, block.clone()));
+        Ok(block)
+    }
+
+    /// Read into `buf` from `offset`, up to the end of the block it falls in.
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+        if offset >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(whole) = &self.whole {
+            let read = buf.len().min(whole.len() - offset as usize);
+            buf[..read].copy_from_slice(&whole[offset as usize..offset as usize + read]);
+            return Ok(read);
+        }
+        let block = self.block(offset / BLOCK)?;
+        let within = (offset % BLOCK) as usize;
+        let read = buf.len().min(block.len().saturating_sub(within));
+        buf[..read].copy_from_slice(&block[within..within + read]);
+        Ok(read)
+    }
+}
+
+// A log is opened several times over a scan (to list its samples, read its header and
+// then its samples), so recently opened objects are kept with the blocks they fetched
+static OPEN: Mutex<VecDeque<(PathBuf, Arc<RemoteObject>)>> = Mutex::new(VecDeque::new());
+
+/// Open a remote log for reading, sharing an object opened recently.
+pub fn open(path: &Path) -> Result<Arc<RemoteObject>> {
+    if let Some((_, object)) = OPEN.lock().unwrap().iter().find(|(open, _)| open == path) {
+        return Ok(object.clone());
+    }
+    let object = Arc::new(RemoteObject::open(path).with_context(|| format!("Failed to open {}", path.display()))?);
+    let mut open = OPEN.lock().unwrap();
+    if open.len() >= rayon::current_num_threads().max(4) {
+        open.pop_front();
+    }
+    open.push_back((path.to_path_buf(), object.clone()));
+    Ok(object)
+}
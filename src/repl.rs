@@ -0,0 +1,195 @@
+use anyhow::Result;
+use colored::*;
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::assembly::SampleEntries;
+use crate::counters::ScanCounters;
+use crate::filter_set::{FilterSet, RoleSet};
+use crate::inspect::{ChatMessage, ChatMessageRole, ReadOptions};
+use crate::message_pattern::{MessagePattern, PatternSyntax};
+use crate::theme::Theme;
+
+const HELP: &str = "\
+Enter a pattern to search the loaded samples' messages, or a command:
+  :roles <roles>   search only messages with these roles (comma-separated; none for all)
+  :count           toggle printing match counts per sample instead of messages
+  :limit <n>       print at most n matches per query (none for no limit)
+  :info            what's loaded, and how much of it is held in memory
+  :help            this help
+  :quit            leave (as does end of input)";
+
+// A sample loaded into the session: always what's needed to find it again, and its
+// messages when they fit under the memory cap
+struct Loaded {
+    log: usize,
+    entries: SampleEntries,
+    id: String,
+    epoch: i64,
+    messages: Option<Vec<Option<ChatMessage>>>,
+}
+
+// Bytes of message text a sample holds, which dominates what keeping it costs
+fn message_bytes(messages: &[Option<ChatMessage>]) -> u64 {
+    messages.iter().flatten().map(|message| message.content.len() as u64).sum()
+}
+
+/// Samples read once from a fixed set of logs and searched again for each query typed
+/// at the prompt, so refining a pattern doesn't re-read the archives. Messages are kept
+/// in memory up to a cap; samples past it are re-read when searched.
+pub struct Session {
+    logs: Vec<PathBuf>,
+    samples: Vec<Loaded>,
+    options: ReadOptions,
+    cached_bytes: u64,
+    // Sample-level filters, and the message filters other than the pattern, from the command line
+    filters: FilterSet,
+    syntax: PatternSyntax,
+    count_only: bool,
+    limit: Option<usize>,
+}
+
+impl Session {
+    /// Read the given samples of each log, keeping those `filters` select by score and
+    /// the messages of as many of them as fit in `memory_cap` bytes.
+    pub fn load(logs: Vec<(PathBuf, Vec<SampleEntries>)>, options: ReadOptions, filters: FilterSet, syntax: PatternSyntax, memory_cap: u64) -> Self {
+        let counters = ScanCounters::default();
+        let read = logs
+            .par_iter()
+            .map(|(path, entries)| {
+                let mut samples = crate::read_whole_samples(path, entries, options);
+                filters.retain(&mut samples, &counters);
+                samples
+            })
+            .collect::<Vec<_>>();
+        let mut session = Session { logs: Vec::new(), samples: Vec::new(), options, cached_bytes: 0, filters, syntax, count_only: false, limit: None };
+        for (log, ((path, entries), samples)) in logs.into_iter().zip(read).enumerate() {
+            session.logs.push(path);
+            for sample in samples {
+                let Some(entries) = entries.iter().find(|entries| entries.name() == sample.entry) else { continue };
+                let bytes = message_bytes(&sample.messages);
+                let messages = (session.cached_bytes + bytes <= memory_cap).then(|| {
+                    session.cached_bytes += bytes;
+                    sample.messages
+                });
+                session.samples.push(Loaded { log, entries: entries.clone(), id: sample.id, epoch: sample.epoch, messages });
+            }
+        }
+        session
+    }
+
+    // What's loaded, on stderr
+    fn info(&self) {
+        let cached = self.samples.iter().filter(|sample| sample.messages.is_some()).count();
+        eprintln!(
+            "{} samples from {} logs; messages of {} held in memory ({} MiB), {} re-read per query",
+            self.samples.len(),
+            self.logs.len(),
+            cached,
+            self.cached_bytes >> 20,
+            self.samples.len() - cached
+        );
+    }
+
+    fn messages<'a>(&self, sample: &'a Loaded) -> Option<Cow<'a, [Option<ChatMessage>]>> {
+        if let Some(messages) = &sample.messages {
+            return Some(Cow::Borrowed(messages));
+        }
+        let read = crate::read_sample_filtered(&self.logs[sample.log], &sample.entries, self.options, |_| true);
+        crate::skipped::or_skip(read, format_args!("{}: {}", self.logs[sample.log].display(), sample.entries.name())).map(|sample| Cow::Owned(sample.messages))
+    }
+
+    // Search every loaded sample for the pattern and print the matches in load order
+    fn query(&self, pattern: &str, theme: &Theme) -> Result<()> {
+        let started = Instant::now();
+        let filters = FilterSet { pattern: MessagePattern::new(&[self.syntax.apply(pattern)], false, false)?, ..self.filters.clone() };
+        let counters = ScanCounters::default();
+        let found = self
+            .samples
+            .par_iter()
+            .filter(|_| !crate::interrupt::interrupted())
+            .filter_map(|sample| {
+                let messages = self.messages(sample)?;
+                let matched = filters.matched(&messages, &counters).into_iter().flatten().cloned().collect::<Vec<_>>();
+                (!matched.is_empty()).then_some((sample, matched))
+            })
+            .collect::<Vec<_>>();
+
+        let highlight = filters.pattern.as_ref().map(MessagePattern::regex);
+        let mut shown = 0;
+        for (sample, matched) in &found {
+            let path = &self.logs[sample.log];
+            if self.count_only {
+                println!("{}:{}:{}:{}", path.display(), sample.id, sample.epoch, matched.len());
+                continue;
+            }
+            for message in matched {
+                if self.limit.is_some_and(|limit| shown >= limit) {
+                    break;
+                }
+                crate::display_message((path, &sample.id, sample.epoch), message, None, None, highlight, filters.tool_calls.as_ref(), None, theme);
+                shown += 1;
+            }
+        }
+        let matches = found.iter().map(|(_, matched)| matched.len()).sum::<usize>();
+        let interrupted = if crate::interrupt::interrupted() { " (interrupted)".red().bold().to_string() } else { String::new() };
+        eprintln!("{} matches in {} samples ({}){}", matches, found.len(), crate::notify::format_elapsed(started.elapsed()), interrupted);
+        Ok(())
+    }
+
+    // A `:` command; false to leave
+    fn command(&mut self, command: &str) -> Result<bool> {
+        let (name, argument) = command.split_once(' ').map_or((command, ""), |(name, argument)| (name, argument.trim()));
+        match name {
+            "quit" | "q" | "exit" => return Ok(false),
+            "help" | "h" => eprintln!("{}", HELP),
+            "info" => self.info(),
+            "count" => {
+                self.count_only = !self.count_only;
+                eprintln!("printing {}", if self.count_only { "match counts" } else { "messages" });
+            }
+            "limit" => self.limit = (!argument.is_empty() && argument != "none").then(|| argument.parse()).transpose()?,
+            "roles" => {
+                let roles = argument.split(',').filter(|role| !role.trim().is_empty()).map(|role| clap::ValueEnum::from_str(role.trim(), true)).collect::<Result<Vec<ChatMessageRole>, _>>();
+                self.filters.roles = RoleSet::of(&roles.map_err(|e| anyhow::anyhow!(e))?);
+            }
+            _ => eprintln!("{} unknown command :{} (:help lists them)", "error:".red().bold(), name),
+        }
+        Ok(true)
+    }
+
+    /// Read queries from stdin, one per line, until `:quit` or the end of input. A
+    /// query interrupted with Ctrl-C stops without ending the session.
+    pub fn run(mut self, theme: &Theme) -> Result<()> {
+        let interactive = std::io::stdin().is_terminal();
+        self.info();
+        if interactive {
+            eprintln!("{}", "Type a pattern to search, or :help".dimmed());
+        }
+        let mut lines = std::io::stdin().lock().lines();
+        loop {
+            if interactive {
+                eprint!("{} ", ">".bold());
+                std::io::stderr().flush()?;
+            }
+            let Some(line) = lines.next().transpose()? else { break };
+            let line = line.trim();
+            // A Ctrl-C at the prompt, or one that stopped the last query, doesn't carry over
+            crate::interrupt::reset();
+            let outcome = match line.strip_prefix(':') {
+                _ if line.is_empty() => Ok(true),
+                Some(command) => self.command(command),
+                None => self.query(line, theme).map(|_| true),
+            };
+            match outcome {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => eprintln!("{} {:#}", "error:".red().bold(), e),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use serde_json::Value;
+use std::path::Path;
+
+// Header config fields passed back to `inspect eval` as the options of the same name
+const LIMIT_OPTIONS: &[(&str, &str)] = &[("message_limit", "--message-limit"), ("token_limit", "--token-limit"), ("time_limit", "--time-limit")];
+
+// A word as a POSIX shell reads it back, quoted only when it has to be
+fn shell_quote(word: &str) -> String {
+    let plain = !word.is_empty() && word.chars().all(|c| c.is_ascii_alphanumeric() || "_-./:=@%+,".contains(c));
+    match plain {
+        true => word.to_string(),
+        false => format!("'{}'", word.replace('\'', r"'\''")),
+    }
+}
+
+// `key=value` for -T and -M, with values other than strings written as JSON, which
+// Inspect reads back as YAML
+fn key_values(args: &Value) -> Vec<String> {
+    let Some(args) = args.as_object() else { return Vec::new() };
+    args.iter()
+        .map(|(key, value)| match value {
+            Value::String(value) => format!("{}={}", key, value),
+            value => format!("{}={}", key, value),
+        })
+        .collect()
+}
+
+// How a log's task was run: its `inspect eval` command line, short of the sample to run
+pub struct ReproCommand {
+    words: Vec<String>,
+}
+
+impl ReproCommand {
+    // None when the log has no header to read the task from
+    pub fn of_log(log_path: &Path) -> Option<Self> {
+        let header = crate::log_index::header(log_path).ok().flatten()?;
+        let eval = &header["eval"];
+        let task = eval["task"].as_str()?;
+        // A task defined in a file is named `file.py@task`; a registered one by its registry name
+        let target = match (eval["task_file"].as_str(), eval["task_registry_name"].as_str()) {
+            (Some(file), _) => format!("{}@{}", file, task),
+            (None, Some(name)) => name.to_string(),
+            (None, None) => task.to_string(),
+        };
+        let mut words = vec!["inspect".to_string(), "eval".to_string(), target];
+        if let Some(model) = eval["model"].as_str() {
+            words.extend(["--model".to_string(), model.to_string()]);
+        }
+        for arg in key_values(&eval["task_args"]) {
+            words.extend(["-T".to_string(), arg]);
+        }
+        for arg in key_values(&eval["model_args"]) {
+            words.extend(["-M".to_string(), arg]);
+        }
+        for (field, option) in LIMIT_OPTIONS {
+            if let Some(limit) = eval["config"][field].as_u64() {
+                words.extend([option.to_string(), limit.to_string()]);
+            }
+        }
+        Some(Self { words })
+    }
+
+    // The command re-running just this sample, once
+    pub fn for_sample(&self, sample_id: &str) -> String {
+        let sample = ["--sample-id", sample_id, "--epochs", "1"].map(str::to_string);
+        self.words.iter().chain(&sample).map(|word| shell_quote(word)).collect::<Vec<_>>().join(" ")
+    }
+}
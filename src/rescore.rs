@@ -0,0 +1,85 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+use crate::inspect::{ChatMessage, Score};
+use crate::theme::Theme;
+
+// The chat-json representation of a sample handed to external commands
+#[derive(Serialize)]
+pub struct ChatJson<'a> {
+    pub log: String,
+    pub id: &'a str,
+    pub epoch: i64,
+    pub messages: &'a [&'a ChatMessage],
+    pub scores: &'a HashMap<String, Score>,
+}
+
+// Pipe a sample to the grader command through `sh -c` and return its trimmed stdout
+pub fn rescore(command: &str, sample: &ChatJson) -> Result<String> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run rescore command '{}'", command))?;
+
+    // Write from a separate thread so a grader that replies before reading all of stdin can't deadlock
+    let input = serde_json::to_vec(sample)?;
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    // A grader that exits without reading everything isn't an error
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.trim() {
+            "" => bail!("{}", output.status),
+            stderr => bail!("{} ({})", stderr, output.status),
+        }
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[derive(Serialize)]
+struct Label<'a> {
+    log: &'a str,
+    sample_id: &'a str,
+    epoch: i64,
+    verdict: &'a str,
+}
+
+// JSONL sidecar recording a verdict per rescored sample
+pub struct LabelsFile(Mutex<File>);
+
+impl LabelsFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create labels file {}", path.display()))?;
+        Ok(Self(Mutex::new(file)))
+    }
+
+    pub fn record(&self, sample: &ChatJson, verdict: &str) -> Result<()> {
+        let label = Label { log: &sample.log, sample_id: sample.id, epoch: sample.epoch, verdict };
+        let mut line = serde_json::to_vec(&label)?;
+        line.push(b'\n');
+        self.0.lock().unwrap().write_all(&line)?;
+        Ok(())
+    }
+}
+
+pub fn display_verdict(log_file: &Path, sample: &ChatJson, verdict: &Result<String>, theme: &Theme) {
+    let verdict = match verdict {
+        Ok(verdict) => format!("[verdict: {}]", verdict).bold(),
+        Err(e) => format!("[rescore failed: {}]", e).red().bold(),
+    };
+    println!("{}", theme.header(&log_file.file_name().unwrap().to_string_lossy(), sample.id, sample.epoch, verdict));
+}
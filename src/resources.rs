@@ -0,0 +1,97 @@
+use colored::*;
+use std::io::Read;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// What a run cost, for --resource-report: bytes read from logs on disk or fetched, bytes inflated
+// from their entries, and samples parsed, counted wherever logs are read
+static BYTES_READ: AtomicU64 = AtomicU64::new(0);
+static BYTES_DECOMPRESSED: AtomicU64 = AtomicU64::new(0);
+static SAMPLES_PARSED: AtomicU64 = AtomicU64::new(0);
+
+// A reader adding what passes through it to one of the totals
+pub struct Counted<R> {
+    inner: R,
+    total: &'static AtomicU64,
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.total.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+// `reader` over an entry of a log, counting the bytes it inflates to
+pub fn decompressing<R: Read>(reader: R) -> Counted<R> {
+    Counted { inner: reader, total: &BYTES_DECOMPRESSED }
+}
+
+pub fn add_read(bytes: usize) {
+    BYTES_READ.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+pub fn add_sample_parsed() {
+    SAMPLES_PARSED.fetch_add(1, Ordering::Relaxed);
+}
+
+struct Usage {
+    peak_rss: u64,
+    user: Duration,
+    system: Duration,
+}
+
+// Peak resident set size and CPU time of the process so far
+#[cfg(unix)]
+fn usage() -> Option<Usage> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let usage = unsafe { usage.assume_init() };
+    let time = |time: libc::timeval| Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000);
+    // macOS reports the peak in bytes, other systems in KiB
+    let peak_rss = match cfg!(target_os = "macos") {
+        true => usage.ru_maxrss as u64,
+        false => usage.ru_maxrss as u64 * 1024,
+    };
+    Some(Usage { peak_rss, user: time(usage.ru_utime), system: time(usage.ru_stime) })
+}
+
+#[cfg(not(unix))]
+fn usage() -> Option<Usage> {
+    None
+}
+
+fn bytes(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..0x10_0000 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        0x10_0000..0x4000_0000 => format!("{:.1} MiB", bytes as f64 / 0x10_0000 as f64),
+        _ => format!("{:.2} GiB", bytes as f64 / 0x4000_0000 as f64),
+    }
+}
+
+// The totals on stderr, once the run is over
+pub fn report() {
+    let read = BYTES_READ.load(Ordering::Relaxed);
+    let decompressed = BYTES_DECOMPRESSED.load(Ordering::Relaxed);
+    eprintln!("\n{}", "Resource usage".bold());
+    let row = |label: &str, value: String| eprintln!("  {:<20}{}", label, value);
+    match usage() {
+        Some(usage) => {
+            row("peak RSS", bytes(usage.peak_rss));
+            let cpu = usage.user + usage.system;
+            row("CPU time", format!("{:.2}s ({:.2}s user, {:.2}s system)", cpu.as_secs_f64(), usage.user.as_secs_f64(), usage.system.as_secs_f64()));
+        }
+        None => row("peak RSS, CPU time", "unavailable on this platform".dimmed().to_string()),
+    }
+    row("bytes read", bytes(read));
+    let ratio = match read {
+        0 => String::new(),
+        _ => format!(" ({:.1}x what was read)", decompressed as f64 / read as f64),
+    };
+    row("bytes decompressed", format!("{}{}", bytes(decompressed), ratio));
+    row("samples parsed", SAMPLES_PARSED.load(Ordering::Relaxed).to_string());
+}
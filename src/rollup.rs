@@ -0,0 +1,72 @@
+use colored::*;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct DirTally {
+    // Logs under the directory, at any depth
+    logs: usize,
+    matched_logs: HashSet<PathBuf>,
+    samples: usize,
+    matches: usize,
+}
+
+// Matches totalled for each directory of a recursive scan and all of its ancestors up to
+// the scanned root, for --rollup
+pub struct Rollup {
+    root: PathBuf,
+    dirs: Mutex<BTreeMap<PathBuf, DirTally>>,
+}
+
+impl Rollup {
+    pub fn new(root: &Path, logs: &[PathBuf]) -> Self {
+        let root = if root.is_dir() { root } else { root.parent().unwrap_or(Path::new("")) };
+        let rollup = Rollup { root: root.to_path_buf(), dirs: Mutex::default() };
+        for log in logs {
+            rollup.update(log, |tally| tally.logs += 1);
+        }
+        rollup
+    }
+
+    // Apply `f` to the tally of each directory from the log's own up to the root
+    fn update(&self, log_path: &Path, f: impl Fn(&mut DirTally)) {
+        let dir = log_path.parent().unwrap_or(Path::new(""));
+        let dir = dir.strip_prefix(&self.root).unwrap_or(dir);
+        let mut dirs = self.dirs.lock().unwrap();
+        for ancestor in dir.ancestors() {
+            f(dirs.entry(ancestor.to_path_buf()).or_default());
+        }
+    }
+
+    // Record a matching sample in `log_path`
+    pub fn add(&self, log_path: &Path, matches: usize) {
+        self.update(log_path, |tally| {
+            tally.matched_logs.insert(log_path.to_path_buf());
+            tally.samples += 1;
+            tally.matches += matches;
+        });
+    }
+
+    // One row per directory, indented under its parent, on stderr with the other totals
+    pub fn display(&self) {
+        let dirs = self.dirs.lock().unwrap();
+        let label = |dir: &Path| match dir.file_name() {
+            Some(name) => format!("{}{}/", "  ".repeat(dir.components().count()), name.to_string_lossy()),
+            None => match self.root.display().to_string() {
+                root if root.ends_with('/') => root,
+                root => format!("{}/", root),
+            },
+        };
+        let width = dirs.keys().map(|dir| label(dir).chars().count()).max().unwrap_or_default().max("directory".len());
+        eprintln!("\n{}", format!("{:<width$}  {:>9}  {:>8}  {:>8}", "directory", "logs", "samples", "matches").bold());
+        for (dir, tally) in dirs.iter() {
+            let logs = format!("{}/{}", tally.matched_logs.len(), tally.logs);
+            let row = format!("{:<width$}  {:>9}  {:>8}  {:>8}", label(dir), logs, tally.samples, tally.matches);
+            match tally.matches {
+                0 => eprintln!("{}", row.dimmed()),
+                _ => eprintln!("{}", row),
+            }
+        }
+    }
+}
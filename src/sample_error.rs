@@ -0,0 +1,18 @@
+use colored::*;
+use std::path::Path;
+
+use crate::inspect::SampleError;
+use crate::theme::Theme;
+
+// The error that ended a sample, under its own header after the sample's matches:
+// the message, then the traceback dimmed
+pub fn display(source: (&Path, &str, i64), error: &SampleError, theme: &Theme) {
+    let (log_file, sample_id, epoch) = source;
+    let marker = "[error]".red().bold();
+    println!("\n{}", theme.header(&log_file.file_name().unwrap().to_string_lossy(), sample_id, epoch, marker));
+    println!("{}", error.message.red());
+    let traceback = error.traceback.trim_end();
+    if !traceback.is_empty() {
+        println!("{}", traceback.dimmed());
+    }
+}
@@ -0,0 +1,82 @@
+use anyhow::{anyhow, bail};
+use regex::Regex;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::counters::ScanCounters;
+use crate::inspect::{EvalSample, Score};
+
+// `scorer=value`: the sample's score from that scorer is the value, compared as text
+// (C, I, "yes") or, when the value is a number, numerically (1 also matches C)
+#[derive(Debug, Clone)]
+pub struct ScoreFilter {
+    scorer: String,
+    value: String,
+}
+
+impl FromStr for ScoreFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scorer, value) = s.split_once('=').ok_or_else(|| anyhow!("expected SCORER=VALUE, e.g. match=C"))?;
+        if scorer.is_empty() {
+            bail!("missing scorer name in '{}'", s);
+        }
+        Ok(Self { scorer: scorer.to_string(), value: value.to_string() })
+    }
+}
+
+impl ScoreFilter {
+    fn matches(&self, scores: &HashMap<String, Score>) -> bool {
+        let Some(score) = scores.get(&self.scorer) else { return false };
+        let text = match &score.value {
+            serde_json::Value::String(s) => s.clone(),
+            value => value.to_string(),
+        };
+        text == self.value || self.value.parse::<f64>().is_ok_and(|value| score.as_f64() == Some(value))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Correctness {
+    // Every scorer gave full marks
+    Correct,
+    // Some scorer gave less than full marks
+    Incorrect,
+}
+
+// Which samples to keep by their scores; samples without scores only pass when nothing is asked of them
+#[derive(Debug, Clone, Default)]
+pub struct ScoreSelection {
+    pub filters: Vec<ScoreFilter>,
+    pub correctness: Option<Correctness>,
+    // Some scorer's explanation of its score matches
+    pub explanation: Option<Regex>,
+}
+
+impl ScoreSelection {
+    fn selects(&self, scores: &HashMap<String, Score>) -> bool {
+        let full_marks = |score: &Score| score.as_f64() == Some(1.0);
+        let correctness = match self.correctness {
+            None => true,
+            Some(_) if scores.is_empty() => false,
+            Some(Correctness::Correct) => scores.values().all(full_marks),
+            Some(Correctness::Incorrect) => !scores.values().all(full_marks),
+        };
+        let explained = self.explanation.as_ref().is_none_or(|regex| {
+            scores.values().filter_map(|score| score.explanation.as_deref()).any(|explanation| regex.is_match(explanation))
+        });
+        correctness && explained && self.filters.iter().all(|filter| filter.matches(scores))
+    }
+
+    // Keep the samples selected, counting the rest for the empty-result hint.
+    // Streamed samples were never parsed, so their scores are unknown and they're kept
+    pub fn retain(&self, samples: &mut Vec<EvalSample>, counters: &ScanCounters) {
+        if self.filters.is_empty() && self.correctness.is_none() && self.explanation.is_none() {
+            return;
+        }
+        let before = samples.len();
+        samples.retain(|sample| sample.streamed.is_some() || self.selects(&sample.scores));
+        ScanCounters::add(&counters.samples_outside_scores, before - samples.len());
+    }
+}
@@ -0,0 +1,98 @@
+use anyhow::Result;
+use colored::*;
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+
+use crate::inspect::{EvalLogHeader, EvalSampleReductions};
+use crate::skipped;
+
+// One row of the score table: a scorer's metrics within a single log
+pub struct ScoreRow {
+    pub file: String,
+    pub task: String,
+    pub model: String,
+    pub scorer: String,
+    pub samples: String,
+    pub metrics: Vec<(String, f64)>,
+}
+
+pub fn score_rows(log_path: &Path) -> Result<Vec<ScoreRow>> {
+    let header: EvalLogHeader = crate::read_json_entry(log_path, "header.json")?
+        .ok_or_else(|| anyhow::anyhow!("{} has no header.json", log_path.display()))?;
+    let file = log_path.file_name().unwrap().to_string_lossy().to_string();
+    let row = |scorer: String, samples: String, metrics: Vec<(String, f64)>| ScoreRow {
+        file: file.clone(),
+        task: header.eval.task.clone(),
+        model: header.eval.model.clone(),
+        scorer,
+        samples,
+        metrics,
+    };
+
+    // Prefer the metrics Inspect computed; logs from interrupted runs only have reductions
+    if let Some(results) = header.results.as_ref().filter(|r| !r.scores.is_empty()) {
+        let samples = format!("{}/{}", results.completed_samples, results.total_samples);
+        return Ok(results
+            .scores
+            .iter()
+            .map(|score| {
+                let metrics = score
+                    .metrics
+                    .iter()
+                    .filter_map(|(name, metric)| Some((name.clone(), metric.get("value")?.as_f64()?)))
+                    .collect();
+                row(score.name.clone(), samples.clone(), metrics)
+            })
+            .collect());
+    }
+
+    let reductions: Vec<EvalSampleReductions> = crate::read_json_entry(log_path, "reductions.json")?.unwrap_or_default();
+    Ok(reductions
+        .iter()
+        .map(|reduction| {
+            let values = reduction.samples.iter().filter_map(|s| s.score.as_f64()).collect::<Vec<_>>();
+            row(reduction.scorer.clone(), values.len().to_string(), mean_and_stderr(&values))
+        })
+        .collect())
+}
+
+fn mean_and_stderr(values: &[f64]) -> Vec<(String, f64)> {
+    if values.is_empty() {
+        return vec![];
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let mut metrics = vec![("mean".to_string(), mean)];
+    if values.len() > 1 {
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        metrics.push(("stderr".to_string(), (variance / n).sqrt()));
+    }
+    metrics
+}
+
+pub fn display_score_table(rows: &[ScoreRow]) {
+    let headings = ["file", "task", "model", "scorer", "samples"];
+    let columns = |row: &ScoreRow| [row.file.clone(), row.task.clone(), row.model.clone(), row.scorer.clone(), row.samples.clone()];
+
+    let mut widths = headings.map(str::len);
+    for row in rows {
+        for (width, value) in widths.iter_mut().zip(columns(row)) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+
+    let heading = headings.iter().zip(widths).map(|(h, w)| format!("{:<w$}", h)).join("  ");
+    println!("{}  {}", heading.bold(), "metrics".bold());
+    for row in rows {
+        let values = columns(row).iter().zip(widths).map(|(v, w)| format!("{:<w$}", v)).join("  ");
+        let metrics = row.metrics.iter().map(|(name, value)| format!("{} {:.3}", name, value)).join("  ");
+        println!("{}  {}", values, metrics.green());
+    }
+}
+
+pub fn run(paths: &[PathBuf]) -> Result<()> {
+    let rows = paths.par_iter().filter_map(|path| skipped::or_skip(score_rows(path), path.display())).flatten().collect::<Vec<_>>();
+    display_score_table(&rows);
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+use regex::Regex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::assembly::SampleLayout;
+use crate::counters::ScanCounters;
+use crate::filter_set::{FilterSet, RoleSet};
+use crate::inspect::{ChatMessage, ChatMessageRole, ReadOptions};
+use crate::log_format::open_log;
+use crate::message_pattern::MessagePattern;
+use crate::{Discovery, IntFilter};
+
+/// What [`search`] matches: the command line's basic message and sample filters.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// Pattern a message's content must match.
+    pub message_regex: Option<Regex>,
+    /// Roles a message must have, or any role.
+    pub roles: Option<Vec<ChatMessageRole>>,
+    /// Pattern sample ids must match.
+    pub samples: Option<Regex>,
+    /// Epochs to read.
+    pub epochs: IntFilter,
+    /// Layout of sample entries, for logs not written in one Inspect uses.
+    pub layout: Option<SampleLayout>,
+    /// How samples are read.
+    pub read_options: ReadOptions,
+    /// Also search `.json` logs found in directories.
+    pub include_json: bool,
+}
+
+/// A matching message and where it was found.
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchRecord {
+    pub log: PathBuf,
+    pub sample_id: String,
+    pub epoch: i64,
+    /// Position of the message in the sample.
+    pub index: usize,
+    /// Zip entry the sample was read from.
+    pub entry: String,
+    /// Byte range of the message's JSON object within `entry`.
+    pub span: (u64, u64),
+    pub message: ChatMessage,
+    /// Byte ranges of `message_regex` matches in the message content.
+    pub matches: Vec<(usize, usize)>,
+}
+
+/// Messages matching `options` in the logs at `paths`, which may be log files or
+/// directories to search for them.
+///
+/// Logs are read one at a time, in path order, with their samples parsed in
+/// parallel; matches are yielded in sample order within each log. A log or sample
+/// that can't be read yields an error naming it, in its place, and the search goes
+/// on with the rest.
+pub fn search(paths: &[PathBuf], options: SearchOptions) -> impl Iterator<Item = Result<MatchRecord>> {
+    let logs = paths.iter().flat_map(|path| crate::eval_files(path, &Discovery { include_json: options.include_json, ..Discovery::default() })).collect::<Vec<_>>();
+    let filters = FilterSet {
+        roles: RoleSet::of(options.roles.as_deref().unwrap_or_default()),
+        pattern: options.message_regex.clone().map(MessagePattern::from),
+        ..FilterSet::default()
+    };
+    logs.into_iter().flat_map(move |log| search_log(&log, &options, &filters))
+}
+
+fn search_log(log: &Path, options: &SearchOptions, filters: &FilterSet) -> Vec<Result<MatchRecord>> {
+    let counters = ScanCounters::default();
+    let opened = crate::matching_samples_in_log(log, &options.samples, &options.epochs, options.layout.as_ref(), &counters)
+        .and_then(|entries| Ok((entries, open_log(log)?)))
+        .with_context(|| format!("Failed to read {}", log.display()));
+    let (entries, archive) = match opened {
+        Ok(opened) => opened,
+        Err(e) => return vec![Err(e)],
+    };
+    let filter = |message: &ChatMessage| filters.accepts(message, &counters);
+    let samples = entries
+        .par_iter()
+        .map_init(
+            || archive.clone(),
+            |archive, entry| {
+                crate::read_sample_from(archive, entry, options.read_options, filters.indices.as_ref(), filter)
+                    .with_context(|| format!("Failed to read sample {} (epoch {}) in {}", entry.sample_id, entry.epoch, log.display()))
+            },
+        )
+        .collect::<Vec<_>>();
+
+    let mut records = Vec::new();
+    for sample in samples {
+        let sample = match sample {
+            Ok(sample) => sample,
+            Err(e) => {
+                records.push(Err(e));
+                continue;
+            }
+        };
+        for (index, message) in sample.messages.into_iter().enumerate() {
+            let Some(message) = message else { continue };
+            let matches = options
+                .message_regex
+                .as_ref()
+                .map(|re| re.find_iter(&message.content).map(|m| (m.start(), m.end())).collect())
+                .unwrap_or_default();
+            records.push(Ok(MatchRecord {
+                log: log.to_path_buf(),
+                sample_id: sample.id.clone(),
+                epoch: sample.epoch,
+                index,
+                entry: sample.entry.clone(),
+                span: sample.message_spans[index],
+                message,
+                matches,
+            }));
+        }
+    }
+    records
+}
@@ -0,0 +1,50 @@
+use colored::*;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// Logs and samples that couldn't be read, with why, skipped so one corrupt sample doesn't
+// end a long scan; with --strict the first one does
+static STRICT: AtomicBool = AtomicBool::new(false);
+static SKIPPED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Skipped items listed in the report; the rest are only counted
+const REPORTED: usize = 20;
+
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Relaxed);
+}
+
+// The value, or None once the failure to read `what` is recorded. With --strict the
+// failure ends the process instead
+pub fn or_skip<T, E: Into<anyhow::Error>>(result: Result<T, E>, what: impl Display) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            let failure = format!("{}: {:#}", what, e.into());
+            if STRICT.load(Ordering::Relaxed) {
+                crate::interrupt::finish();
+                eprintln!("{} failed to read {}", "error:".red().bold(), failure);
+                std::process::exit(2);
+            }
+            SKIPPED.lock().unwrap().push(failure);
+            None
+        }
+    }
+}
+
+// What was skipped, on stderr, once the scan is over
+pub fn report() {
+    let mut skipped = SKIPPED.lock().unwrap();
+    if skipped.is_empty() {
+        return;
+    }
+    skipped.sort();
+    eprintln!("{} skipped {} unreadable logs or samples (--strict stops at the first):", "warning:".yellow().bold(), skipped.len());
+    for failure in skipped.iter().take(REPORTED) {
+        eprintln!("  {}", failure);
+    }
+    if skipped.len() > REPORTED {
+        eprintln!("  {}", format!("(+{} more)", skipped.len() - REPORTED).dimmed());
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::assembly::{sample_entries, SampleLayout};
+use crate::inspect::ChatMessageRole;
+use crate::log_format::open_log;
+
+// What to strip from a log
+pub struct SlimOptions {
+    pub drop_roles: Vec<ChatMessageRole>,
+    pub max_chars: Option<usize>,
+}
+
+#[derive(Default)]
+struct SlimCounts {
+    dropped: usize,
+    truncated: usize,
+}
+
+impl SlimOptions {
+    fn truncate(&self, text: &mut String, counts: &mut SlimCounts) {
+        let Some(max_chars) = self.max_chars else { return };
+        if let Some((end, _)) = text.char_indices().nth(max_chars) {
+            let removed = text[end..].chars().count();
+            text.truncate(end);
+            text.push_str(&format!("… [truncated {} chars]", removed));
+            counts.truncated += 1;
+        }
+    }
+
+    // Content is either a string or a list of parts, of which text and reasoning are bulky
+    fn truncate_content(&self, content: &mut Value, counts: &mut SlimCounts) {
+        match content {
+            Value::String(text) => self.truncate(text, counts),
+            Value::Array(parts) => {
+                for part in parts.iter_mut().filter_map(Value::as_object_mut) {
+                    for key in ["text", "reasoning"] {
+                        if let Some(Value::String(text)) = part.get_mut(key) {
+                            self.truncate(text, counts);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn is_dropped(&self, message: &Value) -> bool {
+        let role = message.get("role").cloned().and_then(|role| serde_json::from_value::<ChatMessageRole>(role).ok());
+        role.is_some_and(|role| self.drop_roles.contains(&role))
+    }
+
+    fn slim_messages(&self, messages: &mut Value, counts: &mut SlimCounts) {
+        let Some(messages) = messages.as_array_mut() else { return };
+        let before = messages.len();
+        messages.retain(|message| !self.is_dropped(message));
+        counts.dropped += before - messages.len();
+        for message in messages.iter_mut() {
+            if let Some(content) = message.get_mut("content") {
+                self.truncate_content(content, counts);
+            }
+        }
+    }
+
+    // The sample's messages, the copies of them model events were given, and its attachments
+    fn slim_sample(&self, sample: &mut Value, counts: &mut SlimCounts) {
+        if let Some(messages) = sample.get_mut("messages") {
+            self.slim_messages(messages, counts);
+        }
+        if let Some(events) = sample.get_mut("events").and_then(Value::as_array_mut) {
+            for event in events.iter_mut().filter(|event| event.get("event").and_then(Value::as_str) == Some("model")) {
+                if let Some(input) = event.get_mut("input") {
+                    self.slim_messages(input, &mut SlimCounts::default());
+                }
+            }
+        }
+        if let Some(attachments) = sample.get_mut("attachments").and_then(Value::as_object_mut) {
+            for attachment in attachments.values_mut() {
+                if let Value::String(text) = attachment {
+                    self.truncate(text, counts);
+                }
+            }
+        }
+    }
+}
+
+fn write_entry<W: Write + std::io::Seek>(writer: &mut ZipWriter<W>, name: &str, contents: &[u8]) -> Result<()> {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(contents.len() as u64 >= u32::MAX as u64);
+    writer.start_file(name, options)?;
+    writer.write_all(contents)?;
+    Ok(())
+}
+
+// Rewrite a log with messages of some roles removed and long content truncated,
+// copying everything but the samples and their attachments unchanged
+pub fn slim(log_path: &Path, output: &Path, layout: Option<&SampleLayout>, options: &SlimOptions) -> Result<()> {
+    let mut archive = open_log(log_path)?;
+    let file_names = archive.file_names().map(String::from).collect::<Vec<_>>();
+    let samples = sample_entries(&file_names.iter().map(String::as_str).collect::<Vec<_>>(), layout);
+
+    let out = std::fs::File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+    let mut writer = ZipWriter::new(out);
+    let mut counts = SlimCounts::default();
+
+    // Samples are written in place of their first entry, stitching shards together
+    let mut rewritten = HashSet::new();
+    for sample in &samples {
+        let mut json = Vec::new();
+        sample.reader(&mut archive)?.read_to_end(&mut json)?;
+        let mut value: Value = serde_json::from_slice(&json).with_context(|| format!("Failed to parse {}", sample.name()))?;
+        options.slim_sample(&mut value, &mut counts);
+        write_entry(&mut writer, sample.name(), &serde_json::to_vec(&value)?)?;
+        rewritten.extend(sample.entries.iter().cloned());
+
+        for (_, entry) in &sample.attachments {
+            let mut content = String::new();
+            archive.by_name(entry)?.read_to_string(&mut content)?;
+            options.truncate(&mut content, &mut counts);
+            write_entry(&mut writer, entry, content.as_bytes())?;
+            rewritten.insert(entry.clone());
+        }
+    }
+    for name in file_names.iter().filter(|name| !rewritten.contains(*name)) {
+        writer.raw_copy_file(archive.by_name(name)?)?;
+    }
+    writer.finish()?;
+
+    let size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+    eprintln!(
+        "Wrote {}: {} KiB -> {} KiB ({} samples, {} messages dropped, {} texts truncated)",
+        output.display(),
+        size(log_path) >> 10,
+        size(output) >> 10,
+        samples.len(),
+        counts.dropped,
+        counts.truncated
+    );
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tempfile::NamedTempFile;
+
+// A byte count such as `512M` or `2G` (binary units)
+#[derive(Debug, Clone, Copy)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (number, unit) = s.split_at(digits);
+        let number: u64 = number.parse().map_err(|_| anyhow!("invalid size '{}', expected e.g. 512M or 2G", s))?;
+        let shift = match unit.trim_end_matches(['B', 'b']).to_ascii_uppercase().as_str() {
+            "" => 0,
+            "K" => 10,
+            "M" => 20,
+            "G" => 30,
+            "T" => 40,
+            _ => return Err(anyhow!("unknown size unit '{}', expected K, M, G or T", unit)),
+        };
+        Ok(ByteSize(number << shift))
+    }
+}
+
+// One file's results, serialized
+enum Run {
+    Memory(Vec<u8>),
+    Disk(NamedTempFile),
+}
+
+// Collects per-file results for ordered output, keeping at most `limit` bytes
+// of them in memory and writing the rest to temporary files
+pub struct SpillBuffer<T> {
+    limit: u64,
+    used: AtomicU64,
+    // Each file's results with its position in the scan
+    runs: Mutex<Vec<(usize, PathBuf, Run)>>,
+    _items: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> SpillBuffer<T> {
+    pub fn new(limit: ByteSize) -> Self {
+        Self { limit: limit.0, used: AtomicU64::new(0), runs: Mutex::new(Vec::new()), _items: PhantomData }
+    }
+
+    pub fn push(&self, index: usize, path: &Path, items: &[T]) -> Result<()> {
+        let bytes = serde_json::to_vec(items)?;
+        let size = bytes.len() as u64;
+        let fits = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| (used + size <= self.limit).then_some(used + size))
+            .is_ok();
+        let run = if fits {
+            Run::Memory(bytes)
+        } else {
+            let mut file = NamedTempFile::new().with_context(|| format!("Failed to create a file in {} to spill results to", std::env::temp_dir().display()))?;
+            let spill_path = file.path().to_path_buf();
+            let mut writer = BufWriter::new(file.as_file_mut());
+            writer.write_all(&bytes).and_then(|_| writer.flush()).with_context(|| format!("Failed to spill results to {}", spill_path.display()))?;
+            drop(writer);
+            Run::Disk(file)
+        };
+        self.runs.lock().unwrap().push((index, path.to_path_buf(), run));
+        Ok(())
+    }
+
+    // Every file's results, in scan order, reading spilled ones back one at a time
+    pub fn into_sorted(self) -> impl Iterator<Item = Result<(PathBuf, Vec<T>)>> {
+        let mut runs = self.runs.into_inner().unwrap();
+        runs.sort_by_key(|(index, _, _)| *index);
+        runs.into_iter().map(|(_, path, run)| {
+            let items = match run {
+                Run::Memory(bytes) => serde_json::from_slice(&bytes)?,
+                Run::Disk(mut file) => {
+                    let spill_path = file.path().to_path_buf();
+                    let context = || format!("Failed to read spilled results back from {}", spill_path.display());
+                    file.as_file_mut().seek(SeekFrom::Start(0)).with_context(context)?;
+                    serde_json::from_reader(BufReader::new(file.as_file())).with_context(context)?
+                }
+            };
+            Ok((path, items))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(s: &str) -> u64 {
+        s.parse::<ByteSize>().unwrap().0
+    }
+
+    #[test]
+    fn byte_size_units() {
+        assert_eq!(size("512"), 512);
+        assert_eq!(size("512B"), 512);
+        assert_eq!(size("4K"), 4 << 10);
+        assert_eq!(size("4kb"), 4 << 10);
+        assert_eq!(size("512M"), 512 << 20);
+        assert_eq!(size(" 2G "), 2 << 30);
+        assert_eq!(size("1T"), 1 << 40);
+    }
+
+    #[test]
+    fn byte_size_errors() {
+        assert!("".parse::<ByteSize>().unwrap_err().to_string().contains("invalid size"));
+        assert!("M".parse::<ByteSize>().unwrap_err().to_string().contains("invalid size"));
+        assert!("1.5G".parse::<ByteSize>().is_err());
+        assert!("2P".parse::<ByteSize>().unwrap_err().to_string().contains("unknown size unit 'P'"));
+    }
+
+    #[test]
+    fn spilled_results_come_back_in_scan_order() {
+        // Room for the first file pushed only, so the rest go to disk
+        let buffer = SpillBuffer::<String>::new(ByteSize(serde_json::to_vec(&["b"]).unwrap().len() as u64));
+        buffer.push(1, Path::new("b.eval"), &["b".to_string()]).unwrap();
+        buffer.push(2, Path::new("c.eval"), &["c".to_string(), "d".to_string()]).unwrap();
+        buffer.push(0, Path::new("a.eval"), &[]).unwrap();
+        let runs = buffer.runs.lock().unwrap().iter().filter(|(_, _, run)| matches!(run, Run::Disk(_))).count();
+        assert_eq!(runs, 2);
+        let results = buffer.into_sorted().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(
+            results,
+            vec![
+                (PathBuf::from("a.eval"), vec![]),
+                (PathBuf::from("b.eval"), vec!["b".to_string()]),
+                (PathBuf::from("c.eval"), vec!["c".to_string(), "d".to_string()]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use colored::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::inspect::EvalSample;
+use crate::notify::format_elapsed;
+use crate::svg::{histogram, BarChart};
+
+const HISTOGRAM_BINS: usize = 10;
+
+// Aggregate statistics over the samples selected by a query
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub samples_scanned: usize,
+    pub samples_matched: usize,
+    pub matches_by_role: BTreeMap<String, usize>,
+    pub matches_per_sample: Vec<f64>,
+    pub scores: BTreeMap<String, Vec<Value>>,
+}
+
+impl Stats {
+    pub fn add_sample(&mut self, sample: &EvalSample) {
+        self.samples_scanned += 1;
+
+        let matches = sample.messages.iter().flatten().collect::<Vec<_>>();
+        if matches.is_empty() {
+            return;
+        }
+        self.samples_matched += 1;
+        self.matches_per_sample.push(matches.len() as f64);
+        for message in matches {
+            *self.matches_by_role.entry(message.role.to_string()).or_default() += 1;
+        }
+        for (scorer, score) in &sample.scores {
+            self.scores.entry(scorer.clone()).or_default().push(score.value.clone());
+        }
+    }
+
+    pub fn merge(mut self, other: Stats) -> Stats {
+        self.samples_scanned += other.samples_scanned;
+        self.samples_matched += other.samples_matched;
+        for (role, count) in other.matches_by_role {
+            *self.matches_by_role.entry(role).or_default() += count;
+        }
+        self.matches_per_sample.extend(other.matches_per_sample);
+        for (scorer, values) in other.scores {
+            self.scores.entry(scorer).or_default().extend(values);
+        }
+        self
+    }
+
+    pub fn display(&self) {
+        println!("{} {}", "samples scanned:".bold(), self.samples_scanned);
+        println!("{} {}", "samples matched:".bold(), self.samples_matched);
+        println!("{}", "matches by role:".bold());
+        for (role, count) in &self.matches_by_role {
+            println!("  {:<10} {}", role, count);
+        }
+        for (scorer, values) in &self.scores {
+            println!("{}", format!("score distribution ({}):", scorer).bold());
+            for (label, count) in score_distribution(values) {
+                println!("  {:<10} {}", label, count);
+            }
+        }
+    }
+
+    pub fn charts(&self) -> Vec<BarChart> {
+        let mut charts = vec![
+            BarChart {
+                title: "Matches by role".to_string(),
+                bars: self.matches_by_role.iter().map(|(role, count)| (role.clone(), *count as f64)).collect(),
+            },
+            BarChart {
+                title: "Matched messages per sample".to_string(),
+                bars: histogram(&self.matches_per_sample, HISTOGRAM_BINS),
+            },
+        ];
+        for (scorer, values) in &self.scores {
+            charts.push(BarChart {
+                title: format!("Score distribution: {}", scorer),
+                bars: score_distribution(values),
+            });
+        }
+        charts
+    }
+}
+
+// Numeric scores are histogrammed; anything else (e.g. C/I grades) is counted per value
+fn score_distribution(values: &[Value]) -> Vec<(String, f64)> {
+    if let Some(numbers) = values.iter().map(Value::as_f64).collect::<Option<Vec<_>>>() {
+        return histogram(&numbers, HISTOGRAM_BINS);
+    }
+    let mut counts: BTreeMap<String, f64> = BTreeMap::new();
+    for value in values {
+        *counts.entry(score_label(value)).or_default() += 1.0;
+    }
+    counts.into_iter().collect()
+}
+
+pub fn score_label(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Matching samples listed by --stats, most matches first
+const TOP_SAMPLES: usize = 10;
+
+#[derive(Default)]
+struct Tallies {
+    by_role: BTreeMap<String, usize>,
+    by_task: BTreeMap<String, usize>,
+    by_epoch: BTreeMap<i64, usize>,
+    // (log, sample id, epoch) -> matches
+    by_sample: HashMap<(PathBuf, String, i64), usize>,
+    // Task names by log, read from each log's header once
+    tasks: HashMap<PathBuf, String>,
+}
+
+// Where a search's matches fell, tallied as matching samples are shown, for --stats
+#[derive(Default)]
+pub struct MatchStats {
+    tallies: Mutex<Tallies>,
+}
+
+// The task a log ran, or its file name when the header doesn't say
+fn log_task(log_path: &Path) -> String {
+    let header = crate::log_index::header(log_path).ok().flatten();
+    let task = header.as_ref().and_then(|header| header["eval"]["task"].as_str()).map(str::to_string);
+    task.unwrap_or_else(|| log_path.file_stem().unwrap_or_default().to_string_lossy().to_string())
+}
+
+impl MatchStats {
+    // Record a matching sample by the role of each of its matches. `log_path` is the log
+    // read, and `source` the log and sample as they're shown
+    pub fn add(&self, log_path: &Path, source: (&Path, &str, i64), roles: impl IntoIterator<Item = String>) {
+        let (path, sample_id, epoch) = source;
+        let mut tallies = self.tallies.lock().unwrap();
+        let mut matches = 0;
+        for role in roles {
+            *tallies.by_role.entry(role).or_default() += 1;
+            matches += 1;
+        }
+        let task = match tallies.tasks.get(log_path) {
+            Some(task) => task.clone(),
+            None => {
+                let task = log_task(log_path);
+                tallies.tasks.insert(log_path.to_path_buf(), task.clone());
+                task
+            }
+        };
+        *tallies.by_task.entry(task).or_default() += matches;
+        *tallies.by_epoch.entry(epoch).or_default() += matches;
+        *tallies.by_sample.entry((path.to_path_buf(), sample_id.to_string(), epoch)).or_default() += matches;
+    }
+
+    // The report, on stderr with the other totals so stdout stays just matches
+    pub fn display(&self, files: (usize, usize), samples_scanned: usize, elapsed: Duration) {
+        let tallies = self.tallies.lock().unwrap();
+        let (scanned, total) = files;
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        eprintln!("\n{} {} of {}", "files scanned:".bold(), scanned, total);
+        eprintln!("{} {}", "samples scanned:".bold(), samples_scanned);
+        eprintln!("{} {}", "samples matched:".bold(), tallies.by_sample.len());
+        let table = |title: &str, rows: Vec<(String, usize)>| {
+            eprintln!("{}", title.bold());
+            let width = rows.iter().map(|(label, _)| label.chars().count()).max().unwrap_or_default();
+            for (label, count) in rows {
+                eprintln!("  {:<width$}  {}", label, count);
+            }
+        };
+        table("matches by role:", tallies.by_role.iter().map(|(role, count)| (role.clone(), *count)).collect());
+        table("matches by task:", tallies.by_task.iter().map(|(task, count)| (task.clone(), *count)).collect());
+        table("matches by epoch:", tallies.by_epoch.iter().map(|(epoch, count)| (epoch.to_string(), *count)).collect());
+        let mut samples = tallies.by_sample.iter().collect::<Vec<_>>();
+        samples.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+        let top = samples.into_iter().take(TOP_SAMPLES).map(|((path, id, epoch), count)| (format!("{}:{}:{}", path.display(), id, epoch), *count));
+        table("top matching samples:", top.collect());
+        eprintln!(
+            "{} {} ({:.1} files/s, {:.1} samples/s)",
+            "elapsed:".bold(),
+            format_elapsed(elapsed),
+            scanned as f64 / seconds,
+            samples_scanned as f64 / seconds
+        );
+    }
+}
@@ -0,0 +1,36 @@
+use clap::ValueEnum;
+
+use crate::inspect::EvalSample;
+
+/// Why the model stopped generating, as --stop-reason names it. Each stands for the
+/// values Inspect and the providers it passes through record for it.
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+#[value(rename_all = "snake_case")]
+pub enum StopReason {
+    /// Finished its turn.
+    Stop,
+    /// Cut off by the token limit or the model's context length.
+    Length,
+    /// Stopped to call tools.
+    ToolCalls,
+    /// Blocked by the provider's content filter.
+    ContentFilter,
+}
+
+impl StopReason {
+    pub fn matches(self, reason: &str) -> bool {
+        match self {
+            StopReason::Stop => matches!(reason, "stop" | "end_turn" | "stop_sequence"),
+            StopReason::Length => matches!(reason, "max_tokens" | "model_length" | "length"),
+            StopReason::ToolCalls => matches!(reason, "tool_calls" | "tool_use" | "function_call"),
+            StopReason::ContentFilter => matches!(reason, "content_filter" | "refusal"),
+        }
+    }
+}
+
+/// Whether the sample's final output, or any model call in its event log, stopped for
+/// one of `reasons`.
+pub fn any_of(reasons: &[StopReason], sample: &EvalSample) -> bool {
+    let mut recorded = sample.stop_reason().into_iter().chain(sample.stop_reasons.iter().map(String::as_str));
+    recorded.any(|recorded| reasons.iter().any(|reason| reason.matches(recorded)))
+}
@@ -0,0 +1,38 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use std::io::Write;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::Mutex;
+
+// Where --stream-to sends each match, as a line of JSON, the moment its sample is
+// read; once the consumer goes away, matches are no longer sent
+pub struct MatchStream {
+    writer: Mutex<Option<Box<dyn Write + Send>>>,
+}
+
+impl MatchStream {
+    // Connect to a listening Unix socket, or open a named pipe for writing, which
+    // waits until something opens it for reading
+    pub fn open(path: &Path) -> Result<Self> {
+        let file_type = std::fs::metadata(path).with_context(|| format!("Failed to stat {}", path.display()))?.file_type();
+        let writer: Box<dyn Write + Send> = if file_type.is_socket() {
+            Box::new(UnixStream::connect(path).with_context(|| format!("Failed to connect to {}", path.display()))?)
+        } else if file_type.is_fifo() {
+            Box::new(std::fs::OpenOptions::new().write(true).open(path).with_context(|| format!("Failed to open {}", path.display()))?)
+        } else {
+            bail!("{} is not a Unix socket or named pipe", path.display());
+        };
+        Ok(Self { writer: Mutex::new(Some(writer)) })
+    }
+
+    pub fn send(&self, line: &str) {
+        let mut writer = self.writer.lock().unwrap();
+        let Some(stream) = writer.as_mut() else { return };
+        if let Err(e) = writeln!(stream, "{}", line).and_then(|_| stream.flush()) {
+            eprintln!("{} stopped streaming matches: {}", "warning:".yellow().bold(), e);
+            *writer = None;
+        }
+    }
+}
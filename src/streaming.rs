@@ -0,0 +1,120 @@
+use anyhow::Result;
+use colored::*;
+use regex::bytes::Regex;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+
+use crate::assembly::SampleEntries;
+use crate::log_format::open_log;
+use crate::theme::Theme;
+
+// Bytes of the sample read per search window
+const CHUNK: usize = 1 << 20;
+// Bytes carried over between windows, bounding how long a match can be
+const OVERLAP: usize = 64 << 10;
+// Bytes of context kept either side of a match
+const CONTEXT: usize = 60;
+// Snippets kept per sample; further matches are only counted
+const MAX_SNIPPETS: usize = 20;
+
+// A sample too large to parse, searched as raw JSON in bounded windows
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Streamed {
+    // Uncompressed size of the sample's entries
+    pub size: u64,
+    pub count: usize,
+    pub snippets: Vec<String>,
+}
+
+pub fn uncompressed_size<R: Read + std::io::Seek>(archive: &mut ZipArchive<R>, sample: &SampleEntries) -> Result<u64> {
+    sample.entries.iter().try_fold(0, |size, entry| Ok(size + archive.by_name(entry)?.size()))
+}
+
+fn snippet(buffer: &[u8], start: usize, end: usize) -> String {
+    let from = start.saturating_sub(CONTEXT);
+    let to = (end + CONTEXT).min(buffer.len());
+    String::from_utf8_lossy(&buffer[from..to]).split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// The part of the sample currently held in memory
+struct Window {
+    buffer: Vec<u8>,
+    // Matches starting before this offset in the buffer have already been seen
+    searched: usize,
+}
+
+impl Window {
+    fn is_full(&self) -> bool {
+        self.buffer.len() >= CHUNK + OVERLAP
+    }
+
+    fn fill(&mut self, reader: &mut impl Read) -> Result<usize> {
+        let filled = self.buffer.len();
+        self.buffer.resize(CHUNK + OVERLAP, 0);
+        let n = reader.read(&mut self.buffer[filled..])?;
+        self.buffer.truncate(filled + n);
+        Ok(n)
+    }
+
+    // Record matches in the window; unless this is the end of the sample, matches
+    // starting in the overlap are left to the next window, which will see all of them
+    fn search(&mut self, regex: &Regex, eof: bool, streamed: &mut Streamed) {
+        let limit = if eof { self.buffer.len() } else { self.buffer.len() - OVERLAP };
+        let base = self.searched;
+        for found in regex.find_iter(&self.buffer[base..]) {
+            let (start, end) = (base + found.start(), base + found.end());
+            if start >= limit {
+                break;
+            }
+            streamed.count += 1;
+            if streamed.snippets.len() < MAX_SNIPPETS {
+                streamed.snippets.push(snippet(&self.buffer, start, end));
+            }
+            self.searched = end.max(start + 1);
+        }
+        if eof {
+            return;
+        }
+        // Slide the window, keeping enough before the limit for snippet context
+        let keep_from = limit.saturating_sub(CONTEXT);
+        self.buffer.drain(..keep_from);
+        self.searched = self.searched.max(limit) - keep_from;
+    }
+}
+
+// Search a sample's JSON for the pattern without ever holding more than a window of it
+pub fn stream_sample(log_path: &Path, sample: &SampleEntries, regex: &Regex) -> Result<Streamed> {
+    let mut archive = open_log(log_path)?;
+    let size = uncompressed_size(&mut archive, sample)?;
+    let mut streamed = Streamed { size, count: 0, snippets: Vec::new() };
+
+    // Shards are read one after another, carrying the window across entries
+    let mut window = Window { buffer: Vec::with_capacity(CHUNK + OVERLAP), searched: 0 };
+    for entry in &sample.entries {
+        let mut reader = crate::resources::decompressing(archive.by_name(entry)?);
+        while window.fill(&mut reader)? > 0 {
+            if window.is_full() {
+                window.search(regex, false, &mut streamed);
+            }
+        }
+    }
+    window.search(regex, true, &mut streamed);
+    Ok(streamed)
+}
+
+pub fn display_streamed(path: &Path, sample_id: &str, epoch: i64, streamed: &Streamed, highlight: Option<&regex::Regex>, theme: &Theme) {
+    let marker = format!("[streamed: {} MiB, {} matches in raw JSON]", streamed.size >> 20, streamed.count).yellow().bold();
+    println!("\n{}", theme.header(&path.file_name().unwrap().to_string_lossy(), sample_id, epoch, marker));
+    for snippet in &streamed.snippets {
+        let snippet = match highlight {
+            Some(re) => re.replace_all(snippet, |caps: &regex::Captures| theme.highlight(&caps[0]).to_string()).to_string(),
+            None => snippet.clone(),
+        };
+        println!("{1}{0}{1}", snippet, crate::glyphs::ellipsis());
+    }
+    if streamed.count > streamed.snippets.len() {
+        println!("{}", format!("(+{} more matches)", streamed.count - streamed.snippets.len()).dimmed());
+    }
+}
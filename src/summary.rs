@@ -0,0 +1,64 @@
+use colored::*;
+use regex::Regex;
+use std::path::Path;
+
+use crate::inspect::{ChatMessage, EvalSample};
+use crate::stats::score_label;
+use crate::theme::Theme;
+
+// Characters of message content shown in a summary line
+const PREVIEW_WIDTH: usize = 80;
+// How much context to keep before the match when the preview has to start mid-message
+const PREVIEW_LEAD: usize = 20;
+
+// A single-line excerpt of the content around the first match (or its start, without a pattern)
+fn preview(content: &str, highlight_regex: Option<&Regex>, theme: &Theme) -> String {
+    let flat = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    let found = highlight_regex.and_then(|re| re.find(&flat));
+    let match_start = found.map_or(0, |m| flat[..m.start()].chars().count());
+    let start = match_start.saturating_sub(PREVIEW_LEAD);
+    let total = flat.chars().count();
+    let end = (start + PREVIEW_WIDTH).min(total);
+    let window = flat.chars().skip(start).take(end - start).collect::<String>();
+
+    let window = match highlight_regex {
+        Some(re) => re.replace_all(&window, |caps: &regex::Captures| theme.highlight(&caps[0]).to_string()).to_string(),
+        None => window,
+    };
+    let ellipsis = crate::glyphs::ellipsis();
+    format!("{}{}{}", if start > 0 { ellipsis } else { "" }, window, if end < total { ellipsis } else { "" })
+}
+
+// The sample's scores: the bare value with a single scorer, else name=value for each
+pub fn scores(sample: &EvalSample) -> String {
+    let mut scores = sample.scores.iter().collect::<Vec<_>>();
+    scores.sort_by_key(|(name, _)| name.as_str());
+    match scores.as_slice() {
+        [] => "-".to_string(),
+        [(_, score)] => score_label(&score.value),
+        scores => scores.iter().map(|(name, score)| format!("{}={}", name, score_label(&score.value))).collect::<Vec<_>>().join(","),
+    }
+}
+
+// One line for a matched sample: where it is, its score, how many messages matched,
+// and a preview of the first match with its role
+pub fn display_summary_line(
+    path: &Path,
+    sample: &EvalSample,
+    matched: &[&ChatMessage],
+    // Per-epoch and reduced scores, when the log has several epochs
+    score: Option<&str>,
+    highlight_regex: Option<&Regex>,
+    theme: &Theme,
+) {
+    let Some(first) = matched.first() else { return };
+    let role = format!("[{}]", first.role).color(theme.role_color(&first.role)).bold();
+    let header = theme.header(&path.file_name().unwrap().to_string_lossy(), &sample.id, sample.epoch, role);
+    println!(
+        "{} {} {} {}",
+        header,
+        score.map_or_else(|| scores(sample), str::to_string).bold(),
+        format!("({} matches)", matched.len()).dimmed(),
+        preview(&first.content, highlight_regex, theme)
+    );
+}
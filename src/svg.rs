@@ -0,0 +1,94 @@
+use std::fmt::Write;
+
+const WIDTH: usize = 640;
+const LABEL_WIDTH: usize = 160;
+const BAR_AREA_WIDTH: usize = 400;
+const BAR_HEIGHT: usize = 18;
+const BAR_GAP: usize = 4;
+const TITLE_HEIGHT: usize = 30;
+const CHART_GAP: usize = 20;
+
+// A horizontal bar chart with one labelled bar per entry
+pub struct BarChart {
+    pub title: String,
+    pub bars: Vec<(String, f64)>,
+}
+
+impl BarChart {
+    fn height(&self) -> usize {
+        TITLE_HEIGHT + self.bars.len().max(1) * (BAR_HEIGHT + BAR_GAP)
+    }
+
+    fn render(&self, out: &mut String, top: usize) {
+        let max = self.bars.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+
+        writeln!(out, r#"<text x="10" y="{}" font-size="14" font-weight="bold">{}</text>"#, top + 20, escape(&self.title)).unwrap();
+        if self.bars.is_empty() {
+            writeln!(out, r##"<text x="{}" y="{}" font-size="12" fill="#888">no data</text>"##, LABEL_WIDTH, top + TITLE_HEIGHT + BAR_HEIGHT - 5).unwrap();
+        }
+        for (i, (label, value)) in self.bars.iter().enumerate() {
+            let y = top + TITLE_HEIGHT + i * (BAR_HEIGHT + BAR_GAP);
+            let width = if max > 0.0 { (value / max * BAR_AREA_WIDTH as f64).round() as usize } else { 0 };
+            writeln!(out, r#"<text x="{}" y="{}" font-size="12" text-anchor="end">{}</text>"#, LABEL_WIDTH - 8, y + BAR_HEIGHT - 5, escape(label)).unwrap();
+            writeln!(out, r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#4682b4"/>"##, LABEL_WIDTH, y, width, BAR_HEIGHT).unwrap();
+            writeln!(out, r#"<text x="{}" y="{}" font-size="12">{}</text>"#, LABEL_WIDTH + width + 6, y + BAR_HEIGHT - 5, format_value(*value)).unwrap();
+        }
+    }
+}
+
+// Render charts stacked vertically into a standalone SVG document
+pub fn render_charts(charts: &[BarChart]) -> String {
+    let height = charts.iter().map(|c| c.height() + CHART_GAP).sum::<usize>().max(CHART_GAP);
+    let mut out = String::new();
+    writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" font-family="sans-serif">"#).unwrap();
+    writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+    let mut top = 0;
+    for chart in charts {
+        chart.render(&mut out, top);
+        top += chart.height() + CHART_GAP;
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+// Bucket numeric values into labelled bars: exact values when there are
+// few distinct ones, otherwise equal-width bins
+pub fn histogram(values: &[f64], bins: usize) -> Vec<(String, f64)> {
+    let mut distinct = values.to_vec();
+    distinct.sort_by(f64::total_cmp);
+    distinct.dedup();
+    if distinct.len() <= bins {
+        return distinct
+            .iter()
+            .map(|d| (format_value(*d), values.iter().filter(|v| *v == d).count() as f64))
+            .collect();
+    }
+
+    let (min, max) = (distinct[0], distinct[distinct.len() - 1]);
+    let step = (max - min) / bins as f64;
+    let mut counts = vec![0usize; bins];
+    for value in values {
+        let bin = (((value - min) / step) as usize).min(bins - 1);
+        counts[bin] += 1;
+    }
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let start = min + step * i as f64;
+            (format!("{}–{}", format_value(start), format_value(start + step)), count as f64)
+        })
+        .collect()
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{:.2}", value)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
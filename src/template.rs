@@ -0,0 +1,170 @@
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::inspect::ChatMessage;
+use crate::streaming::Streamed;
+
+/// What a placeholder in a --template stands for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    File,
+    Path,
+    Sample,
+    Epoch,
+    Index,
+    Role,
+    Content,
+    Match,
+    Score,
+    Entry,
+}
+
+const FIELDS: [(&str, Field); 10] = [
+    ("file", Field::File),
+    ("path", Field::Path),
+    ("sample", Field::Sample),
+    ("epoch", Field::Epoch),
+    ("index", Field::Index),
+    ("role", Field::Role),
+    ("content", Field::Content),
+    ("match", Field::Match),
+    ("score", Field::Score),
+    ("entry", Field::Entry),
+];
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    // A field, cut to at most this many characters with `{field:.N}`
+    Field(Field, Option<usize>),
+}
+
+/// A --template format string, such as `{file}:{sample}:{epoch}:{role}: {content:.200}`,
+/// printed once per matching message. `{{` and `}}` are literal braces.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+/// The values a template is filled in with, for one match.
+pub struct TemplateValues<'a> {
+    pub path: &'a Path,
+    pub sample_id: &'a str,
+    pub epoch: i64,
+    /// Position of the message in the sample, unknown for streamed samples.
+    pub index: Option<usize>,
+    pub message: Option<&'a ChatMessage>,
+    /// The message's content, or a streamed sample's snippet.
+    pub content: &'a str,
+    pub score: Option<&'a str>,
+    pub entry: &'a str,
+}
+
+fn parse_placeholder(placeholder: &str) -> Result<Part> {
+    let (name, spec) = placeholder.split_once(':').unwrap_or((placeholder, ""));
+    let Some((_, field)) = FIELDS.iter().find(|(known, _)| *known == name.trim()) else {
+        let names = FIELDS.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+        bail!("unknown placeholder '{{{}}}' (expected one of {})", placeholder, names);
+    };
+    let max_chars = match spec {
+        "" => None,
+        spec => match spec.strip_prefix('.').and_then(|chars| chars.parse().ok()) {
+            Some(chars) => Some(chars),
+            None => bail!("unsupported format '{}' in '{{{}}}' (only '{{{}:.N}}', keeping N characters, is supported)", spec, placeholder, name),
+        },
+    };
+    Ok(Part::Field(*field, max_chars))
+}
+
+impl FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => bail!("unclosed '{{{}' in template (write '{{{{' for a literal brace)", placeholder),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(parse_placeholder(&placeholder)?);
+                }
+                '}' => bail!("unmatched '}}' in template (write '}}}}' for a literal brace)"),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+}
+
+// Whitespace runs, newlines included, as single spaces, so each match stays on one line
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+impl Template {
+    /// The line for one match; `regex` finds `{match}`, the first match in the content.
+    pub fn render(&self, values: &TemplateValues, regex: Option<&Regex>) -> String {
+        let mut line = String::new();
+        for part in &self.parts {
+            let (field, max_chars) = match part {
+                Part::Literal(literal) => {
+                    line.push_str(literal);
+                    continue;
+                }
+                Part::Field(field, max_chars) => (field, max_chars),
+            };
+            let value = match field {
+                Field::File => values.path.file_name().map_or_else(|| values.path.display().to_string(), |name| name.to_string_lossy().into_owned()),
+                Field::Path => values.path.display().to_string(),
+                Field::Sample => values.sample_id.to_string(),
+                Field::Epoch => values.epoch.to_string(),
+                Field::Index => values.index.map_or_else(|| "-".to_string(), |index| index.to_string()),
+                Field::Role => values.message.map_or_else(|| "-".to_string(), |message| message.role.to_string()),
+                Field::Content => one_line(values.content),
+                Field::Match => regex.and_then(|regex| regex.find(values.content)).map(|found| one_line(found.as_str())).unwrap_or_default(),
+                Field::Score => values.score.unwrap_or_default().to_string(),
+                Field::Entry => values.entry.to_string(),
+            };
+            match max_chars.and_then(|max| value.char_indices().nth(max)) {
+                Some((end, _)) => line.push_str(&value[..end]),
+                None => line.push_str(&value),
+            }
+        }
+        line
+    }
+
+    /// A line for each snippet of a streamed sample, which has no messages to fill in
+    /// `{role}` and `{index}`.
+    pub fn render_streamed(&self, source: (&Path, &str, i64), entry: &str, streamed: &Streamed, regex: Option<&Regex>) -> Vec<String> {
+        let (path, sample_id, epoch) = source;
+        streamed
+            .snippets
+            .iter()
+            .map(|snippet| self.render(&TemplateValues { path, sample_id, epoch, index: None, message: None, content: snippet, score: None, entry }, regex))
+            .collect()
+    }
+}
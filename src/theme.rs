@@ -0,0 +1,132 @@
+use anyhow::{bail, Context, Result};
+use colored::*;
+use serde::{Deserialize, Deserializer};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::inspect::ChatMessageRole;
+
+const BUILTIN_THEMES: &[(&str, &str)] = &[
+    ("dark", include_str!("themes/dark.toml")),
+    ("light", include_str!("themes/light.toml")),
+];
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Theme {
+    pub roles: RoleColors,
+    pub highlight: Highlight,
+    pub header: Header,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoleColors {
+    #[serde(deserialize_with = "color")]
+    pub system: Color,
+    #[serde(deserialize_with = "color")]
+    pub user: Color,
+    #[serde(deserialize_with = "color")]
+    pub assistant: Color,
+    #[serde(deserialize_with = "color")]
+    pub tool: Color,
+    // Messages whose role was missing or unrecognized; optional so older theme files still load
+    #[serde(default = "unknown_role_color", deserialize_with = "color")]
+    pub unknown: Color,
+}
+
+fn unknown_role_color() -> Color {
+    Color::Red
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightStyle {
+    // Bold text in the highlight color
+    Bold,
+    // Highlight color behind the matched text
+    Background,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Highlight {
+    pub style: HighlightStyle,
+    #[serde(deserialize_with = "color")]
+    pub color: Color,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HeaderLayout {
+    // `a.eval sample 1 epoch 2 | [assistant]`
+    Full,
+    // `a.eval:1:2 [assistant]`
+    Compact,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Header {
+    pub layout: HeaderLayout,
+    #[serde(deserialize_with = "color")]
+    pub file: Color,
+    #[serde(deserialize_with = "color")]
+    pub sample: Color,
+    #[serde(deserialize_with = "color")]
+    pub epoch: Color,
+}
+
+fn color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Color::from_str(&name).map_err(|_| serde::de::Error::custom(format!("unknown color '{}'", name)))
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        toml::from_str(BUILTIN_THEMES[0].1).expect("Failed to parse built-in theme")
+    }
+}
+
+impl FromStr for Theme {
+    type Err = anyhow::Error;
+
+    // A built-in theme name, or the path to a TOML theme file
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((_, source)) = BUILTIN_THEMES.iter().find(|(name, _)| *name == s) {
+            return Ok(toml::from_str(source)?);
+        }
+        let path = Path::new(s);
+        if !path.is_file() {
+            let names = BUILTIN_THEMES.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(", ");
+            bail!("'{}' is neither a built-in theme ({}) nor a theme file", s, names);
+        }
+        let source = std::fs::read_to_string(path)?;
+        toml::from_str(&source).with_context(|| format!("Failed to parse theme {}", path.display()))
+    }
+}
+
+impl Theme {
+    pub fn role_color(&self, role: &ChatMessageRole) -> Color {
+        match role {
+            ChatMessageRole::System => self.roles.system,
+            ChatMessageRole::User => self.roles.user,
+            ChatMessageRole::Assistant => self.roles.assistant,
+            ChatMessageRole::Tool => self.roles.tool,
+            ChatMessageRole::Unknown => self.roles.unknown,
+        }
+    }
+
+    pub fn highlight(&self, text: &str) -> ColoredString {
+        match self.highlight.style {
+            HighlightStyle::Bold => text.color(self.highlight.color).bold(),
+            HighlightStyle::Background => text.on_color(self.highlight.color),
+        }
+    }
+
+    pub fn header(&self, file: &str, sample_id: &str, epoch: i64, role: ColoredString) -> String {
+        let file = file.color(self.header.file);
+        let sample_id = sample_id.color(self.header.sample);
+        let epoch = epoch.to_string().color(self.header.epoch);
+        match self.header.layout {
+            HeaderLayout::Full => format!("{} sample {} epoch {} | {}", file, sample_id, epoch, role),
+            HeaderLayout::Compact => format!("{}:{}:{} {}", file, sample_id, epoch, role),
+        }
+    }
+}
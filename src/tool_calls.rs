@@ -0,0 +1,52 @@
+use colored::*;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::inspect::{ChatMessage, ToolCall};
+use crate::theme::Theme;
+
+// Tool calls to match: the function name and the rendered arguments must each match when given
+#[derive(Debug, Clone)]
+pub struct ToolCallFilter {
+    pub name: Option<Regex>,
+    pub arguments: Option<Regex>,
+}
+
+impl ToolCallFilter {
+    pub fn matches(&self, call: &ToolCall) -> bool {
+        self.name.as_ref().is_none_or(|re| re.is_match(&call.function))
+            && self.arguments.as_ref().is_none_or(|re| re.is_match(&render_arguments(&call.arguments)))
+    }
+
+    pub fn any_in(&self, message: &ChatMessage) -> bool {
+        message.tool_calls.iter().any(|call| self.matches(call))
+    }
+}
+
+// One `name: value` line per argument, with strings unescaped (continuation lines indented)
+// so patterns match commands and code as they'd be typed
+pub fn render_arguments(arguments: &Value) -> String {
+    let render_value = |value: &Value| match value {
+        Value::String(s) => s.replace('\n', "\n    "),
+        value => serde_json::to_string_pretty(value).unwrap().replace('\n', "\n    "),
+    };
+    match arguments {
+        Value::Object(map) => map.iter().map(|(name, value)| format!("{}: {}", name, render_value(value))).collect::<Vec<_>>().join("\n"),
+        Value::Null => String::new(),
+        value => render_value(value),
+    }
+}
+
+// The message's calls that matched, with the name and arguments highlighted
+pub fn display_tool_calls(message: &ChatMessage, filter: &ToolCallFilter, theme: &Theme) {
+    let highlight = |text: &str, regex: Option<&Regex>| match regex {
+        Some(re) => re.replace_all(text, |caps: &regex::Captures| theme.highlight(&caps[0]).to_string()).to_string(),
+        None => text.to_string(),
+    };
+    for call in message.tool_calls.iter().filter(|call| filter.matches(call)) {
+        println!("{} {}", crate::glyphs::glyph("→", "->").dimmed(), highlight(&call.function, filter.name.as_ref()).bold());
+        for line in highlight(&render_arguments(&call.arguments), filter.arguments.as_ref()).lines() {
+            println!("  {}", line);
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use serde_json::{Map, Value};
+
+// Stream fields of a tool output envelope, in display order
+const STREAMS: [&str; 2] = ["stdout", "stderr"];
+const EXIT_CODES: [&str; 3] = ["returncode", "exit_code", "returnCode"];
+
+// Tool output like `{"stdout": "...", "stderr": "...", "returncode": 1}` as plain
+// text with a label per stream, or None if the content isn't such an envelope
+pub fn unwrap_envelope(content: &str) -> Option<String> {
+    let trimmed = content.trim();
+    if !trimmed.starts_with('{') {
+        return None;
+    }
+    let envelope: Map<String, Value> = serde_json::from_str(trimmed).ok()?;
+    if !STREAMS.iter().any(|stream| envelope.get(*stream).is_some_and(Value::is_string)) {
+        return None;
+    }
+
+    let mut sections = Vec::new();
+    for stream in STREAMS {
+        if let Some(text) = envelope.get(stream).and_then(Value::as_str).filter(|text| !text.is_empty()) {
+            sections.push(format!("[{}]\n{}", stream, text.trim_end_matches('\n')));
+        }
+    }
+    if let Some(code) = EXIT_CODES.iter().find_map(|key| envelope.get(*key)).filter(|code| !code.is_null()) {
+        sections.push(format!("[exit code {}]", code));
+    }
+    Some(sections.join("\n"))
+}
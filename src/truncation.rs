@@ -0,0 +1,33 @@
+use crate::inspect::{ChatMessageRole, EvalSample};
+use crate::stop_reason::StopReason;
+
+// Signs that a sample ended abnormally, judged from the output's stop reason,
+// any limit the sample hit, and the shape of the end of the transcript
+pub fn truncation_reasons(sample: &EvalSample) -> Vec<String> {
+    let mut reasons = Vec::new();
+
+    if let Some(stop_reason) = sample.stop_reason().filter(|r| StopReason::Length.matches(r)) {
+        reasons.push(format!("stop reason {}", stop_reason));
+    }
+    if let Some(limit) = &sample.limit {
+        reasons.push(format!("hit {} limit", limit.kind));
+    }
+
+    let messages = sample.messages.iter().flatten().collect::<Vec<_>>();
+    match messages.last() {
+        None => reasons.push("no messages".to_string()),
+        Some(last) if last.role == ChatMessageRole::Assistant && !last.tool_calls.is_empty() => {
+            let functions = last.tool_calls.iter().map(|call| call.function.as_str()).collect::<Vec<_>>();
+            reasons.push(format!("unanswered tool call ({})", functions.join(", ")));
+        }
+        Some(last) if last.role != ChatMessageRole::Assistant => {
+            reasons.push(format!("ends with {} message", last.role));
+        }
+        Some(_) => {}
+    }
+    if !messages.iter().any(|m| m.role == ChatMessageRole::Assistant) {
+        reasons.push("no assistant answer".to_string());
+    }
+
+    reasons
+}
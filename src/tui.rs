@@ -0,0 +1,301 @@
+use anyhow::{bail, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::border;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use regex::Regex;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+
+use crate::glyphs::{self, glyph};
+use crate::inspect::{ChatMessage, ChatMessageRole, EvalSample};
+use crate::theme::{HighlightStyle, Theme};
+
+// Roles that can be toggled, with the key that toggles each
+const ROLE_KEYS: [(char, ChatMessageRole); 4] = [
+    ('s', ChatMessageRole::System),
+    ('u', ChatMessageRole::User),
+    ('a', ChatMessageRole::Assistant),
+    ('t', ChatMessageRole::Tool),
+];
+
+const HELP: &str = "match  n/N sample  PgUp/PgDn scroll  s/u/a/t role  enter whole sample  q quit";
+
+// Borders for terminals that can't draw box characters
+const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+fn bordered() -> Block<'static> {
+    let block = Block::default().borders(Borders::ALL);
+    match glyphs::ascii() {
+        true => block.border_set(ASCII_BORDER),
+        false => block,
+    }
+}
+
+// A sample with at least one match, and which of its messages matched (before the
+// role filter, which can be changed while browsing)
+pub struct Transcript {
+    pub path: PathBuf,
+    pub sample: EvalSample,
+    pub hits: Vec<usize>,
+}
+
+struct Browser<'a> {
+    transcripts: Vec<Transcript>,
+    message_regex: Option<&'a Regex>,
+    theme: &'a Theme,
+    roles: Vec<ChatMessageRole>,
+    // (transcript, message) of each hit whose role is shown
+    visible: Vec<(usize, usize)>,
+    list: ListState,
+    whole_sample: bool,
+    // Lines scrolled, once scrolled; until then the whole sample opens at the selected message
+    scroll: Option<u16>,
+    opened_at: u16,
+}
+
+// Browse the matches in `transcripts` until the user quits; `roles` are the roles
+// shown at first (all of them when empty)
+pub fn browse(transcripts: Vec<Transcript>, message_regex: Option<&Regex>, roles: &[ChatMessageRole], theme: &Theme) -> Result<()> {
+    if !std::io::stdout().is_terminal() {
+        bail!("--tui needs a terminal");
+    }
+    let roles = match roles {
+        [] => ROLE_KEYS.iter().map(|(_, role)| role.clone()).chain([ChatMessageRole::Unknown]).collect(),
+        roles => roles.to_vec(),
+    };
+    let mut browser = Browser { transcripts, message_regex, theme, roles, visible: Vec::new(), list: ListState::default(), whole_sample: false, scroll: None, opened_at: 0 };
+    browser.refilter();
+
+    // Restores the terminal on panics as well
+    let mut terminal = ratatui::init();
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl Browser<'_> {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.select_by(1),
+                KeyCode::Up | KeyCode::Char('k') => self.select_by(-1),
+                KeyCode::Char('n') => self.next_sample(true),
+                KeyCode::Char('N') => self.next_sample(false),
+                KeyCode::Home | KeyCode::Char('g') => self.select(0),
+                KeyCode::End | KeyCode::Char('G') => self.select(self.visible.len().saturating_sub(1)),
+                KeyCode::PageDown | KeyCode::Char(' ') => self.scroll = Some(self.scroll.unwrap_or(self.opened_at).saturating_add(10)),
+                KeyCode::PageUp => self.scroll = Some(self.scroll.unwrap_or(self.opened_at).saturating_sub(10)),
+                KeyCode::Enter | KeyCode::Char('o') => {
+                    self.whole_sample = !self.whole_sample;
+                    self.scroll = None;
+                }
+                KeyCode::Char(c) => {
+                    if let Some((_, role)) = ROLE_KEYS.iter().find(|(key, _)| *key == c) {
+                        self.toggle_role(role);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn refilter(&mut self) {
+        let current = self.list.selected().and_then(|i| self.visible.get(i).copied());
+        self.visible = self
+            .transcripts
+            .iter()
+            .enumerate()
+            .flat_map(|(t, transcript)| transcript.hits.iter().map(move |&m| (t, m)))
+            .filter(|&(t, m)| self.message(t, m).is_some_and(|message| self.roles.contains(&message.role)))
+            .collect();
+        // Stay on the same hit, or the first one after it
+        let selected = current.map_or(0, |current| self.visible.partition_point(|&hit| hit < current));
+        self.list.select((!self.visible.is_empty()).then(|| selected.min(self.visible.len() - 1)));
+        self.scroll = None;
+    }
+
+    fn toggle_role(&mut self, role: &ChatMessageRole) {
+        match self.roles.iter().position(|r| r == role) {
+            Some(i) => {
+                self.roles.remove(i);
+            }
+            None => self.roles.push(role.clone()),
+        }
+        self.refilter();
+    }
+
+    fn message(&self, transcript: usize, index: usize) -> Option<&ChatMessage> {
+        self.transcripts[transcript].sample.messages.get(index)?.as_ref()
+    }
+
+    fn select(&mut self, index: usize) {
+        if !self.visible.is_empty() {
+            self.list.select(Some(index.min(self.visible.len() - 1)));
+            self.scroll = None;
+        }
+    }
+
+    fn select_by(&mut self, step: isize) {
+        let selected = self.list.selected().unwrap_or(0);
+        self.select(selected.saturating_add_signed(step));
+    }
+
+    // The first hit of the next (or previous) sample
+    fn next_sample(&mut self, forward: bool) {
+        let Some(selected) = self.list.selected() else { return };
+        let transcript = self.visible[selected].0;
+        let target = match forward {
+            true => self.visible.iter().position(|&(t, _)| t > transcript),
+            false => {
+                let previous = self.visible[..selected].iter().rev().find(|&&(t, _)| t < transcript).map(|&(t, _)| t);
+                previous.and_then(|previous| self.visible.iter().position(|&(t, _)| t == previous))
+            }
+        };
+        if let Some(target) = target {
+            self.select(target);
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] = Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] = Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main);
+        self.draw_list(frame, left);
+        self.draw_message(frame, right);
+        self.draw_status(frame, status);
+    }
+
+    fn draw_list(&mut self, frame: &mut Frame, area: Rect) {
+        let items = self.visible.iter().map(|&(t, m)| {
+            let transcript = &self.transcripts[t];
+            let file = transcript.path.file_name().unwrap_or_default().to_string_lossy();
+            let message = self.message(t, m).unwrap();
+            let preview = message.content.split_whitespace().collect::<Vec<_>>().join(" ");
+            ListItem::new(Line::from(vec![
+                Span::styled(file.into_owned(), Style::new().fg(color(self.theme.header.file))),
+                Span::raw(" "),
+                Span::styled(transcript.sample.id.clone(), Style::new().fg(color(self.theme.header.sample))),
+                Span::raw(" "),
+                Span::styled(format!("e{}", transcript.sample.epoch), Style::new().fg(color(self.theme.header.epoch))),
+                Span::raw(format!(" #{} ", m)),
+                Span::styled(message.role.to_string(), Style::new().fg(color(self.theme.role_color(&message.role)))),
+                Span::raw(": "),
+                Span::raw(preview),
+            ]))
+        });
+        let title = format!(" {} matches in {} samples ", self.visible.len(), self.transcripts.len());
+        let list = List::new(items)
+            .block(bordered().title(title))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_message(&mut self, frame: &mut Frame, area: Rect) {
+        let block = bordered();
+        let Some(&(t, m)) = self.list.selected().and_then(|i| self.visible.get(i)) else {
+            frame.render_widget(Paragraph::new("No matches with these roles").block(block), area);
+            return;
+        };
+        let sample = &self.transcripts[t].sample;
+        let title = format!(" {} sample {} epoch {} ", self.transcripts[t].path.display(), sample.id, sample.epoch);
+        let block = block.title(title);
+        self.opened_at = 0;
+        let lines = match self.whole_sample {
+            false => self.message_lines(self.message(t, m).unwrap(), m, false),
+            true => {
+                let before = sample.messages[..m].iter().enumerate().filter_map(|(i, message)| Some(self.message_lines(message.as_ref()?, i, false)));
+                let before = before.flatten().collect::<Vec<_>>();
+                let width = area.width.saturating_sub(2);
+                self.opened_at = Paragraph::new(before.clone()).wrap(Wrap { trim: false }).line_count(width) as u16;
+                let rest = sample.messages.iter().enumerate().skip(m).filter_map(|(i, message)| Some(self.message_lines(message.as_ref()?, i, i == m)));
+                before.into_iter().chain(rest.flatten()).collect()
+            }
+        };
+        let paragraph = Paragraph::new(lines).block(block).wrap(Wrap { trim: false }).scroll((self.scroll.unwrap_or(self.opened_at), 0));
+        frame.render_widget(paragraph, area);
+    }
+
+    // A message's header and content, with matches highlighted
+    fn message_lines(&self, message: &ChatMessage, index: usize, selected: bool) -> Vec<Line<'static>> {
+        let role = Style::new().fg(color(self.theme.role_color(&message.role))).add_modifier(Modifier::BOLD);
+        let marker = if selected { glyph("▶ ", "> ") } else { "" };
+        let mut lines = vec![Line::from(vec![Span::raw(marker), Span::styled(format!("[{}] {}", index, message.role), role)])];
+        let highlight = match self.theme.highlight.style {
+            HighlightStyle::Bold => Style::new().fg(color(self.theme.highlight.color)).add_modifier(Modifier::BOLD),
+            HighlightStyle::Background => Style::new().bg(color(self.theme.highlight.color)),
+        };
+        let matches = self.message_regex.map(|re| re.find_iter(&message.content).map(|m| m.range()).collect::<Vec<_>>()).unwrap_or_default();
+        let mut offset = 0;
+        for line in message.content.split('\n') {
+            let end = offset + line.len();
+            let mut spans = Vec::new();
+            let mut at = offset;
+            for range in matches.iter().filter(|range| range.start < end && range.end > offset) {
+                let (start, stop) = (range.start.max(at), range.end.min(end));
+                spans.push(Span::raw(message.content[at..start].to_string()));
+                spans.push(Span::styled(message.content[start..stop].to_string(), highlight));
+                at = stop;
+            }
+            spans.push(Span::raw(message.content[at..end].to_string()));
+            lines.push(Line::from(spans));
+            offset = end + 1;
+        }
+        lines.push(Line::default());
+        lines
+    }
+
+    fn draw_status(&self, frame: &mut Frame, area: Rect) {
+        let mut spans = vec![Span::raw("roles: ")];
+        for (key, role) in &ROLE_KEYS {
+            let style = match self.roles.contains(role) {
+                true => Style::new().fg(color(self.theme.role_color(role))).add_modifier(Modifier::BOLD),
+                false => Style::new().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT),
+            };
+            spans.push(Span::styled(format!("[{}]{} ", key, &role.to_string()[1..]), style));
+        }
+        spans.push(Span::styled(format!(" {} {}", glyph("↑↓", "up/down"), HELP), Style::new().fg(Color::DarkGray)));
+        frame.render_widget(Paragraph::new(Line::from(spans)), area);
+    }
+}
+
+fn color(color: colored::Color) -> Color {
+    use colored::Color as C;
+    match color {
+        C::Black => Color::Black,
+        C::Red => Color::Red,
+        C::Green => Color::Green,
+        C::Yellow => Color::Yellow,
+        C::Blue => Color::Blue,
+        C::Magenta => Color::Magenta,
+        C::Cyan => Color::Cyan,
+        C::White => Color::Gray,
+        C::BrightBlack => Color::DarkGray,
+        C::BrightRed => Color::LightRed,
+        C::BrightGreen => Color::LightGreen,
+        C::BrightYellow => Color::LightYellow,
+        C::BrightBlue => Color::LightBlue,
+        C::BrightMagenta => Color::LightMagenta,
+        C::BrightCyan => Color::LightCyan,
+        C::BrightWhite => Color::White,
+        C::TrueColor { r, g, b } => Color::Rgb(r, g, b),
+    }
+}
@@ -0,0 +1,43 @@
+use colored::*;
+use std::path::Path;
+
+use crate::inspect::{ChatMessageRole, EvalSample, ModelCall};
+use crate::theme::Theme;
+
+// Characters of each call's resulting message shown
+const PREVIEW_WIDTH: usize = 100;
+
+fn preview(text: &str) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match flat.char_indices().nth(PREVIEW_WIDTH) {
+        Some((end, _)) => format!("{}{}", &flat[..end], crate::glyphs::ellipsis()),
+        None => flat,
+    }
+}
+
+// Whether any model call used at least this many tokens
+pub fn has_turn_over(sample: &EvalSample, min_tokens: u64) -> bool {
+    sample.model_calls.iter().any(|call| call.total_tokens() >= min_tokens)
+}
+
+// Token usage of each model call in the sample with the message it produced;
+// with a minimum, only the calls that reached it
+pub fn display_turns(path: &Path, sample: &EvalSample, min_tokens: Option<u64>, theme: &Theme) {
+    let total = sample.model_calls.iter().map(ModelCall::total_tokens).sum::<u64>();
+    let summary = format!("[{} model calls, {} tokens]", sample.model_calls.len(), total).bold();
+    println!("\n{}", theme.header(&path.file_name().unwrap().to_string_lossy(), &sample.id, sample.epoch, summary));
+    if sample.model_calls.is_empty() {
+        println!("{}", "(no model events in this sample)".dimmed());
+    }
+
+    let role = format!("[{}]", ChatMessageRole::Assistant).color(theme.role_color(&ChatMessageRole::Assistant));
+    for (index, call) in sample.model_calls.iter().enumerate() {
+        if min_tokens.is_some_and(|min| call.total_tokens() < min) {
+            continue;
+        }
+        let tokens = format!("in {:>7}  out {:>6}", call.input_tokens, call.output_tokens);
+        let tokens = if min_tokens.is_some() { tokens.red().bold() } else { tokens.normal() };
+        let message = call.message.as_deref().map_or_else(|| "(no message)".dimmed().to_string(), preview);
+        println!("turn {:>3}  {}  {} {}", index + 1, tokens, role, message);
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+use regex::Regex;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::inspect::ChatMessageRole;
+use crate::IntFilter;
+
+// Ask until the answer passes `check`; an empty answer takes the default
+fn ask(question: &str, default: &str, check: impl Fn(&str) -> Result<()>) -> Result<String> {
+    let stdin = std::io::stdin();
+    loop {
+        match default {
+            "" => eprint!("{}: ", question),
+            default => eprint!("{} [{}]: ", question, default),
+        }
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        if stdin.lock().read_line(&mut answer)? == 0 {
+            bail!("wizard cancelled");
+        }
+        let answer = match answer.trim() {
+            "" => default.to_string(),
+            answer => answer.to_string(),
+        };
+        match check(&answer) {
+            Ok(()) => return Ok(answer),
+            Err(e) => eprintln!("  {}", e),
+        }
+    }
+}
+
+fn ask_yes_no(question: &str, default: bool) -> Result<bool> {
+    let answer = ask(question, if default { "Y/n" } else { "y/N" }, |answer| match answer {
+        "Y/n" | "y/N" | "y" | "Y" | "yes" | "n" | "N" | "no" => Ok(()),
+        _ => bail!("answer y or n"),
+    })?;
+    Ok(match answer.as_str() {
+        "Y/n" | "y/N" => default,
+        answer => answer.starts_with(['y', 'Y']),
+    })
+}
+
+// Quote an argument for a POSIX shell, leaving plain words alone
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./,:=@%+".contains(c);
+    match !arg.is_empty() && arg.chars().all(plain) {
+        true => arg.to_string(),
+        false => format!("'{}'", arg.replace('\'', r"'\''")),
+    }
+}
+
+// Prompt through the common search options, print the equivalent command line and
+// return its arguments if the user wants to run it
+pub fn run() -> Result<Option<Vec<String>>> {
+    let path = ask("Path to a .eval file or directory of logs", ".", |path| match Path::new(path).exists() {
+        true => Ok(()),
+        false => bail!("{} doesn't exist", path),
+    })?;
+    let pattern = ask("Regex to search message content for (empty for every message)", "", |pattern| {
+        Regex::new(pattern)?;
+        Ok(())
+    })?;
+    let roles = ask("Roles to search (comma-separated: system, user, assistant, tool; empty for all)", "", |roles| {
+        for role in roles.split(',').map(str::trim).filter(|role| !role.is_empty()) {
+            ChatMessageRole::from_str(role, true).map_err(anyhow::Error::msg)?;
+        }
+        Ok(())
+    })?;
+    let samples = ask("Regex for sample IDs (empty for all samples)", "", |samples| {
+        Regex::new(samples)?;
+        Ok(())
+    })?;
+    let epochs = ask("Epochs (all, a list like 1,2 or a range like 1-3)", "all", |epochs| {
+        IntFilter::from_str(epochs)?;
+        Ok(())
+    })?;
+    let summary_line = ask_yes_no("Show one line per sample with its score instead of every message?", false)?;
+
+    let mut args = vec![env!("CARGO_PKG_NAME").to_string(), path];
+    let epochs = if epochs == "all" { String::new() } else { epochs };
+    let options = [("-m", pattern), ("-r", roles.replace(' ', "")), ("-s", samples), ("-e", epochs)];
+    for (flag, value) in options.into_iter().filter(|(_, value)| !value.is_empty()) {
+        args.extend([flag.to_string(), value]);
+    }
+    if summary_line {
+        args.push("--summary-line".to_string());
+    }
+
+    eprintln!("\nEquivalent command:\n  {}\n", args.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>().join(" "));
+    Ok(ask_yes_no("Run it now?", true)?.then_some(args))
+}
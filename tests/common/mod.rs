@@ -0,0 +1,81 @@
+// Helpers shared by the integration tests: synthetic logs in a temporary directory, and
+// the binary run over them
+#![allow(dead_code)]
+
+use inspect_grep::fixtures::{self, FixtureOptions};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+pub struct Output {
+    pub code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Output {
+    pub fn lines(&self) -> Vec<&str> {
+        self.stdout.lines().collect()
+    }
+}
+
+// A temporary directory of logs, which also holds the log index so runs don't share one
+pub struct Logs {
+    pub dir: TempDir,
+}
+
+impl Logs {
+    pub fn new() -> Self {
+        Self { dir: TempDir::new().unwrap() }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    // Write a log of `options` as `name`
+    pub fn add(&self, name: &str, options: &FixtureOptions) -> PathBuf {
+        let path = self.dir.path().join(name);
+        fixtures::write(&path, options).unwrap();
+        path
+    }
+
+    pub fn run<I, S>(&self, args: I) -> Output
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        self.run_with_env(args, &[])
+    }
+
+    // Run with extra environment variables, e.g. `TMPDIR`
+    pub fn run_with_env<I, S>(&self, args: I, vars: &[(&str, &Path)]) -> Output
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let output = Command::new(env!("CARGO_BIN_EXE_inspect-grep"))
+            .args(args)
+            .envs(vars.iter().copied())
+            .env("XDG_CACHE_HOME", self.dir.path().join(".cache"))
+            .env("NO_COLOR", "1")
+            .env_remove("RUST_BACKTRACE")
+            .env_remove("RUST_LIB_BACKTRACE")
+            .output()
+            .unwrap();
+        Output { code: output.status.code(), stdout: String::from_utf8_lossy(&output.stdout).into_owned(), stderr: String::from_utf8_lossy(&output.stderr).into_owned() }
+    }
+}
+
+// Four samples over two epochs with two tool call rounds each: 1 and 2 answered
+// correctly, 3 incorrectly, and 4 ending in an error
+pub fn standard() -> FixtureOptions {
+    FixtureOptions { samples: 4, epochs: 2, turns: 2, accuracy: 0.67, errors: 1, ..FixtureOptions::default() }
+}
+
+// The total of what `--count` prints for each log, as `path:N` after its samples' counts;
+// nothing is printed when nothing matched
+pub fn count_total(output: &Output) -> usize {
+    assert_eq!(output.code, Some(0), "{}", output.stderr);
+    output.stdout.lines().filter(|line| line.matches(':').count() == 1).map(|line| line.rsplit(':').next().unwrap().parse::<usize>().unwrap()).sum()
+}
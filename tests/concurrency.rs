@@ -0,0 +1,44 @@
+mod common;
+
+use common::{standard, Logs};
+use serde_json::Value;
+
+#[test]
+fn concurrent_alias_ids_do_not_conflict() {
+    let logs = Logs::new();
+    let paths = ["a.eval", "b.eval", "c.eval", "d.eval"].map(|name| logs.add(name, &standard()));
+    let map = logs.path().join("aliases.json");
+    std::thread::scope(|scope| {
+        for path in &paths {
+            let (logs, map) = (&logs, &map);
+            scope.spawn(move || {
+                let output = logs.run([path.to_str().unwrap(), "-m", "answer is", "--alias-ids", map.to_str().unwrap()]);
+                assert_eq!(output.code, Some(0), "{}", output.stderr);
+            });
+        }
+    });
+    let map: Value = serde_json::from_slice(&std::fs::read(&map).unwrap()).unwrap();
+    let mut aliases = map["logs"].as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+    aliases.sort();
+    assert_eq!(aliases, ["log-1.eval", "log-2.eval", "log-3.eval", "log-4.eval"]);
+}
+
+#[test]
+fn concurrent_hot_first_searches_are_all_remembered() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let patterns = ["answer is", "compute", "bash", "precisely"];
+    std::thread::scope(|scope| {
+        for pattern in patterns {
+            let (logs, log) = (&logs, &log);
+            scope.spawn(move || {
+                let output = logs.run([log.to_str().unwrap(), "-m", pattern, "--hot-first"]);
+                assert_eq!(output.code, Some(0), "{}", output.stderr);
+            });
+        }
+    });
+    let hits: Value = serde_json::from_slice(&std::fs::read(logs.path().join(".cache/inspect-grep/hits.json")).unwrap()).unwrap();
+    let mut remembered = hits["queries"].as_array().unwrap().iter().map(|query| query["pattern"].as_str().unwrap().to_string()).collect::<Vec<_>>();
+    remembered.sort();
+    assert_eq!(remembered, ["answer is", "bash", "compute", "precisely"]);
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use common::{count_total, standard, Logs};
+use inspect_grep::fixtures;
+
+#[test]
+fn invalid_regex() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "-m", "("]);
+    assert_eq!(output.code, Some(1));
+    assert!(output.stderr.contains("Failed to compile message regex '('"), "{}", output.stderr);
+}
+
+#[test]
+fn invalid_role_pattern() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let unknown = logs.run([log.to_str().unwrap(), "--role-regex", "robot=hello"]);
+    assert_eq!(unknown.code, Some(2));
+    assert!(unknown.stderr.contains("unknown role 'robot'"), "{}", unknown.stderr);
+    let missing = logs.run([log.to_str().unwrap(), "--role-regex", "hello"]);
+    assert_eq!(missing.code, Some(2));
+    assert!(missing.stderr.contains("expected ROLES=PATTERN"), "{}", missing.stderr);
+}
+
+#[test]
+fn conflicting_flags() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "--assistant-only", "-r", "user"]);
+    assert_eq!(output.code, Some(2));
+    assert!(output.stderr.contains("cannot be used with"), "{}", output.stderr);
+}
+
+#[test]
+fn corrupt_log_is_skipped() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    std::fs::write(logs.path().join("corrupt.eval"), b"not a zip archive").unwrap();
+    let output = logs.run([logs.path().to_str().unwrap(), "-m", "answer is", "--count"]);
+    assert_eq!(output.code, Some(0));
+    assert_eq!(count_total(&output), 6);
+    assert!(output.stderr.contains("skipped 1 unreadable logs or samples"), "{}", output.stderr);
+    assert!(output.stderr.contains("corrupt.eval"), "{}", output.stderr);
+}
+
+#[test]
+fn corrupt_log_with_strict() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    std::fs::write(logs.path().join("corrupt.eval"), b"not a zip archive").unwrap();
+    let output = logs.run([logs.path().to_str().unwrap(), "-m", "answer is", "--strict"]);
+    assert_eq!(output.code, Some(2));
+    assert!(output.stderr.contains("failed to read") && output.stderr.contains("corrupt.eval"), "{}", output.stderr);
+}
+
+#[test]
+fn no_logs() {
+    let logs = Logs::new();
+    let output = logs.run([logs.path().to_str().unwrap(), "-m", "anything"]);
+    assert_eq!(output.code, Some(0));
+    assert!(output.stderr.contains("hint: no .eval files found"), "{}", output.stderr);
+}
+
+#[test]
+fn no_match_hint() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "--exclude-roles", "system,user,assistant,tool,unknown"]);
+    assert!(output.stderr.contains("--exclude-roles excludes every role"), "{}", output.stderr);
+    let output = logs.run([log.to_str().unwrap(), "--role-regex", "assistant=zzz"]);
+    assert!(output.stderr.contains("'zzz' in assistant messages"), "{}", output.stderr);
+}
+
+#[test]
+fn spill_failure_names_the_temp_dir() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let missing = logs.path().join("missing");
+    let output = logs.run_with_env([log.to_str().unwrap(), "-m", "answer is", "--max-memory", "0"], &[("TMPDIR", &missing)]);
+    assert_eq!(output.code, Some(1));
+    assert!(output.stderr.contains(&format!("Failed to create a file in {} to spill results to", missing.display())), "{}", output.stderr);
+}
+
+#[test]
+fn corrupt_log_is_skipped_by_subcommands() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let corrupt = logs.path().join("corrupt.eval");
+    std::fs::write(&corrupt, b"not a zip archive").unwrap();
+    let dir = logs.path().to_str().unwrap();
+    let out = logs.path().join("out");
+    let bundle = logs.path().join("bundle.zip");
+    let commands = [
+        vec!["scores", dir],
+        vec!["history", "1", dir],
+        vec!["export", dir, "-m", "answer is", "--dir", out.to_str().unwrap()],
+        vec!["bundle", dir, "-m", "answer is", "--out", bundle.to_str().unwrap()],
+        vec!["compare", log.to_str().unwrap(), corrupt.to_str().unwrap()],
+    ];
+    for command in commands {
+        let output = logs.run(&command);
+        assert_eq!(output.code, Some(0), "{:?}: {}", command, output.stderr);
+        assert!(output.stderr.contains("skipped 1 unreadable logs or samples") && output.stderr.contains("corrupt.eval"), "{:?}: {}", command, output.stderr);
+        let strict = logs.run(command.iter().chain(&["--strict"]));
+        assert_eq!(strict.code, Some(2), "{:?}: {}", command, strict.stderr);
+        assert!(strict.stderr.contains("failed to read") && strict.stderr.contains("corrupt.eval"), "{:?}: {}", command, strict.stderr);
+    }
+}
+
+#[test]
+fn quiet_still_reports_and_saves_the_index() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    std::fs::write(logs.path().join("corrupt.eval"), b"not a zip archive").unwrap();
+    let output = logs.run([logs.path().to_str().unwrap(), "-m", "no such text", "-q"]);
+    assert_eq!(output.code, Some(1));
+    assert!(output.stderr.contains("skipped 1 unreadable logs or samples"), "{}", output.stderr);
+    assert!(logs.path().join(".cache/inspect-grep/index").read_dir().unwrap().next().is_some());
+}
+
+#[test]
+fn failed_assertion_still_reports_skipped_logs() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    std::fs::write(logs.path().join("corrupt.eval"), b"not a zip archive").unwrap();
+    let output = logs.run([logs.path().to_str().unwrap(), "-m", "answer is", "--assert", "matches == 0"]);
+    assert_eq!(output.code, Some(1));
+    assert!(output.stderr.contains("assertion failed: matches == 0 (matches = 6)"), "{}", output.stderr);
+    assert!(output.stderr.contains("skipped 1 unreadable logs or samples") && output.stderr.contains("corrupt.eval"), "{}", output.stderr);
+}
+
+// The roles each log's index entry records, by file name
+fn indexed_roles(logs: &Logs) -> Vec<(String, serde_json::Value)> {
+    let mut entries = std::fs::read_dir(logs.path().join(".cache/inspect-grep/index"))
+        .unwrap()
+        .map(|entry| serde_json::from_slice::<serde_json::Value>(&std::fs::read(entry.unwrap().path()).unwrap()).unwrap())
+        .map(|entry| (entry["path"].as_str().unwrap().rsplit('/').next().unwrap().to_string(), entry["roles"].clone()))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
+
+#[test]
+fn roles_are_not_indexed_for_logs_with_skipped_samples() {
+    use std::io::Write;
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    std::fs::write(logs.path().join("corrupt.eval"), b"not a zip archive").unwrap();
+    // A log whose second sample is cut off mid-way
+    let options = standard();
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(logs.path().join("partial.eval")).unwrap());
+    writer.start_file("header.json", zip::write::FileOptions::default()).unwrap();
+    writer.write_all(&serde_json::to_vec(&fixtures::header(&options)).unwrap()).unwrap();
+    writer.start_file("samples/1_epoch_1.json", zip::write::FileOptions::default()).unwrap();
+    writer.write_all(&serde_json::to_vec(&fixtures::sample(&options, 0, 1)).unwrap()).unwrap();
+    writer.start_file("samples/2_epoch_1.json", zip::write::FileOptions::default()).unwrap();
+    writer.write_all(br#"{"id": "2", "epoch": 1, "messages": [{"role": "tool", "#).unwrap();
+    writer.finish().unwrap();
+
+    let output = logs.run([logs.path().to_str().unwrap(), "-m", "answer is"]);
+    assert!(output.stderr.contains("skipped 2 unreadable logs or samples"), "{}", output.stderr);
+    let roles = indexed_roles(&logs);
+    let expected = [("a.eval", serde_json::json!(["system", "user", "assistant", "tool"])), ("partial.eval", serde_json::Value::Null)];
+    assert_eq!(roles, expected.map(|(name, roles)| (name.to_string(), roles)));
+    // So the logs are still opened when searching for a role they may have
+    let tool = logs.run([logs.path().to_str().unwrap(), "-r", "tool", "-m", "."]);
+    assert!(tool.stderr.contains("skipped 2 unreadable logs or samples"), "{}", tool.stderr);
+}
+
+// /dev/full fails every write with "No space left on device"
+#[cfg(target_os = "linux")]
+#[test]
+fn labels_write_failure_is_an_error() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "-m", "answer is", "--rescore-cmd", "cat >/dev/null; echo ok", "--labels", "/dev/full"]);
+    assert_eq!(output.code, Some(1));
+    assert!(output.stderr.contains("Failed to write labels file /dev/full"), "{}", output.stderr);
+    assert!(!output.stderr.contains("panicked"), "{}", output.stderr);
+}
+
+#[test]
+fn source_map_needs_utf8() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "-m", "answer is", "--source-map", "--encoding", "latin1"]);
+    assert_eq!(output.code, Some(1));
+    assert!(output.stderr.contains("leave --encoding at utf8"), "{}", output.stderr);
+}
+
+#[test]
+fn source_map_skips_sharded_samples() {
+    use std::io::Write;
+    let logs = Logs::new();
+    let options = standard();
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(logs.path().join("sharded.eval")).unwrap());
+    writer.start_file("header.json", zip::write::FileOptions::default()).unwrap();
+    writer.write_all(&serde_json::to_vec(&fixtures::header(&options)).unwrap()).unwrap();
+    writer.start_file("samples/1_epoch_1.json", zip::write::FileOptions::default()).unwrap();
+    writer.write_all(&serde_json::to_vec(&fixtures::sample(&options, 0, 1)).unwrap()).unwrap();
+    // The second sample's JSON split in two
+    let json = serde_json::to_vec(&fixtures::sample(&options, 1, 1)).unwrap();
+    let (first, second) = json.split_at(json.len() / 2);
+    writer.start_file("samples/2_epoch_1.part0.json", zip::write::FileOptions::default()).unwrap();
+    writer.write_all(first).unwrap();
+    writer.start_file("samples/2_epoch_1.part1.json", zip::write::FileOptions::default()).unwrap();
+    writer.write_all(second).unwrap();
+    writer.finish().unwrap();
+
+    let log = logs.path().join("sharded.eval");
+    let mapped = logs.run([log.to_str().unwrap(), "-m", "answer is", "--source-map", "--count"]);
+    assert_eq!(count_total(&mapped), 1);
+    assert!(mapped.stderr.contains("split across 2 shards"), "{}", mapped.stderr);
+    let unmapped = logs.run([log.to_str().unwrap(), "-m", "answer is", "--count"]);
+    assert_eq!(count_total(&unmapped), 2);
+    assert!(!unmapped.stderr.contains("skipped"), "{}", unmapped.stderr);
+}
+
+#[test]
+fn invalid_sample_regex_is_an_error() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    let dir = logs.path().to_str().unwrap();
+    for args in [vec![dir, "-s", "("], vec!["stats", dir, "-s", "("], vec!["fingerprint", dir, "-s", "("]] {
+        let output = logs.run(args.clone());
+        assert_eq!(output.code, Some(1), "{:?}", args);
+        assert!(output.stderr.contains("Failed to compile sample regex '('"), "{:?}: {}", args, output.stderr);
+    }
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{count_total, standard, Logs};
+use inspect_grep::fixtures::FixtureOptions;
+
+// Messages in the standard log: samples 1-3 have a system prompt, the question, two
+// rounds of an assistant tool call and its output, and a final answer; sample 4 has
+// no final answer. Each sample has two epochs.
+
+fn count(logs: &Logs, args: &[&str]) -> usize {
+    let mut full = vec![logs.path().to_str().unwrap(), "--count"];
+    full.extend_from_slice(args);
+    count_total(&logs.run(full))
+}
+
+#[test]
+fn message_regex() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    assert_eq!(count(&logs, &["-m", "answer is"]), 6);
+    assert_eq!(count(&logs, &["-m", "ANSWER IS", "-i"]), 6);
+    assert_eq!(count(&logs, &["-m", "What", "-m", "precisely", "--all"]), 8);
+    assert_eq!(count(&logs, &["-m", "answer is", "-m", "compute"]), 22);
+}
+
+#[test]
+fn invert_match() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    assert_eq!(count(&logs, &["-r", "assistant", "-v", "-m", "answer is"]), 16);
+}
+
+#[test]
+fn roles() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    assert_eq!(count(&logs, &["-r", "tool"]), 16);
+    assert_eq!(count(&logs, &["-r", "system,user"]), 16);
+    assert_eq!(count(&logs, &["--assistant-only"]), 22);
+    assert_eq!(count(&logs, &["--user-only"]), 8);
+    assert_eq!(count(&logs, &["--exclude-roles", "tool,system,user"]), 22);
+    assert_eq!(count(&logs, &["-r", "assistant,tool", "--exclude-roles", "tool"]), 22);
+}
+
+#[test]
+fn role_regex() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    assert_eq!(count(&logs, &["--role-regex", "assistant=answer is"]), 6);
+    // The user's question mentions no answer, so only the assistant pattern finds it
+    assert_eq!(count(&logs, &["--role-regex", "user=answer is"]), 0);
+    assert_eq!(count(&logs, &["--role-regex", "user=What is", "--role-regex", "assistant=answer is"]), 14);
+    // -m applies to every role: the assistant announces its bash calls
+    assert_eq!(count(&logs, &["-m", "bash", "--role-regex", "user=What is"]), 24);
+}
+
+#[test]
+fn samples_and_epochs() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    assert_eq!(count(&logs, &["-s", "^2$"]), 14);
+    assert_eq!(count(&logs, &["-s", "^2$", "-e", "1"]), 7);
+    assert_eq!(count(&logs, &["-e", "2", "-m", "answer is"]), 3);
+}
+
+#[test]
+fn scores() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    assert_eq!(count(&logs, &["--score", "match=C", "-m", "answer is"]), 4);
+    assert_eq!(count(&logs, &["--score", "match=I", "-m", "answer is"]), 2);
+}
+
+#[test]
+fn errors() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    assert_eq!(count(&logs, &["--errors-only"]), 12);
+    assert_eq!(count(&logs, &["--no-errors", "-m", "answer is"]), 6);
+}
+
+#[test]
+fn tool_calls() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    assert_eq!(count(&logs, &["--tool-name", "bash"]), 16);
+    assert_eq!(count(&logs, &["--tool-name", "python"]), 0);
+}
+
+#[test]
+fn header_filters() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    logs.add("b.eval", &FixtureOptions { task: "other".to_string(), ..standard() });
+    assert_eq!(count(&logs, &["-m", "answer is"]), 12);
+    assert_eq!(count(&logs, &["-m", "answer is", "--task", "^other$"]), 6);
+}
+
+#[test]
+fn samples_without_match() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "--samples-without-match", "-m", "answer is"]);
+    let expected = [format!("{}:4:1", log.display()), format!("{}:4:2", log.display())];
+    assert_eq!(output.lines(), expected.iter().map(String::as_str).collect::<Vec<_>>());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{standard, Logs};
+use inspect_grep::fixtures::{self, FixtureOptions};
+use inspect_grep::inspect::ChatMessageRole;
+use inspect_grep::{search, SearchOptions};
+use regex::Regex;
+
+#[test]
+fn samples_are_deterministic() {
+    let options = standard();
+    assert_eq!(fixtures::sample(&options, 0, 1), fixtures::sample(&options, 0, 1));
+    assert_ne!(fixtures::sample(&options, 0, 1)["input"], fixtures::sample(&FixtureOptions { seed: 1, ..standard() }, 0, 1)["input"]);
+}
+
+#[test]
+fn sample_shape() {
+    let options = standard();
+    let correct = fixtures::sample(&options, 0, 2);
+    assert_eq!(correct["id"], "1");
+    assert_eq!(correct["epoch"], 2);
+    assert_eq!(correct["messages"].as_array().unwrap().len(), 7);
+    assert_eq!(correct["scores"]["match"]["value"], "C");
+    assert_eq!(correct["scores"]["match"]["answer"], correct["target"]);
+    assert_eq!(fixtures::sample(&options, 2, 1)["scores"]["match"]["value"], "I");
+    let errored = fixtures::sample(&options, 3, 1);
+    assert!(errored["error"]["message"].is_string());
+    assert_eq!(errored["messages"].as_array().unwrap().len(), 6);
+}
+
+#[test]
+fn header_describes_samples() {
+    let header = fixtures::header(&standard());
+    assert_eq!(header["eval"]["dataset"]["sample_ids"], serde_json::json!(["1", "2", "3", "4"]));
+    assert_eq!(header["results"]["total_samples"], 8);
+    assert_eq!(header["results"]["completed_samples"], 6);
+}
+
+#[test]
+fn written_logs_are_searchable() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let options = SearchOptions { message_regex: Some(Regex::new("answer is").unwrap()), roles: Some(vec![ChatMessageRole::Assistant]), ..SearchOptions::default() };
+    let matches = search(std::slice::from_ref(&log), options).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(matches.len(), 6);
+    assert!(matches.iter().all(|record| record.log == log && record.index == 6));
+    let ids = matches.iter().map(|record| (record.sample_id.as_str(), record.epoch)).collect::<Vec<_>>();
+    assert_eq!(ids, vec![("1", 1), ("1", 2), ("2", 1), ("2", 2), ("3", 1), ("3", 2)]);
+}
+
+#[test]
+fn search_reports_unreadable_logs() {
+    let logs = Logs::new();
+    logs.add("a.eval", &standard());
+    std::fs::write(logs.path().join("corrupt.eval"), b"not a zip archive").unwrap();
+    let options = SearchOptions { message_regex: Some(Regex::new("answer is").unwrap()), roles: Some(vec![ChatMessageRole::Assistant]), ..SearchOptions::default() };
+    let (matches, errors): (Vec<_>, Vec<_>) = search(&[logs.path().to_path_buf()], options).partition(Result::is_ok);
+    assert_eq!(matches.len(), 6);
+    assert_eq!(errors.len(), 1);
+    let error = format!("{:#}", errors.into_iter().next().unwrap().unwrap_err());
+    assert!(error.contains("corrupt.eval"), "{}", error);
+}
+
+#[test]
+fn subcommand() {
+    let logs = Logs::new();
+    let log = logs.path().join("cli.eval");
+    let output = logs.run(["fixtures", "-o", log.to_str().unwrap(), "--samples", "5", "--accuracy", "1", "--turns", "0"]);
+    assert_eq!(output.code, Some(0), "{}", output.stderr);
+    let options = SearchOptions { message_regex: Some(Regex::new("answer is").unwrap()), ..SearchOptions::default() };
+    let matches = search(&[log], options).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(matches.len(), 5);
+    // Without tool call rounds, the answer follows the question
+    assert!(matches.iter().all(|record| record.index == 2));
+}
@@ -0,0 +1,148 @@
+mod common;
+
+use common::{standard, Logs};
+use inspect_grep::fixtures::FixtureOptions;
+use regex::Regex;
+use serde_json::Value;
+
+#[test]
+fn jsonl() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "-m", "answer is", "-s", "^1$", "-e", "1", "--format", "jsonl"]);
+    let records = output.lines().into_iter().map(|line| serde_json::from_str::<Value>(line).unwrap()).collect::<Vec<_>>();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record["sample_id"], "1");
+    assert_eq!(record["epoch"], 1);
+    assert_eq!(record["role"], "assistant");
+    assert_eq!(record["index"], 6);
+    assert_eq!(record["entry"], "samples/1_epoch_1.json");
+    let content = record["content"].as_str().unwrap();
+    let (start, end) = (record["matches"][0][0].as_u64().unwrap() as usize, record["matches"][0][1].as_u64().unwrap() as usize);
+    assert_eq!(&content[start..end], "answer is");
+}
+
+#[test]
+fn text() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "-m", "answer is", "-s", "^3$", "-e", "2"]);
+    assert!(output.stdout.contains("a.eval sample 3 epoch 2 | [assistant] match I"), "{}", output.stdout);
+    assert!(output.stdout.contains("The answer is"));
+    assert!(output.stderr.contains("1 matches in 1 of 1 files"), "{}", output.stderr);
+}
+
+#[test]
+fn summary_line() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "-m", "answer is", "--summary-line"]);
+    let lines = output.lines();
+    assert_eq!(lines.len(), 6);
+    assert!(lines.iter().all(|line| line.contains("(1 matches) The answer is")), "{:?}", lines);
+}
+
+#[test]
+fn per_sample_modes_count_matches() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    // Both tool call rounds mention bash, in each of 4 samples × 2 epochs
+    for mode in ["--summary-line", "--heatmap", "--turns", "--emit-repro"] {
+        let output = logs.run([log.to_str().unwrap(), "-m", "bash", mode, "--assert", "matches == 16"]);
+        assert_eq!(output.code, Some(0), "{}: {}", mode, output.stderr);
+        assert!(output.stderr.contains("16 matches"), "{}: {}", mode, output.stderr);
+    }
+    let output = logs.run([log.to_str().unwrap(), "-m", "bash", "--summary-line", "--limit", "2"]);
+    assert_eq!(output.lines().len(), 2);
+    assert!(output.stderr.contains("16 matches (showing 1-2)"), "{}", output.stderr);
+}
+
+#[test]
+fn count() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "-m", "answer is", "--count"]);
+    let path = log.display();
+    let expected = (1..=3).flat_map(|id| (1..=2).map(move |epoch| (id, epoch))).map(|(id, epoch)| format!("{}:{}:{}:1", path, id, epoch)).chain([format!("{}:6", path)]).collect::<Vec<_>>();
+    assert_eq!(output.lines(), expected.iter().map(String::as_str).collect::<Vec<_>>());
+}
+
+#[test]
+fn quiet() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let found = logs.run([log.to_str().unwrap(), "-m", "answer is", "-q"]);
+    assert_eq!(found.code, Some(0));
+    assert!(found.stdout.is_empty());
+    let missing = logs.run([log.to_str().unwrap(), "-m", "no such text", "-q"]);
+    assert_eq!(missing.code, Some(1));
+}
+
+#[test]
+fn list_files() {
+    let logs = Logs::new();
+    let a = logs.add("a.eval", &standard());
+    let b = logs.add("b.eval", &standard());
+    let output = logs.run([logs.path().to_str().unwrap(), "--list-files"]);
+    let mut listed = output.lines();
+    listed.sort();
+    assert_eq!(listed, vec![a.to_str().unwrap(), b.to_str().unwrap()]);
+}
+
+#[test]
+fn scores_table() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run(["scores", log.to_str().unwrap()]);
+    let row = output.lines().into_iter().find(|line| line.starts_with("a.eval")).unwrap_or_else(|| panic!("{}", output.stdout));
+    assert!(row.contains("fixture") && row.contains("mockllm/model") && row.contains("6/8") && row.contains("accuracy 0.667"), "{}", row);
+}
+
+#[test]
+fn template() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "-m", "answer is \\d+", "-e", "1", "--template", "{file}:{sample}:{epoch}:{index}:{role}: {content:.10} [{match}] {{{score}}}"]);
+    let lines = output.lines();
+    assert_eq!(lines.len(), 3, "{}", output.stderr);
+    assert!(lines[0].starts_with("a.eval:1:1:6:assistant: The answer [answer is "), "{}", lines[0]);
+    assert!(lines[0].ends_with("] {match C (mean 1.0)}"), "{}", lines[0]);
+    // Tool output ends in a newline, which doesn't make it onto the line
+    let output = logs.run([log.to_str().unwrap(), "-r", "tool", "-s", "^1$", "-e", "1", "--template", "<{content}>"]);
+    assert!(output.lines().iter().all(|line| line.starts_with('<') && line.ends_with('>') && !line.ends_with(" >")), "{}", output.stdout);
+}
+
+#[test]
+fn invalid_template() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "--template", "{sample} {bogus}"]);
+    assert_eq!(output.code, Some(2));
+    assert!(output.stderr.contains("unknown placeholder '{bogus}'"), "{}", output.stderr);
+}
+
+#[test]
+fn rank() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &FixtureOptions { samples: 6, ..standard() });
+    let answers = logs.run([log.to_str().unwrap(), "-m", "answer is", "-e", "1", "--template", "{sample} {content}"]);
+    // Samples whose answer has three digits match both patterns, so they come first
+    let long_answers = answers.lines().into_iter().filter(|line| Regex::new(r"answer is \d{3}\.").unwrap().is_match(line)).map(|line| line.split(' ').next().unwrap().to_string()).collect::<Vec<_>>();
+    assert!(!long_answers.is_empty() && long_answers.len() < 5, "{}", answers.stdout);
+    let ranked = logs.run([log.to_str().unwrap(), "-m", "answer is", "-m", r"\b\d{3}\.", "-r", "assistant", "-e", "1", "--rank", "--template", "{sample}"]);
+    let order = ranked.lines();
+    assert_eq!(order.len(), 5, "{}", ranked.stderr);
+    assert_eq!(order[..long_answers.len()].iter().map(|id| id.to_string()).collect::<Vec<_>>(), long_answers);
+    let text = logs.run([log.to_str().unwrap(), "-m", "answer is", "--rank", "-e", "1"]);
+    assert!(text.stdout.contains("relevance "), "{}", text.stdout);
+}
+
+#[test]
+fn rank_needs_patterns() {
+    let logs = Logs::new();
+    let log = logs.add("a.eval", &standard());
+    let output = logs.run([log.to_str().unwrap(), "-r", "tool", "--rank"]);
+    assert_eq!(output.code, Some(1));
+    assert!(output.stderr.contains("--rank needs -m or --role-regex patterns"), "{}", output.stderr);
+}